@@ -0,0 +1,18 @@
+CREATE TABLE IF NOT EXISTS sign_jobs (
+    id TEXT PRIMARY KEY,
+    created_by TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    user_ids JSONB NOT NULL,
+    url TEXT NOT NULL,
+    event_url TEXT,
+    not_before TIMESTAMP NOT NULL,
+    deadline TIMESTAMP,
+    status TEXT NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    results JSONB,
+    error TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    executed_at TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_sign_jobs_status_not_before ON sign_jobs(status, not_before);
+CREATE INDEX IF NOT EXISTS idx_sign_jobs_created_by ON sign_jobs(created_by);
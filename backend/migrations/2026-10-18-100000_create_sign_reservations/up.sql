@@ -0,0 +1,12 @@
+-- Réservations "signez-moi" pour une journée de session (et optionnellement un événement intra précis)
+CREATE TABLE IF NOT EXISTS sign_reservations (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    date DATE NOT NULL,
+    event TEXT,
+    status TEXT NOT NULL DEFAULT 'pending',
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    fulfilled_at TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_sign_reservations_date_status ON sign_reservations(date, status);
@@ -0,0 +1,11 @@
+DROP INDEX IF EXISTS idx_edsquare_cookies_user_id;
+
+ALTER TABLE edsquare_cookies ADD COLUMN IF NOT EXISTS date DATE NOT NULL DEFAULT CURRENT_DATE;
+UPDATE edsquare_cookies SET date = updated_at::date;
+ALTER TABLE edsquare_cookies ALTER COLUMN date DROP DEFAULT;
+
+ALTER TABLE edsquare_cookies DROP COLUMN IF EXISTS expires_at;
+ALTER TABLE edsquare_cookies DROP COLUMN IF EXISTS last_verified_at;
+ALTER TABLE edsquare_cookies DROP COLUMN IF EXISTS updated_at;
+
+CREATE INDEX IF NOT EXISTS idx_edsquare_cookies_user_id_date ON edsquare_cookies(user_id, date);
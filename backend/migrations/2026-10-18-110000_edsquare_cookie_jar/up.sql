@@ -0,0 +1,18 @@
+-- Un seul jeu de cookies EDSquare par utilisateur, valide jusqu'à son expiration (et non plus par jour)
+
+-- On garde la ligne la plus récente de chaque utilisateur (les ids ULID sont croissants dans le temps)
+DELETE FROM edsquare_cookies a
+    USING edsquare_cookies b
+    WHERE a.user_id = b.user_id
+      AND (a.date < b.date OR (a.date = b.date AND a.id < b.id));
+
+ALTER TABLE edsquare_cookies ADD COLUMN IF NOT EXISTS expires_at TIMESTAMP;
+ALTER TABLE edsquare_cookies ADD COLUMN IF NOT EXISTS last_verified_at TIMESTAMP NOT NULL DEFAULT NOW();
+ALTER TABLE edsquare_cookies ADD COLUMN IF NOT EXISTS updated_at TIMESTAMP NOT NULL DEFAULT NOW();
+
+UPDATE edsquare_cookies SET last_verified_at = date::timestamp, updated_at = date::timestamp;
+
+DROP INDEX IF EXISTS idx_edsquare_cookies_user_id_date;
+ALTER TABLE edsquare_cookies DROP COLUMN IF EXISTS date;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_edsquare_cookies_user_id ON edsquare_cookies(user_id);
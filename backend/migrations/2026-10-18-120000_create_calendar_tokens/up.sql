@@ -0,0 +1,7 @@
+-- Jeton secret par utilisateur pour s'abonner au flux iCalendar de ses cours EDSquare
+CREATE TABLE IF NOT EXISTS calendar_tokens (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL UNIQUE REFERENCES users(id) ON DELETE CASCADE,
+    token TEXT NOT NULL UNIQUE,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,3 @@
+ALTER TABLE edsquare_credentials DROP COLUMN IF EXISTS suspended_at;
+ALTER TABLE edsquare_credentials DROP COLUMN IF EXISTS last_failed_login_at;
+ALTER TABLE edsquare_credentials DROP COLUMN IF EXISTS failed_login_count;
@@ -0,0 +1,4 @@
+-- Compteur d'échecs de connexion avec les identifiants sauvegardés : au-delà d'un seuil, la reconnexion automatique est suspendue
+ALTER TABLE edsquare_credentials ADD COLUMN IF NOT EXISTS failed_login_count INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE edsquare_credentials ADD COLUMN IF NOT EXISTS last_failed_login_at TIMESTAMP;
+ALTER TABLE edsquare_credentials ADD COLUMN IF NOT EXISTS suspended_at TIMESTAMP;
@@ -0,0 +1,2 @@
+-- Intensité (0-100) des variations appliquées à la signature avant chaque envoi à EDSquare ; 0 = signature envoyée telle quelle
+ALTER TABLE users ADD COLUMN IF NOT EXISTS signature_variation SMALLINT NOT NULL DEFAULT 0;
@@ -0,0 +1,6 @@
+ALTER TABLE users DROP COLUMN IF EXISTS pinned_signature_id;
+ALTER TABLE users DROP COLUMN IF EXISTS signature_rotation;
+ALTER TABLE user_signatures DROP COLUMN IF EXISTS last_used_at;
+ALTER TABLE user_signatures DROP COLUMN IF EXISTS use_count;
+ALTER TABLE user_signatures DROP COLUMN IF EXISTS enabled;
+ALTER TABLE user_signatures DROP COLUMN IF EXISTS label;
@@ -0,0 +1,9 @@
+-- Suivi d'utilisation des signatures (rotation déterministe) et retrait sans suppression
+ALTER TABLE user_signatures ADD COLUMN IF NOT EXISTS label TEXT;
+ALTER TABLE user_signatures ADD COLUMN IF NOT EXISTS enabled BOOLEAN NOT NULL DEFAULT TRUE;
+ALTER TABLE user_signatures ADD COLUMN IF NOT EXISTS use_count INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE user_signatures ADD COLUMN IF NOT EXISTS last_used_at TIMESTAMP;
+
+-- Stratégie de choix de la signature : round_robin, least_recently_used, random ou pinned (signature favorite)
+ALTER TABLE users ADD COLUMN IF NOT EXISTS signature_rotation TEXT NOT NULL DEFAULT 'random';
+ALTER TABLE users ADD COLUMN IF NOT EXISTS pinned_signature_id TEXT REFERENCES user_signatures(id) ON DELETE SET NULL;
@@ -0,0 +1 @@
+ALTER TABLE user_signatures DROP COLUMN IF EXISTS signature_path;
@@ -0,0 +1,2 @@
+-- Tracé vectoriel de la signature (données de chemin SVG dans le canevas 800x300), source des PNG rendus à la demande
+ALTER TABLE user_signatures ADD COLUMN IF NOT EXISTS signature_path TEXT;
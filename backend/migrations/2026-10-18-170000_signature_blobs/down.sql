@@ -0,0 +1,17 @@
+DROP INDEX IF EXISTS idx_user_signatures_user_id_content_hash;
+ALTER TABLE user_signatures ADD COLUMN IF NOT EXISTS signature_data TEXT;
+UPDATE user_signatures SET signature_data = 'data:image/png;base64,' || translate(encode(image, 'base64'), E'\n', '');
+ALTER TABLE user_signatures ALTER COLUMN signature_data SET NOT NULL;
+ALTER TABLE user_signatures DROP COLUMN IF EXISTS content_hash;
+ALTER TABLE user_signatures DROP COLUMN IF EXISTS image;
+
+-- Lignes mises de côté par up.sql (illisibles et doublons), avec leur épinglage
+INSERT INTO user_signatures (id, user_id, signature_data, created_at, label, enabled, use_count, last_used_at, signature_path)
+SELECT id, user_id, signature_data, created_at, label, enabled, use_count, last_used_at, signature_path
+FROM user_signatures_blob_backup
+ON CONFLICT (id) DO NOTHING;
+UPDATE users u
+SET pinned_signature_id = b.id
+FROM user_signatures_blob_backup b
+WHERE b.was_pinned AND u.id = b.user_id;
+DROP TABLE IF EXISTS user_signatures_blob_backup;
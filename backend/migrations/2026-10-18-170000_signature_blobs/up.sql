@@ -0,0 +1,76 @@
+-- Signatures stockées en binaire (PNG) avec une empreinte SHA-256 pour éviter les doublons
+ALTER TABLE user_signatures ADD COLUMN IF NOT EXISTS image BYTEA;
+ALTER TABLE user_signatures ADD COLUMN IF NOT EXISTS content_hash TEXT;
+
+-- Lignes retirées par cette migration (illisibles ou doublons), conservées pour down.sql.
+-- À supprimer à la main une fois la migration validée.
+CREATE TABLE IF NOT EXISTS user_signatures_blob_backup (
+    LIKE user_signatures INCLUDING DEFAULTS,
+    -- unreadable ou duplicate
+    reason TEXT NOT NULL,
+    -- Signature conservée à la place d'un doublon
+    kept_id TEXT,
+    was_pinned BOOLEAN NOT NULL DEFAULT FALSE,
+    backed_up_at TIMESTAMP NOT NULL DEFAULT now(),
+    PRIMARY KEY (id),
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+-- Décodage ligne par ligne : un base64 invalide ou une image qui n'est pas un PNG laisse `image` à NULL
+-- au lieu d'interrompre la migration
+DO $$
+DECLARE
+    sig RECORD;
+    decoded BYTEA;
+BEGIN
+    FOR sig IN
+        SELECT id, signature_data FROM user_signatures WHERE signature_data LIKE 'data:image/png;base64,%'
+    LOOP
+        BEGIN
+            decoded := decode(substring(sig.signature_data FROM length('data:image/png;base64,') + 1), 'base64');
+        EXCEPTION WHEN data_exception THEN
+            RAISE NOTICE 'signature %: base64 invalide, mise de côté', sig.id;
+            CONTINUE;
+        END;
+        IF substring(decoded FROM 1 FOR 8) = '\x89504e470d0a1a0a'::bytea THEN
+            UPDATE user_signatures SET image = decoded WHERE id = sig.id;
+        ELSE
+            RAISE NOTICE 'signature %: contenu qui n''est pas un PNG, mise de côté', sig.id;
+        END IF;
+    END LOOP;
+END $$;
+UPDATE user_signatures SET content_hash = encode(sha256(image), 'hex') WHERE image IS NOT NULL;
+
+-- Lignes illisibles (jamais envoyables) : mises de côté
+INSERT INTO user_signatures_blob_backup
+SELECT s.*, 'unreadable', NULL, EXISTS (SELECT 1 FROM users u WHERE u.pinned_signature_id = s.id), now()
+FROM user_signatures s
+WHERE s.image IS NULL;
+
+-- Doublons d'un même utilisateur : on garde la plus ancienne, les autres sont mises de côté
+CREATE TEMPORARY TABLE signature_duplicates AS
+SELECT s.id, kept.id AS kept_id
+FROM user_signatures s
+JOIN LATERAL (
+    SELECT k.id FROM user_signatures k
+    WHERE k.user_id = s.user_id AND k.content_hash = s.content_hash
+    ORDER BY k.created_at, k.id
+    LIMIT 1
+) kept ON TRUE
+WHERE s.image IS NOT NULL AND kept.id <> s.id;
+INSERT INTO user_signatures_blob_backup
+SELECT s.*, 'duplicate', d.kept_id, EXISTS (SELECT 1 FROM users u WHERE u.pinned_signature_id = s.id), now()
+FROM user_signatures s
+JOIN signature_duplicates d ON d.id = s.id;
+UPDATE users u
+SET pinned_signature_id = d.kept_id
+FROM signature_duplicates d
+WHERE u.pinned_signature_id = d.id;
+
+DELETE FROM user_signatures s USING user_signatures_blob_backup b WHERE s.id = b.id;
+DROP TABLE signature_duplicates;
+
+ALTER TABLE user_signatures ALTER COLUMN image SET NOT NULL;
+ALTER TABLE user_signatures ALTER COLUMN content_hash SET NOT NULL;
+ALTER TABLE user_signatures DROP COLUMN signature_data;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_user_signatures_user_id_content_hash ON user_signatures (user_id, content_hash);
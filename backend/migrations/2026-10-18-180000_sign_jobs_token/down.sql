@@ -0,0 +1,3 @@
+UPDATE sign_jobs SET url = COALESCE(token, '') WHERE url IS NULL;
+ALTER TABLE sign_jobs ALTER COLUMN url SET NOT NULL;
+ALTER TABLE sign_jobs DROP COLUMN token;
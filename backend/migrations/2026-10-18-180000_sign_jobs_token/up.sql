@@ -0,0 +1,6 @@
+-- Token envoyé à part de l'événement sélectionné (`event_url`) ; `url` ne contient plus que des liens
+ALTER TABLE sign_jobs ADD COLUMN token TEXT;
+ALTER TABLE sign_jobs ALTER COLUMN url DROP NOT NULL;
+
+-- Anciens jobs : un token seul était envoyé dans `url`
+UPDATE sign_jobs SET token = url, url = NULL WHERE url ~ '^\s*[A-Za-z0-9]{1,32}\s*$';
@@ -0,0 +1,133 @@
+use axum::{
+    Json,
+    extract::{Query, State},
+    response::IntoResponse,
+};
+use chrono::NaiveDate;
+use http::StatusCode;
+use tracing::error;
+
+use crate::{
+    api::{
+        attendance::{
+            models::{AttendanceEventsResponse, AttendanceReadinessResponse, ProviderEvents, ProviderStatus},
+            provider::AttendanceProvider,
+        },
+        auth::JwtClaims,
+        edsquare::EdsquareProvider,
+        sign::IntraProvider,
+        users::{User, get_user_by_id},
+    },
+    misc::GlobalState,
+};
+
+#[derive(serde::Deserialize)]
+pub struct AttendanceEventsQuery {
+    pub date: Option<String>,
+}
+
+fn provider_status<P: AttendanceProvider>(state: &GlobalState, provider: &P, user: &User) -> ProviderStatus {
+    ProviderStatus {
+        provider: provider.name().to_string(),
+        readiness: provider.readiness(state, user),
+    }
+}
+
+async fn provider_events<P: AttendanceProvider>(
+    state: &GlobalState,
+    provider: &P,
+    user: &User,
+    date: NaiveDate,
+) -> ProviderEvents {
+    match provider.fetch_events(state, user, date).await {
+        Ok(events) => ProviderEvents {
+            provider: provider.name().to_string(),
+            events,
+            error: None,
+        },
+        Err(e) => ProviderEvents {
+            provider: provider.name().to_string(),
+            events: vec![],
+            error: Some(e),
+        },
+    }
+}
+
+fn current_user(state: &GlobalState, jwt_user: &JwtClaims) -> Result<User, StatusCode> {
+    match get_user_by_id(state, &jwt_user.sub) {
+        Ok(Some(user)) => Ok(user),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            error!("Error fetching user {}: {:?}", jwt_user.sub, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/attendance/readiness",
+    description = "Whether the current user can be signed on each attendance platform, and what is missing otherwise",
+    responses(
+        (status = 200, description = "Readiness per platform", body = AttendanceReadinessResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "User not found"),
+    ),
+    tag = "Attendance"
+)]
+pub async fn get_readiness(State(state): State<GlobalState>, jwt_user: JwtClaims) -> impl IntoResponse {
+    let user = match current_user(&state, &jwt_user) {
+        Ok(user) => user,
+        Err(status) => return status.into_response(),
+    };
+    let response = AttendanceReadinessResponse {
+        providers: vec![
+            provider_status(&state, &IntraProvider, &user),
+            provider_status(&state, &EdsquareProvider, &user),
+        ],
+    };
+    (StatusCode::OK, Json(response)).into_response()
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/attendance/events",
+    description = "The current user's events for a day on each attendance platform",
+    params(
+        ("date" = Option<String>, Query, description = "Date YYYY-MM-DD (default: today)")
+    ),
+    responses(
+        (status = 200, description = "Events per platform", body = AttendanceEventsResponse),
+        (status = 400, description = "Invalid date format"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "User not found"),
+    ),
+    tag = "Attendance"
+)]
+pub async fn get_events(
+    State(state): State<GlobalState>,
+    jwt_user: JwtClaims,
+    Query(query): Query<AttendanceEventsQuery>,
+) -> impl IntoResponse {
+    let date = match &query.date {
+        Some(d) => match NaiveDate::parse_from_str(d, "%Y-%m-%d") {
+            Ok(date) => date,
+            Err(_) => {
+                return (StatusCode::BAD_REQUEST, "Format de date invalide. Utilisez YYYY-MM-DD.").into_response();
+            }
+        },
+        None => state.today(),
+    };
+    let user = match current_user(&state, &jwt_user) {
+        Ok(user) => user,
+        Err(status) => return status.into_response(),
+    };
+    let (intra, edsquare) = tokio::join!(
+        provider_events(&state, &IntraProvider, &user, date),
+        provider_events(&state, &EdsquareProvider, &user, date),
+    );
+    let response = AttendanceEventsResponse {
+        providers: vec![intra, edsquare],
+    };
+    (StatusCode::OK, Json(response)).into_response()
+}
@@ -0,0 +1,8 @@
+pub mod endpoints;
+pub mod models;
+mod provider;
+mod services;
+
+pub use models::{AttendanceEvent, Readiness, UserAttendanceResult, WebhookBilan};
+pub use provider::AttendanceProvider;
+pub use services::{eligible_users, get_routes, report_results, resolve_users, send_results_webhook, submit_for_users};
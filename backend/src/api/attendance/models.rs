@@ -0,0 +1,101 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Résultat d'une plateforme de présence pour un utilisateur, commun à toutes les plateformes.
+/// `detail` (aplati dans le JSON) porte ce qui est propre à la plateforme.
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct UserAttendanceResult<D> {
+    pub user_id: String,
+    pub username: String,
+    pub success: bool,
+    pub message: String,
+    #[serde(flatten)]
+    pub detail: D,
+}
+
+impl<D: Default> UserAttendanceResult<D> {
+    /// Échec avant toute soumission (utilisateur introuvable, délai dépassé, prérequis manquant...).
+    pub fn failure(user_id: &str, username: &str, message: impl Into<String>) -> Self {
+        Self {
+            user_id: user_id.to_string(),
+            username: username.to_string(),
+            success: false,
+            message: message.into(),
+            detail: D::default(),
+        }
+    }
+}
+
+/// L'utilisateur peut-il être signé sur la plateforme ?
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Readiness {
+    pub ready: bool,
+    /// Ce qui manque quand l'utilisateur n'est pas prêt
+    pub reason: Option<String>,
+}
+
+impl Readiness {
+    pub fn ready() -> Self {
+        Self { ready: true, reason: None }
+    }
+
+    pub fn not_ready(reason: impl Into<String>) -> Self {
+        Self {
+            ready: false,
+            reason: Some(reason.into()),
+        }
+    }
+}
+
+/// Cours ou événement d'une plateforme, dans un format commun.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AttendanceEvent {
+    /// Identifiant de l'événement sur la plateforme (event ID EDSquare, chemin intra...)
+    pub id: String,
+    pub title: String,
+    /// Début (RFC 3339)
+    pub start: String,
+    /// Fin (RFC 3339)
+    pub end: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ProviderStatus {
+    /// Plateforme (`intra`, `edsquare`)
+    pub provider: String,
+    #[serde(flatten)]
+    pub readiness: Readiness,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AttendanceReadinessResponse {
+    pub providers: Vec<ProviderStatus>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ProviderEvents {
+    pub provider: String,
+    pub events: Vec<AttendanceEvent>,
+    /// Erreur de la plateforme (session expirée, service injoignable...)
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AttendanceEventsResponse {
+    pub providers: Vec<ProviderEvents>,
+}
+
+/// Bilan envoyé au webhook d'une plateforme après une signature multi-utilisateurs.
+pub struct WebhookBilan {
+    /// Titre du message Discord (ex: "Bilan EDSquare")
+    pub title: String,
+    /// Champ `event` du payload JSON structuré
+    pub event: String,
+    pub initiated_by: String,
+    /// Lignes de contexte (libellé, valeur) : URL signée, codes validés...
+    pub context: Vec<(String, String)>,
+    pub validated: Vec<String>,
+    pub failed: Vec<(String, String)>,
+    /// Champs supplémentaires du payload JSON structuré
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
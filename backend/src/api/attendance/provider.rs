@@ -0,0 +1,60 @@
+use std::{future::Future, time::Duration};
+
+use chrono::NaiveDate;
+
+use crate::api::attendance::models::{AttendanceEvent, Readiness, UserAttendanceResult, WebhookBilan};
+use crate::api::users::User;
+use crate::misc::{GlobalState, ServerEvent, UserResultEvent};
+
+/// Plateforme de présence de l'école (intra, EDSquare...).
+/// Une nouvelle plateforme s'ajoute comme un module qui implémente ce trait : l'exécution multi-utilisateurs
+/// bornée, la liste des utilisateurs éligibles, le bilan SSE et le webhook sont communs (voir `attendance::services`).
+pub trait AttendanceProvider: Sync {
+    /// Ce qui est soumis pour chaque utilisateur (lien de signature intra, code EDSquare...)
+    type Submission: Sync;
+    /// Détail propre à la plateforme dans le résultat de chaque utilisateur
+    type Detail: Default + Send;
+
+    /// Identifiant court de la plateforme (`intra`, `edsquare`)
+    fn name(&self) -> &'static str;
+
+    /// Nombre d'utilisateurs traités en parallèle et temps maximal accordé à chacun.
+    fn limits(&self, state: &GlobalState) -> (usize, Duration);
+
+    /// L'utilisateur a-t-il tout ce qu'il faut (jeton, cookies, signature...) pour être signé ?
+    fn readiness(&self, state: &GlobalState, user: &User) -> Readiness;
+
+    /// Enregistre la présence d'un utilisateur.
+    fn submit_for_user(
+        &self,
+        state: &GlobalState,
+        user: &User,
+        submission: &Self::Submission,
+    ) -> impl Future<Output = UserAttendanceResult<Self::Detail>> + Send;
+
+    /// Cours de l'utilisateur pour une journée (fuseau de l'école).
+    fn fetch_events(
+        &self,
+        state: &GlobalState,
+        user: &User,
+        date: NaiveDate,
+    ) -> impl Future<Output = Result<Vec<AttendanceEvent>, String>> + Send;
+
+    /// Événement SSE diffusé après une signature multi-utilisateurs.
+    fn results_event(
+        &self,
+        initiated_by: &str,
+        submission: &Self::Submission,
+        results: &[UserAttendanceResult<Self::Detail>],
+        user_results: Vec<UserResultEvent>,
+    ) -> ServerEvent;
+
+    /// Bilan pour le webhook de la plateforme (`None` si aucun webhook n'est configuré ou rien à signaler).
+    fn webhook_bilan(
+        &self,
+        state: &GlobalState,
+        initiated_by: &str,
+        submission: &Self::Submission,
+        results: &[UserAttendanceResult<Self::Detail>],
+    ) -> Option<(String, WebhookBilan)>;
+}
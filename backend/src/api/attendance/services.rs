@@ -0,0 +1,228 @@
+use std::sync::Arc;
+
+use axum::{Router, routing::get};
+use tracing::{error, info, warn};
+use ulid::Ulid;
+
+use crate::api::attendance::models::{UserAttendanceResult, WebhookBilan};
+use crate::api::attendance::provider::AttendanceProvider;
+use crate::api::users::{User, get_all_users, get_users_by_ulids};
+use crate::misc::{GlobalState, UserResultEvent, run_bounded};
+
+/// Longueur maximale d'un message Discord.
+const DISCORD_MAX_CHARS: usize = 2000;
+
+pub fn get_routes(state: GlobalState) -> Router {
+    Router::new()
+        .route("/readiness", get(super::endpoints::get_readiness))
+        .route("/events", get(super::endpoints::get_events))
+        .with_state(state)
+}
+
+/// Charge les utilisateurs demandés ; les ids invalides ou inconnus donnent directement un résultat en échec.
+pub fn resolve_users<D: Default>(
+    state: &GlobalState,
+    user_ids: &[String],
+) -> Result<(Vec<User>, Vec<UserAttendanceResult<D>>), String> {
+    let mut failures = Vec::new();
+    let mut ulids = Vec::new();
+    for user_id in user_ids {
+        match Ulid::from_string(user_id) {
+            Ok(ulid) => ulids.push(ulid),
+            Err(e) => {
+                warn!("Identifiant utilisateur invalide '{}': {}", user_id, e);
+                failures.push(UserAttendanceResult::failure(user_id, "<invalid id>", "Invalid user id"));
+            }
+        }
+    }
+    let users = get_users_by_ulids(state, &ulids).map_err(|e| format!("Error fetching users: {}", e))?;
+    for ulid in &ulids {
+        let user_id = ulid.to_string();
+        if !users.iter().any(|u| u.id == user_id) {
+            warn!("Utilisateur introuvable: {}", user_id);
+            failures.push(UserAttendanceResult::failure(&user_id, "<unknown>", "User not found"));
+        }
+    }
+    Ok((users, failures))
+}
+
+/// Soumet la présence de chaque utilisateur sur la plateforme, en parallèle dans les limites de la plateforme.
+/// Les résultats sont dans l'ordre des utilisateurs.
+pub async fn submit_for_users<P: AttendanceProvider>(
+    state: &GlobalState,
+    provider: &P,
+    users: Vec<User>,
+    submission: &P::Submission,
+) -> Vec<UserAttendanceResult<P::Detail>> {
+    let (limit, timeout) = provider.limits(state);
+    let users: Vec<Arc<User>> = users.into_iter().map(Arc::new).collect();
+    run_bounded(
+        users,
+        limit,
+        timeout,
+        |user| async move { provider.submit_for_user(state, &user, submission).await },
+        |user| {
+            warn!("{}: délai dépassé pour {} ({})", provider.name(), user.username, user.id);
+            UserAttendanceResult::failure(&user.id, &user.username, format!("Délai dépassé ({}s)", timeout.as_secs()))
+        },
+    )
+    .await
+}
+
+/// Utilisateurs prêts à être signés sur la plateforme, triés par nom.
+pub fn eligible_users<P: AttendanceProvider>(state: &GlobalState, provider: &P) -> Result<Vec<User>, String> {
+    let users = get_all_users(state).map_err(|e| format!("Error fetching users: {}", e))?;
+    let mut eligible: Vec<User> = users
+        .into_iter()
+        .filter(|user| provider.readiness(state, user).ready)
+        .collect();
+    eligible.sort_by_key(|u| u.username.to_lowercase());
+    Ok(eligible)
+}
+
+/// Diffuse le bilan d'une signature multi-utilisateurs (SSE) et l'envoie au webhook de la plateforme.
+pub fn report_results<P: AttendanceProvider>(
+    state: &GlobalState,
+    provider: &P,
+    initiated_by: &str,
+    submission: &P::Submission,
+    results: &[UserAttendanceResult<P::Detail>],
+) {
+    let user_results = results
+        .iter()
+        .map(|r| UserResultEvent {
+            user_id: r.user_id.clone(),
+            username: r.username.clone(),
+            success: r.success,
+            message: r.message.clone(),
+        })
+        .collect();
+    state
+        .events
+        .publish(provider.results_event(initiated_by, submission, results, user_results));
+
+    send_results_webhook(state, provider, initiated_by, submission, results);
+}
+
+/// Envoie le bilan au webhook de la plateforme, sans le diffuser en SSE (l'appelant publie son propre événement).
+pub fn send_results_webhook<P: AttendanceProvider>(
+    state: &GlobalState,
+    provider: &P,
+    initiated_by: &str,
+    submission: &P::Submission,
+    results: &[UserAttendanceResult<P::Detail>],
+) {
+    if let Some((webhook_url, bilan)) = provider.webhook_bilan(state, initiated_by, submission, results) {
+        tokio::spawn(async move {
+            send_webhook_bilan(&webhook_url, &bilan).await;
+        });
+    }
+}
+
+/// Corps du webhook bilan.
+/// Discord : payload { "content": "message lisible" } (max 2000 caractères).
+/// Autres URLs : payload JSON structuré { "event", "initiated_by", "validated", "failed", ... }.
+fn webhook_body(webhook_url: &str, bilan: &WebhookBilan) -> serde_json::Value {
+    let is_discord = webhook_url.to_lowercase().contains("discord.com");
+    if is_discord {
+        let mut parts: Vec<String> = Vec::new();
+        parts.push(format!("**{}**", bilan.title));
+        parts.push(format!("**Lancé par :** {}", bilan.initiated_by));
+        for (label, value) in &bilan.context {
+            parts.push(format!("**{} :** {}", label, value));
+        }
+        if !bilan.validated.is_empty() {
+            parts.push(format!("✅ **Validés :** {}.", bilan.validated.join(", ")));
+        }
+        if !bilan.failed.is_empty() {
+            let failed_list: Vec<String> = bilan
+                .failed
+                .iter()
+                .map(|(u, m)| format!("{} ({})", u, m))
+                .collect();
+            parts.push(format!("❌ **Échecs :** {}.", failed_list.join(" ; ")));
+        }
+        let content = parts.join("\n");
+        let content = if content.chars().count() > DISCORD_MAX_CHARS {
+            format!("{}…", content.chars().take(DISCORD_MAX_CHARS - 1).collect::<String>())
+        } else {
+            content
+        };
+        serde_json::json!({ "content": content })
+    } else {
+        let mut body = serde_json::json!({
+            "event": bilan.event,
+            "initiated_by": bilan.initiated_by,
+            "validated": bilan.validated,
+            "failed": bilan.failed.iter().map(|(u, m)| serde_json::json!({ "username": u, "message": m })).collect::<Vec<_>>(),
+        });
+        if let Some(object) = body.as_object_mut() {
+            object.extend(bilan.extra.clone());
+        }
+        body
+    }
+}
+
+async fn send_webhook_bilan(webhook_url: &str, bilan: &WebhookBilan) {
+    let body = webhook_body(webhook_url, bilan);
+    let client = match reqwest::Client::builder().build() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Webhook {}: impossible de créer le client HTTP: {}", bilan.title, e);
+            return;
+        }
+    };
+    match client.post(webhook_url).json(&body).send().await {
+        Ok(res) if !res.status().is_success() => {
+            warn!("Webhook {}: statut {} pour {}", bilan.title, res.status(), webhook_url);
+        }
+        Err(e) => {
+            warn!("Webhook {}: erreur envoi vers {}: {}", bilan.title, webhook_url, e);
+        }
+        _ => {
+            info!(
+                "Webhook {}: bilan envoyé (validés: {}, échecs: {})",
+                bilan.title,
+                bilan.validated.len(),
+                bilan.failed.len()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bilan(failed: usize) -> WebhookBilan {
+        let mut extra = serde_json::Map::new();
+        extra.insert("url".to_string(), serde_json::json!("https://intra.epitech.eu/module/x"));
+        WebhookBilan {
+            title: "Bilan signature".to_string(),
+            event: "sign_multi".to_string(),
+            initiated_by: "alice".to_string(),
+            context: vec![("URL".to_string(), "https://intra.epitech.eu/module/x".to_string())],
+            validated: vec!["alice".to_string(), "bob".to_string()],
+            failed: (0..failed).map(|i| (format!("élève{}", i), "Token expiré".to_string())).collect(),
+            extra,
+        }
+    }
+
+    #[test]
+    fn structured_webhook_keeps_provider_fields() {
+        let body = webhook_body("https://example.com/hook", &bilan(1));
+        assert_eq!(body["event"], "sign_multi");
+        assert_eq!(body["url"], "https://intra.epitech.eu/module/x");
+        assert_eq!(body["validated"], serde_json::json!(["alice", "bob"]));
+        assert_eq!(body["failed"][0]["message"], "Token expiré");
+    }
+
+    #[test]
+    fn discord_webhook_is_truncated_on_char_boundary() {
+        let body = webhook_body("https://discord.com/api/webhooks/1/x", &bilan(200));
+        let content = body["content"].as_str().unwrap();
+        assert!(content.starts_with("**Bilan signature**\n**Lancé par :** alice\n**URL :** https://"));
+        assert_eq!(content.chars().count(), DISCORD_MAX_CHARS);
+        assert!(content.ends_with('…'));
+    }
+}
@@ -31,7 +31,7 @@ impl<S: Send + Sync> FromRequestParts<S> for JwtClaims {
 
         let auth_cookie = cookies
             .get("auth")
-            .ok_or_else(|| (StatusCode::UNAUTHORIZED, Json("Unauthorized")))?;
+            .ok_or((StatusCode::UNAUTHORIZED, Json("Unauthorized")))?;
 
         let jwt = match JwtClaims::from_jwt(auth_cookie.value()) {
             Ok(jwt) => jwt,
@@ -0,0 +1,232 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use rand::RngCore;
+use ulid::Ulid;
+
+use crate::api::edsquare::models::EdsquarePlanningEvent;
+use crate::misc::GlobalState;
+
+/// Jours passés inclus dans le flux iCalendar.
+pub const FEED_DAYS_BEFORE: i64 = 7;
+/// Jours à venir inclus dans le flux iCalendar.
+pub const FEED_DAYS_AFTER: i64 = 28;
+
+/// Longueur maximale d'une ligne iCalendar en octets (RFC 5545, 3.1), hors CRLF.
+const MAX_LINE_OCTETS: usize = 75;
+
+#[derive(diesel::Queryable, diesel::Selectable)]
+#[diesel(table_name = crate::schema::calendar_tokens)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[allow(dead_code)] // id et user_id ne servent qu'aux requêtes Diesel
+pub struct CalendarToken {
+    pub id: String,
+    pub user_id: String,
+    pub token: String,
+    pub created_at: NaiveDateTime,
+}
+
+fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Jeton du flux de l'utilisateur, s'il en a créé un.
+pub fn get_calendar_token(state: &GlobalState, user_id_param: &str) -> Result<Option<CalendarToken>, String> {
+    use crate::schema::calendar_tokens::dsl::*;
+
+    let mut conn = state
+        .get_db_conn()
+        .map_err(|_| "Failed to get database connection".to_string())?;
+    calendar_tokens
+        .filter(user_id.eq(user_id_param))
+        .select(CalendarToken::as_select())
+        .first(&mut conn)
+        .optional()
+        .map_err(|e| format!("Failed to read calendar token: {}", e))
+}
+
+/// Crée le jeton du flux, ou le remplace : l'ancienne URL cesse alors de fonctionner.
+pub fn rotate_calendar_token(state: &GlobalState, user_id_param: &str) -> Result<CalendarToken, String> {
+    use crate::schema::calendar_tokens::dsl::*;
+
+    let mut conn = state
+        .get_db_conn()
+        .map_err(|_| "Failed to get database connection".to_string())?;
+    let new_token = generate_token();
+    let now = Utc::now().naive_utc();
+    diesel::insert_into(calendar_tokens)
+        .values((
+            id.eq(Ulid::new().to_string()),
+            user_id.eq(user_id_param),
+            token.eq(&new_token),
+            created_at.eq(now),
+        ))
+        .on_conflict(user_id)
+        .do_update()
+        .set((token.eq(&new_token), created_at.eq(now)))
+        .returning(CalendarToken::as_returning())
+        .get_result(&mut conn)
+        .map_err(|e| format!("Failed to save calendar token: {}", e))
+}
+
+/// Révoque le flux de l'utilisateur. Retourne false s'il n'en avait pas.
+pub fn delete_calendar_token(state: &GlobalState, user_id_param: &str) -> Result<bool, String> {
+    use crate::schema::calendar_tokens::dsl::*;
+
+    let mut conn = state
+        .get_db_conn()
+        .map_err(|_| "Failed to get database connection".to_string())?;
+    diesel::delete(calendar_tokens.filter(user_id.eq(user_id_param)))
+        .execute(&mut conn)
+        .map(|deleted| deleted > 0)
+        .map_err(|e| format!("Failed to delete calendar token: {}", e))
+}
+
+/// Utilisateur propriétaire d'un jeton de flux.
+pub fn find_calendar_token_owner(state: &GlobalState, token_param: &str) -> Result<Option<String>, String> {
+    use crate::schema::calendar_tokens::dsl::*;
+
+    let mut conn = state
+        .get_db_conn()
+        .map_err(|_| "Failed to get database connection".to_string())?;
+    calendar_tokens
+        .filter(token.eq(token_param))
+        .select(user_id)
+        .first(&mut conn)
+        .optional()
+        .map_err(|e| format!("Failed to read calendar token: {}", e))
+}
+
+/// Chemin public du flux pour un jeton.
+pub fn feed_path(token: &str) -> String {
+    format!("/api/calendar/{}.ics", token)
+}
+
+/// Génère le calendrier iCalendar (RFC 5545) des cours EDSquare.
+/// Les UID reprennent l'id EDSquare du cours pour que les agendas mettent à jour l'événement au lieu de le dupliquer.
+pub fn render_ics(calendar_name: &str, events: &[EdsquarePlanningEvent], now: DateTime<Utc>) -> String {
+    let dtstamp = format_utc(now);
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//epi-sign//EDSquare//FR".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        "METHOD:PUBLISH".to_string(),
+        format!("X-WR-CALNAME:{}", escape_text(calendar_name)),
+    ];
+
+    for event in events {
+        let (Some(start), Some(end)) = (parse_utc(&event.start), parse_utc(&event.end)) else {
+            continue;
+        };
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:edsquare-{}@epi-sign", event.id));
+        lines.push(format!("DTSTAMP:{}", dtstamp));
+        lines.push(format!("DTSTART:{}", format_utc(start)));
+        lines.push(format!("DTEND:{}", format_utc(end)));
+        lines.push(format!("SUMMARY:{}", escape_text(&event.title)));
+        let description: Vec<&str> = [event.target.as_deref(), event.event_type.as_deref()]
+            .into_iter()
+            .flatten()
+            .filter(|s| !s.is_empty())
+            .collect();
+        if !description.is_empty() {
+            lines.push(format!("DESCRIPTION:{}", escape_text(&description.join("\n"))));
+        }
+        lines.push("END:VEVENT".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    lines.iter().map(|line| fold_line(line) + "\r\n").collect()
+}
+
+fn parse_utc(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value).ok().map(|dt| dt.with_timezone(&Utc))
+}
+
+fn format_utc(dt: DateTime<Utc>) -> String {
+    dt.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Échappe une valeur TEXT (antislash, point-virgule, virgule, retours à la ligne).
+fn escape_text(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Replie une ligne à 75 octets sans couper de caractère UTF-8 ; les lignes de continuation commencent par une espace.
+fn fold_line(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + line.len() / MAX_LINE_OCTETS * 3);
+    let mut line_len = 0;
+    for c in line.chars() {
+        if line_len + c.len_utf8() > MAX_LINE_OCTETS {
+            folded.push_str("\r\n ");
+            line_len = 1;
+        }
+        folded.push(c);
+        line_len += c.len_utf8();
+    }
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn event(id: i64, title: &str) -> EdsquarePlanningEvent {
+        EdsquarePlanningEvent {
+            id,
+            title: title.to_string(),
+            target: Some("Salle 204".to_string()),
+            start: "2026-10-19T09:00:00+02:00".to_string(),
+            end: "2026-10-19T12:30:00+02:00".to_string(),
+            event_type: Some("course".to_string()),
+            registrable: Some(true),
+        }
+    }
+
+    #[test]
+    fn renders_events_with_stable_uid_and_utc_times() {
+        let now = Utc.with_ymd_and_hms(2026, 10, 18, 8, 0, 0).unwrap();
+        let ics = render_ics("EDSquare", &[event(4242, "Algo; partie 1, TD")], now);
+
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+        assert!(ics.contains("\r\nUID:edsquare-4242@epi-sign\r\n"));
+        assert!(ics.contains("\r\nDTSTAMP:20261018T080000Z\r\n"));
+        assert!(ics.contains("\r\nDTSTART:20261019T070000Z\r\n"));
+        assert!(ics.contains("\r\nDTEND:20261019T103000Z\r\n"));
+        assert!(ics.contains("\r\nSUMMARY:Algo\\; partie 1\\, TD\r\n"));
+        assert!(ics.contains("\r\nDESCRIPTION:Salle 204\\ncourse\r\n"));
+    }
+
+    #[test]
+    fn skips_events_with_unreadable_dates() {
+        let mut broken = event(1, "Cassé");
+        broken.start = "demain".to_string();
+        let ics = render_ics("EDSquare", &[broken], Utc::now());
+        assert!(!ics.contains("BEGIN:VEVENT"));
+    }
+
+    #[test]
+    fn folds_long_lines_on_char_boundaries() {
+        let line = format!("SUMMARY:{}", "é".repeat(60));
+        let folded = fold_line(&line);
+        for part in folded.split("\r\n") {
+            assert!(part.len() <= MAX_LINE_OCTETS, "{} octets", part.len());
+        }
+        assert_eq!(folded.replace("\r\n ", ""), line);
+    }
+}
@@ -0,0 +1,101 @@
+/// Adresse d'EDSquare utilisée si `EDSQUARE_BASE_URL` n'est pas définie.
+pub const DEFAULT_EDSQUARE_BASE_URL: &str = "https://app.edsquare.fr";
+
+/// Client HTTP d'EDSquare : adresse de base configurable (tests, proxy) et connexions réutilisées.
+/// Les clients partagés n'ont pas de cookie store : les cookies de chaque utilisateur sont passés explicitement
+/// dans le header `Cookie`. Seule la connexion utilise un client dédié (`session`) qui suit les cookies reçus.
+#[derive(Clone)]
+pub struct EdsquareClient {
+    base_url: String,
+    cookie_domain: String,
+    http: reqwest::Client,
+    no_redirect: reqwest::Client,
+}
+
+impl EdsquareClient {
+    /// Échoue si `base_url` n'est pas une URL http(s) absolue (ex: `EDSQUARE_BASE_URL` sans schéma).
+    pub fn new(base_url: &str) -> Result<Self, String> {
+        let base_url = base_url.trim().trim_end_matches('/');
+        let base = reqwest::Url::parse(base_url)
+            .map_err(|e| format!("Invalid EDSquare base URL '{}': {}", base_url, e))?;
+        let cookie_domain = match base.host_str() {
+            Some(host) if matches!(base.scheme(), "http" | "https") => host.to_string(),
+            _ => return Err(format!("Invalid EDSquare base URL '{}': expected http(s)://host", base_url)),
+        };
+
+        let http = Self::builder()
+            .build()
+            .map_err(|e| format!("Failed to build HTTP client: {}", e))?;
+        // Ne suit pas les redirections pour détecter les sessions expirées (redirection vers /users/sign_in)
+        let no_redirect = Self::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .map_err(|e| format!("Failed to build HTTP client: {}", e))?;
+        Ok(Self {
+            base_url: base_url.to_string(),
+            cookie_domain,
+            http,
+            no_redirect,
+        })
+    }
+
+    fn builder() -> reqwest::ClientBuilder {
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert(
+            reqwest::header::USER_AGENT,
+            reqwest::header::HeaderValue::from_static("Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/144.0.0.0 Safari/537.36"),
+        );
+        headers.insert(
+            reqwest::header::ACCEPT,
+            reqwest::header::HeaderValue::from_static("*/*"),
+        );
+        headers.insert(
+            reqwest::header::ACCEPT_LANGUAGE,
+            reqwest::header::HeaderValue::from_static("fr-FR,fr;q=0.9,en-US;q=0.8,en;q=0.7"),
+        );
+        reqwest::Client::builder().default_headers(headers)
+    }
+
+    /// Origine EDSquare (headers `Origin`), sans slash final.
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// URL absolue d'un chemin EDSquare (ex: `/apps/classrooms`).
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    /// Domaine attribué aux cookies reçus sans attribut `Domain`.
+    pub fn cookie_domain(&self) -> &str {
+        &self.cookie_domain
+    }
+
+    pub fn http(&self) -> &reqwest::Client {
+        &self.http
+    }
+
+    pub fn no_redirect(&self) -> &reqwest::Client {
+        &self.no_redirect
+    }
+
+    /// Client avec cookie store pour une connexion : un nouveau à chaque fois, pour ne pas mélanger les sessions.
+    pub fn session(&self) -> Result<reqwest::Client, String> {
+        Self::builder()
+            .cookie_store(true)
+            .build()
+            .map_err(|e| format!("Failed to build HTTP client: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cookie_domain_comes_from_the_base_url() {
+        assert!(EdsquareClient::new("app.edsquare.fr").is_err());
+        assert_eq!(EdsquareClient::new(DEFAULT_EDSQUARE_BASE_URL).unwrap().cookie_domain(), "app.edsquare.fr");
+        assert_eq!(EdsquareClient::new("http://127.0.0.1:4000/").unwrap().cookie_domain(), "127.0.0.1");
+    }
+}
@@ -1,4 +1,4 @@
-use axum::{Json, extract::{State, Query}, response::IntoResponse};
+use axum::{Json, extract::{Path, State, Query}, response::IntoResponse};
 use http::StatusCode;
 use tracing::{error, debug, info, warn};
 use ulid::Ulid;
@@ -6,14 +6,15 @@ use chrono::NaiveDate;
 
 use crate::{
     api::{
+        attendance::{eligible_users, report_results, resolve_users, submit_for_users},
         auth::JwtClaims,
-        users::{get_user_by_id, get_all_users, get_random_signature_for_user, get_user_signatures},
+        users::{get_user_by_id, get_signature_for_submission, has_enabled_signature, record_signature_use},
         edsquare::models::{
             ValidateEdsquarePayload,
             ValidateEdsquareResponse,
             ValidateEdsquareMultiPayload,
             ValidateEdsquareMultiResponse,
-            EdsquareUserValidationResult,
+            EdsquareValidationDetail,
             SaveEdsquareCookiesPayload,
             LoginEdsquarePayload,
             LoginEdsquareResponse,
@@ -24,6 +25,28 @@ use crate::{
             PlanningEventsForUsersPayload,
             PlanningEventsForUsersResponse,
             UserPlanningEvents,
+            EdsquarePlanningRangeResponse,
+            CalendarTokenResponse,
+            EventMatchStatus,
+            MatchPlanningEventsPayload,
+            MatchPlanningEventsResponse,
+            UserEventMatch,
+            SignatureVerification,
+            EdsquareSessionAlertsResponse,
+        },
+        edsquare::keepalive::{session_alerts, session_health_for},
+        edsquare::matching::{EventMatch, MatchReference, resolve_planning_event_for_user},
+        edsquare::provider::{CONTRADICTED_MESSAGE, EdsquareProvider, EdsquareSubmission, SIGNATURE_SIZE},
+        edsquare::calendar::{
+            CalendarToken,
+            FEED_DAYS_AFTER,
+            FEED_DAYS_BEFORE,
+            delete_calendar_token,
+            feed_path,
+            find_calendar_token_owner,
+            get_calendar_token,
+            render_ics,
+            rotate_calendar_token,
         },
         edsquare::services::{
             validate_edsquare_code,
@@ -31,11 +54,14 @@ use crate::{
             login_edsquare,
             login_edsquare_with_saved,
             get_edsquare_cookies,
-            get_edsquare_credentials,
+            get_edsquare_credential_status,
             fetch_planning_events,
+            fetch_planning_events_range,
+            verify_edsquare_signature,
+            MAX_PLANNING_RANGE_DAYS,
         },
     },
-    misc::GlobalState,
+    misc::{GlobalState, run_bounded},
 };
 
 #[derive(serde::Deserialize)]
@@ -52,7 +78,10 @@ pub struct PlanningEventsQuery {
         (status = 200, description = "Code validated successfully", body = ValidateEdsquareResponse),
         (status = 400, description = "Invalid code or missing signature"),
         (status = 401, description = "Unauthorized"),
-        (status = 404, description = "User not found or signature not set"),
+        (status = 404, description = "User not found, signature not set, EDSquare session expired, event not found or no current event"),
+        (status = 409, description = "No planning_event_id and several current events match"),
+        (status = 410, description = "Code no longer valid"),
+        (status = 502, description = "EDSquare unreachable or unexpected response"),
     ),
     tag = "EDSquare"
 )]
@@ -73,9 +102,10 @@ pub async fn validate_edsquare(
         },
     };
 
-    let signature = match get_random_signature_for_user(&state, &user.id) {
+    let (width, height) = SIGNATURE_SIZE;
+    let signature = match get_signature_for_submission(&state, &user, width, height).await {
         Ok(Some(sig)) => {
-            info!("Signature choisie au hasard pour user {}: {} caractères", user.username, sig.len());
+            info!("Signature choisie pour user {}: {} caractères", user.username, sig.data_url.len());
             sig
         },
         Ok(None) => {
@@ -88,19 +118,56 @@ pub async fn validate_edsquare(
         },
     };
 
-    if payload.planning_event_id.is_empty() {
-        return (StatusCode::BAD_REQUEST, "planning_event_id is required").into_response();
-    }
+    // Sans event ID : le cours ouvert à l'inscription en cours (ou qui commence dans la marge) du planning de l'utilisateur
+    let (planning_event_id, planning_event) = match payload.planning_event_id.as_deref().map(str::trim) {
+        Some(event_id) if !event_id.is_empty() => (event_id.to_string(), None),
+        _ => {
+            let reference = MatchReference::At {
+                at: chrono::Utc::now(),
+                grace: state.edsquare_event_grace,
+            };
+            match resolve_planning_event_for_user(&state, &user.id, &reference).await {
+                Ok(EventMatch::Matched(event)) => {
+                    info!("Cours EDSquare retenu pour {}: {} #{}", user.username, event.title, event.id);
+                    (event.id.to_string(), Some(event))
+                }
+                Ok(EventMatch::Unmatched) => {
+                    return (
+                        StatusCode::NOT_FOUND,
+                        "Aucun cours EDSquare en cours ou sur le point de commencer : précisez planning_event_id",
+                    )
+                        .into_response();
+                }
+                Ok(other) => {
+                    return (StatusCode::CONFLICT, other.failure_message().unwrap_or_default()).into_response();
+                }
+                Err(e) => {
+                    error!("Error fetching EDSquare planning for {}: {}", user.username, e);
+                    return e.into_response();
+                }
+            }
+        }
+    };
 
-    match validate_edsquare_code(&payload.code, &payload.planning_event_id, &signature, &jwt_user.sub.to_string(), &state).await {
-        Ok(response) => (StatusCode::OK, Json(response)).into_response(),
+    match validate_edsquare_code(&payload.code, &planning_event_id, &signature.data_url, &jwt_user.sub.to_string(), &state).await {
+        Ok(response) => {
+            let mut response = ValidateEdsquareResponse { planning_event, ..response };
+            if payload.verify {
+                let verification = verify_edsquare_signature(&state, &user.id, &planning_event_id).await;
+                if verification == SignatureVerification::Contradicted {
+                    response.success = false;
+                    response.message = CONTRADICTED_MESSAGE.to_string();
+                }
+                response.verification = Some(verification);
+            }
+            if response.success && let Err(e) = record_signature_use(&state, &signature.signature_id) {
+                warn!("Utilisation de la signature {} non comptée: {:?}", signature.signature_id, e);
+            }
+            (StatusCode::OK, Json(response)).into_response()
+        }
         Err(e) => {
             error!("Error validating EDSquare code: {}", e);
-            if e.contains("cookie") || e.contains("session") {
-                (StatusCode::NOT_FOUND, e).into_response()
-            } else {
-                (StatusCode::INTERNAL_SERVER_ERROR, format!("Error validating code: {}", e)).into_response()
-            }
+            e.into_response()
         }
     }
 }
@@ -132,182 +199,46 @@ pub async fn validate_edsquare_multi(
     if !use_per_user_codes && payload.code.is_empty() {
         return (StatusCode::BAD_REQUEST, "code is required").into_response();
     }
-    if payload.planning_event_id.is_empty() && payload.user_planning_event_ids.as_ref().map(|m| m.is_empty()).unwrap_or(true) {
-        return (StatusCode::BAD_REQUEST, "planning_event_id or user_planning_event_ids is required").into_response();
+    let auto_match = payload.auto_match || payload.reference_event.is_some();
+    if !auto_match
+        && payload.planning_event_id.is_empty()
+        && payload.user_planning_event_ids.as_ref().map(|m| m.is_empty()).unwrap_or(true)
+    {
+        return (StatusCode::BAD_REQUEST, "planning_event_id, user_planning_event_ids or auto_match is required").into_response();
     }
     if payload.user_ids.is_empty() {
         return (StatusCode::BAD_REQUEST, "user_ids must not be empty").into_response();
     }
-
-    let mut results: Vec<EdsquareUserValidationResult> = Vec::new();
-
-    for user_id in payload.user_ids.iter() {
-        // Convertir l'ID (string) en Ulid pour réutiliser get_user_by_id
-        let ulid = match Ulid::from_string(user_id) {
-            Ok(id) => id,
-            Err(e) => {
-                warn!("Invalid user_id '{}' in multi-validate payload: {}", user_id, e);
-                results.push(EdsquareUserValidationResult {
-                    user_id: user_id.clone(),
-                    username: "<invalid id>".to_string(),
-                    success: false,
-                    message: "Invalid user id".to_string(),
-                });
-                continue;
-            }
-        };
-
-        // Récupérer l'utilisateur
-        let user = match get_user_by_id(&state, &ulid) {
-            Ok(Some(user)) => user,
-            Ok(None) => {
-                warn!("User not found for multi-validate: {}", user_id);
-                results.push(EdsquareUserValidationResult {
-                    user_id: user_id.clone(),
-                    username: "<unknown>".to_string(),
-                    success: false,
-                    message: "User not found".to_string(),
-                });
-                continue;
-            }
-            Err(e) => {
-                error!("Error fetching user {} for multi-validate: {:?}", user_id, e);
-                results.push(EdsquareUserValidationResult {
-                    user_id: user_id.clone(),
-                    username: "<error>".to_string(),
-                    success: false,
-                    message: "Error fetching user".to_string(),
-                });
-                continue;
-            }
-        };
-
-        // Choisir une signature au hasard parmi celles de l'utilisateur
-        let signature = match get_random_signature_for_user(&state, &user.id) {
-            Ok(Some(sig)) => sig,
-            Ok(None) => {
-                warn!("Aucune signature pour user {} ({}) in multi-validate", user.username, user.id);
-                results.push(EdsquareUserValidationResult {
-                    user_id: user.id.clone(),
-                    username: user.username.clone(),
-                    success: false,
-                    message: "Signature not set. Please create a signature first.".to_string(),
-                });
-                continue;
-            }
-            Err(_) => {
-                results.push(EdsquareUserValidationResult {
-                    user_id: user.id.clone(),
-                    username: user.username.clone(),
-                    success: false,
-                    message: "Error fetching signature.".to_string(),
-                });
-                continue;
-            }
-        };
-
-        // Event ID pour cet utilisateur : override par user_planning_event_ids si présent
-        let planning_event_id = payload
-            .user_planning_event_ids
-            .as_ref()
-            .and_then(|m| m.get(&user.id))
-            .map(|s| s.as_str())
-            .unwrap_or(payload.planning_event_id.as_str());
-
-        // Code pour cet utilisateur : override par user_codes si présent (cours différents = code différent)
-        let code = payload
-            .user_codes
-            .as_ref()
-            .and_then(|m| m.get(&user.id))
-            .map(|s| s.as_str())
-            .unwrap_or(payload.code.as_str());
-
-        if code.len() != 6 {
-            results.push(EdsquareUserValidationResult {
-                user_id: user.id.clone(),
-                username: user.username.clone(),
-                success: false,
-                message: format!("Le code doit contenir 6 chiffres, reçu: {} caractères", code.len()),
-            });
-            continue;
+    let reference = if auto_match {
+        match MatchReference::from_payload(payload.reference_event.as_ref(), chrono::Utc::now(), state.edsquare_event_grace) {
+            Ok(reference) => Some(reference),
+            Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
         }
+    } else {
+        None
+    };
 
-        // Appeler la logique de validation existante pour cet utilisateur
-        match validate_edsquare_code(
-            code,
-            planning_event_id,
-            &signature,
-            &user.id,
-            &state,
-        ).await {
-            Ok(_resp) => {
-                info!("EDSquare code validated successfully for user {} ({})", user.username, user.id);
-                results.push(EdsquareUserValidationResult {
-                    user_id: user.id.clone(),
-                    username: user.username.clone(),
-                    success: true,
-                    message: "Code validé avec succès".to_string(),
-                });
-            }
-            Err(e) => {
-                error!("Error validating EDSquare code for user {} ({}): {}", user.username, user.id, e);
-                results.push(EdsquareUserValidationResult {
-                    user_id: user.id.clone(),
-                    username: user.username.clone(),
-                    success: false,
-                    message: e,
-                });
-            }
+    let (users, mut results) = match resolve_users::<EdsquareValidationDetail>(&state, &payload.user_ids) {
+        Ok(resolved) => resolved,
+        Err(e) => {
+            error!("validate-multi: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Error fetching users").into_response();
         }
-    }
-
-    if results.is_empty() {
-        return (
-            StatusCode::BAD_REQUEST,
-            "Aucune validation n'a pu être effectuée (utilisateurs invalides ou sans signature)".to_string(),
-        )
-            .into_response();
-    }
-
-    let global_success = results.iter().all(|r| r.success);
-    let validated: Vec<String> = results.iter().filter(|r| r.success).map(|r| r.username.clone()).collect();
-    let failed: Vec<(String, String)> = results.iter().filter(|r| !r.success).map(|r| (r.username.clone(), r.message.clone())).collect();
-    let validated_codes: Vec<String> = results
-        .iter()
-        .filter(|r| r.success)
-        .map(|r| {
-            payload
-                .user_codes
-                .as_ref()
-                .and_then(|m| m.get(&r.user_id))
-                .cloned()
-                .unwrap_or_else(|| payload.code.clone())
-        })
-        .collect::<std::collections::HashSet<_>>()
-        .into_iter()
-        .collect();
+    };
+    let submission = EdsquareSubmission { payload, reference };
+    results.extend(submit_for_users(&state, &EdsquareProvider, users, &submission).await);
+    // Résultats dans l'ordre de la demande
+    results.sort_by_key(|r| submission.payload.user_ids.iter().position(|id| *id == r.user_id));
 
     let initiator_username = get_user_by_id(&state, &jwt_user.sub)
         .ok()
         .flatten()
         .map(|u| u.username)
         .unwrap_or_else(|| jwt_user.sub.to_string());
-
-    if let Some(ref webhook_url) = state.edsquare_webhook_url {
-        if !validated.is_empty() {
-            let url = webhook_url.clone();
-            let validated_clone = validated.clone();
-            let failed_clone = failed.clone();
-            let codes_clone = validated_codes.clone();
-            let initiator = initiator_username.clone();
-            tokio::spawn(async move {
-                send_edsquare_webhook_bilan(&url, global_success, &validated_clone, &failed_clone, &codes_clone, &initiator).await;
-            });
-        }
-    }
+    report_results(&state, &EdsquareProvider, &initiator_username, &submission, &results);
 
     let response = ValidateEdsquareMultiResponse {
-        global_success,
+        global_success: results.iter().all(|r| r.success),
         results,
     };
 
@@ -317,7 +248,7 @@ pub async fn validate_edsquare_multi(
 #[utoipa::path(
     post,
     path = "/api/edsquare/cookies",
-    description = "Save EDSquare cookies for today",
+    description = "Save the current user's EDSquare cookies (replaces the previous cookie jar, valid until the cookies expire)",
     request_body = SaveEdsquareCookiesPayload,
     responses(
         (status = 200, description = "Cookies saved successfully"),
@@ -349,6 +280,7 @@ pub async fn save_edsquare_cookies_endpoint(
         (status = 200, description = "Login successful and cookies saved", body = LoginEdsquareResponse),
         (status = 400, description = "Invalid credentials"),
         (status = 401, description = "Unauthorized"),
+        (status = 502, description = "EDSquare unreachable or unexpected response"),
     ),
     tag = "EDSquare"
 )]
@@ -361,22 +293,17 @@ pub async fn login_edsquare_endpoint(
         return (StatusCode::BAD_REQUEST, "Email et mot de passe requis").into_response();
     }
 
-    match login_edsquare(&payload.email, &payload.password, &jwt_user.sub.to_string(), &state).await {
+    let user_id_str = jwt_user.sub.to_string();
+    let lock = state.edsquare_login_lock(&user_id_str);
+    let _guard = lock.lock().await;
+    match login_edsquare(&payload.email, &payload.password, &user_id_str, &state).await {
         Ok(response) => {
             info!("Login EDSquare réussi pour: {}", payload.email);
             (StatusCode::OK, Json(response)).into_response()
         },
         Err(e) => {
             error!("Error logging in to EDSquare: {}", e);
-            // Toutes les erreurs de connexion doivent retourner un status d'erreur
-            // pour que le frontend puisse les détecter
-            if e.contains("identifiants invalides") 
-                || e.contains("Échec de la connexion") 
-                || e.contains("invalides") {
-                (StatusCode::BAD_REQUEST, e).into_response()
-            } else {
-                (StatusCode::INTERNAL_SERVER_ERROR, format!("Erreur lors de la connexion: {}", e)).into_response()
-            }
+            e.into_response()
         }
     }
 }
@@ -388,7 +315,9 @@ pub async fn login_edsquare_endpoint(
     responses(
         (status = 200, description = "Login successful and cookies saved", body = LoginEdsquareResponse),
         (status = 400, description = "No saved credentials or invalid credentials"),
+        (status = 423, description = "Auto-reconnect suspended after too many refused logins: log in again with the password"),
         (status = 401, description = "Unauthorized"),
+        (status = 502, description = "EDSquare unreachable or unexpected response"),
     ),
     tag = "EDSquare"
 )]
@@ -397,6 +326,8 @@ pub async fn login_edsquare_with_saved_endpoint(
     jwt_user: JwtClaims,
 ) -> impl IntoResponse {
     let user_id_str = jwt_user.sub.to_string();
+    let lock = state.edsquare_login_lock(&user_id_str);
+    let _guard = lock.lock().await;
 
     match login_edsquare_with_saved(&user_id_str, &state).await {
         Ok(response) => {
@@ -411,20 +342,7 @@ pub async fn login_edsquare_with_saved_endpoint(
                 "Error logging in to EDSquare with saved credentials for user {}: {}",
                 user_id_str, e
             );
-            if e.contains("Aucun identifiant EDSquare enregistré") {
-                (StatusCode::BAD_REQUEST, e).into_response()
-            } else if e.contains("identifiants invalides") || e.contains("Échec de la connexion") {
-                (StatusCode::BAD_REQUEST, e).into_response()
-            } else {
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    format!(
-                        "Erreur lors de la connexion avec identifiants sauvegardés: {}",
-                        e
-                    ),
-                )
-                    .into_response()
-            }
+            e.into_response()
         }
     }
 }
@@ -461,9 +379,7 @@ pub async fn get_edsquare_status(
         },
     };
 
-    let has_signature = get_user_signatures(&state, &user_id_str)
-        .map(|sigs| !sigs.is_empty())
-        .unwrap_or(false);
+    let has_signature = has_enabled_signature(&state, &user_id_str).unwrap_or(false);
     info!("Statut signature pour {}: {}", user.username, has_signature);
 
     let has_cookies = match get_edsquare_cookies(&state, &user_id_str) {
@@ -481,11 +397,12 @@ pub async fn get_edsquare_status(
         },
     };
 
-    let has_saved_credentials = get_edsquare_credentials(&state, &user_id_str)
-        .ok()
-        .and_then(|o| o)
-        .is_some();
-    if has_saved_credentials && !has_cookies {
+    let credential_status = get_edsquare_credential_status(&state, &user_id_str).ok().flatten();
+    let has_saved_credentials = credential_status.is_some();
+    let credentials_suspended = credential_status.as_ref().is_some_and(|s| s.suspended);
+    if credentials_suspended {
+        warn!("Reconnexion EDSquare automatique suspendue pour {} : mot de passe à ressaisir", user.username);
+    } else if has_saved_credentials && !has_cookies {
         info!("Identifiants EDSquare sauvegardés pour {} : reconnexion auto possible", user.username);
     }
 
@@ -493,7 +410,13 @@ pub async fn get_edsquare_status(
         has_signature,
         has_cookies,
         has_saved_credentials,
-        is_ready: has_signature && (has_cookies || has_saved_credentials),
+        credentials_suspended,
+        failed_login_count: credential_status.as_ref().map(|s| s.failed_login_count).unwrap_or(0),
+        last_failed_login_at: credential_status
+            .and_then(|s| s.last_failed_login_at)
+            .map(|at| at.and_utc().to_rfc3339()),
+        is_ready: has_signature && (has_cookies || (has_saved_credentials && !credentials_suspended)),
+        session_health: session_health_for(&state, &user_id_str),
     };
 
     info!("Statut EDSquare final pour {}: has_signature={}, has_cookies={}, is_ready={}", 
@@ -502,6 +425,26 @@ pub async fn get_edsquare_status(
     (StatusCode::OK, Json(response)).into_response()
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/edsquare/session-alerts",
+    description = "Users whose EDSquare session could not be refreshed by the keep-alive job (they need to log in again before class)",
+    responses(
+        (status = 200, description = "Users to warn", body = EdsquareSessionAlertsResponse),
+        (status = 401, description = "Unauthorized"),
+    ),
+    tag = "EDSquare"
+)]
+pub async fn get_edsquare_session_alerts(
+    State(state): State<GlobalState>,
+    _jwt_user: JwtClaims,
+) -> impl IntoResponse {
+    let response = EdsquareSessionAlertsResponse {
+        alerts: session_alerts(&state),
+    };
+    (StatusCode::OK, Json(response)).into_response()
+}
+
 #[utoipa::path(
     get,
     path = "/api/edsquare/eligible-users",
@@ -516,64 +459,20 @@ pub async fn get_edsquare_eligible_users(
     State(state): State<GlobalState>,
     _jwt_user: JwtClaims,
 ) -> impl IntoResponse {
-    // Récupérer tous les utilisateurs
-    let users = match get_all_users(&state) {
-        Ok(users) => users,
+    let eligible = match eligible_users(&state, &EdsquareProvider) {
+        Ok(users) => users
+            .into_iter()
+            .map(|user| EdsquareEligibleUser {
+                id: user.id,
+                username: user.username,
+            })
+            .collect(),
         Err(e) => {
             error!("Error fetching users for EDSquare eligibility: {}", e);
             return (StatusCode::INTERNAL_SERVER_ERROR, "Error fetching users").into_response();
         }
     };
 
-    let mut eligible: Vec<EdsquareEligibleUser> = Vec::new();
-
-    for user in users {
-        let has_signature = get_user_signatures(&state, &user.id)
-            .map(|sigs| !sigs.is_empty())
-            .unwrap_or(false);
-        if !has_signature {
-            continue;
-        }
-
-        let user_id_str = user.id.clone();
-        let has_cookies = match get_edsquare_cookies(&state, &user_id_str) {
-            Ok(Some(cookies)) => {
-                info!(
-                    "EDSquare cookies found for user {} ({}): {} cookies valides",
-                    user.username,
-                    user.id,
-                    cookies.len()
-                );
-                true
-            }
-            Ok(None) => false,
-            Err(e) => {
-                error!(
-                    "Error fetching EDSquare cookies for user {} ({}): {}",
-                    user.username,
-                    user.id,
-                    e
-                );
-                false
-            }
-        };
-
-        let has_saved_credentials = get_edsquare_credentials(&state, &user_id_str)
-            .ok()
-            .and_then(|o| o)
-            .is_some();
-
-        if has_cookies || has_saved_credentials {
-            eligible.push(EdsquareEligibleUser {
-                id: user.id.clone(),
-                username: user.username.clone(),
-            });
-        }
-    }
-
-    // Optionnel : ordonner par username pour plus de lisibilité
-    eligible.sort_by(|a, b| a.username.to_lowercase().cmp(&b.username.to_lowercase()));
-
     let response = EdsquareEligibleUsersResponse { users: eligible };
     (StatusCode::OK, Json(response)).into_response()
 }
@@ -589,7 +488,8 @@ pub async fn get_edsquare_eligible_users(
         (status = 200, description = "Events retrieved successfully", body = EdsquarePlanningEventsResponse),
         (status = 400, description = "Invalid date format"),
         (status = 401, description = "Unauthorized"),
-        (status = 404, description = "No EDSquare cookies"),
+        (status = 404, description = "No EDSquare cookies or session expired"),
+        (status = 502, description = "EDSquare unreachable or unexpected response"),
     ),
     tag = "EDSquare"
 )]
@@ -609,7 +509,7 @@ pub async fn get_planning_events(
                     .into_response()
             }
         },
-        None => chrono::Utc::now().date_naive(),
+        None => state.today(),
     };
 
     match fetch_planning_events(&state, &jwt_user.sub.to_string(), date).await {
@@ -617,13 +517,7 @@ pub async fn get_planning_events(
             let response = EdsquarePlanningEventsResponse { events };
             (StatusCode::OK, Json(response)).into_response()
         }
-        Err(e) => {
-            if e.contains("cookie") || e.contains("Session EDSquare expirée") {
-                (StatusCode::NOT_FOUND, e).into_response()
-            } else {
-                (StatusCode::INTERNAL_SERVER_ERROR, e).into_response()
-            }
-        }
+        Err(e) => e.into_response(),
     }
 }
 
@@ -663,117 +557,339 @@ pub async fn get_planning_events_for_users(
         }
     };
 
-    let mut user_events: Vec<UserPlanningEvents> = Vec::new();
+    let user_events: Vec<UserPlanningEvents> = run_bounded(
+        payload.user_ids.clone(),
+        state.edsquare_concurrency,
+        state.edsquare_user_timeout,
+        |user_id| {
+            let state = &state;
+            async move { planning_events_for_user(state, user_id, date).await }
+        },
+        |user_id| UserPlanningEvents {
+            user_id,
+            username: "<timeout>".to_string(),
+            events: vec![],
+            error: Some(format!("Délai dépassé ({}s)", state.edsquare_user_timeout.as_secs())),
+        },
+    )
+    .await;
 
-    for user_id in &payload.user_ids {
-        let ulid = match Ulid::from_string(user_id) {
-            Ok(id) => id,
-            Err(_) => {
-                user_events.push(UserPlanningEvents {
-                    user_id: user_id.clone(),
-                    username: "<invalid id>".to_string(),
-                    events: vec![],
-                    error: Some("ID utilisateur invalide".to_string()),
-                });
-                continue;
-            }
-        };
-
-        let username = match get_user_by_id(&state, &ulid) {
-            Ok(Some(u)) => u.username.clone(),
-            Ok(None) => "<unknown>".to_string(),
-            Err(_) => "<error>".to_string(),
-        };
-
-        match fetch_planning_events(&state, user_id, date).await {
-            Ok(events) => {
-                user_events.push(UserPlanningEvents {
-                    user_id: user_id.clone(),
-                    username,
-                    events,
-                    error: None,
-                });
-            }
-            Err(e) => {
-                user_events.push(UserPlanningEvents {
-                    user_id: user_id.clone(),
-                    username,
-                    events: vec![],
-                    error: Some(e),
-                });
-            }
+    let response = PlanningEventsForUsersResponse { user_events };
+    (StatusCode::OK, Json(response)).into_response()
+}
+
+/// Cours EDSquare d'un utilisateur pour `get_planning_events_for_users`.
+async fn planning_events_for_user(state: &GlobalState, user_id: String, date: NaiveDate) -> UserPlanningEvents {
+    let ulid = match Ulid::from_string(&user_id) {
+        Ok(id) => id,
+        Err(_) => {
+            return UserPlanningEvents {
+                user_id,
+                username: "<invalid id>".to_string(),
+                events: vec![],
+                error: Some("ID utilisateur invalide".to_string()),
+            };
         }
+    };
+
+    let username = match get_user_by_id(state, &ulid) {
+        Ok(Some(u)) => u.username.clone(),
+        Ok(None) => "<unknown>".to_string(),
+        Err(_) => "<error>".to_string(),
+    };
+
+    match fetch_planning_events(state, &user_id, date).await {
+        Ok(events) => UserPlanningEvents {
+            user_id,
+            username,
+            events,
+            error: None,
+        },
+        Err(e) => UserPlanningEvents {
+            user_id,
+            username,
+            events: vec![],
+            error: Some(e.to_string()),
+        },
     }
+}
 
-    let response = PlanningEventsForUsersResponse { user_events };
-    (StatusCode::OK, Json(response)).into_response()
+#[derive(serde::Deserialize)]
+pub struct PlanningRangeQuery {
+    pub from: String,
+    pub to: String,
 }
 
-/// Envoie un webhook bilan après validation EDSquare multi-utilisateurs.
-/// Discord : payload { "content": "message lisible" } (max 2000 caractères), inclut le(s) code(s) validé(s) et l'initiateur.
-/// Autres URLs : payload JSON structuré { "event", "initiated_by", "global_success", "validated", "failed", "codes" }.
-async fn send_edsquare_webhook_bilan(
-    webhook_url: &str,
-    global_success: bool,
-    validated: &[String],
-    failed: &[(String, String)],
-    validated_codes: &[String],
-    initiated_by: &str,
-) {
-    let is_discord = webhook_url.to_lowercase().contains("discord.com");
-    let body: serde_json::Value = if is_discord {
-        let mut parts: Vec<String> = Vec::new();
-        parts.push("**Bilan EDSquare**".to_string());
-        parts.push(format!("**Lancé par :** {}", initiated_by));
-        if !validated_codes.is_empty() {
-            parts.push(format!(
-                "**Code(s) validé(s) :** {}",
-                validated_codes.join(", ")
-            ));
+#[utoipa::path(
+    get,
+    path = "/api/edsquare/planning-events/range",
+    description = "Get the current user's EDSquare planning events from `from` to `to` (inclusive, YYYY-MM-DD). Uses the per-day cache.",
+    params(
+        ("from" = String, Query, description = "First day (YYYY-MM-DD)"),
+        ("to" = String, Query, description = "Last day, inclusive (YYYY-MM-DD)"),
+    ),
+    responses(
+        (status = 200, description = "Events of the range", body = EdsquarePlanningRangeResponse),
+        (status = 400, description = "Invalid dates or range too long"),
+        (status = 404, description = "EDSquare session expired"),
+        (status = 502, description = "EDSquare unreachable or unexpected response"),
+    ),
+    tag = "EDSquare"
+)]
+pub async fn get_planning_events_range(
+    State(state): State<GlobalState>,
+    jwt_user: JwtClaims,
+    Query(query): Query<PlanningRangeQuery>,
+) -> impl IntoResponse {
+    let (Ok(from), Ok(to)) = (
+        NaiveDate::parse_from_str(&query.from, "%Y-%m-%d"),
+        NaiveDate::parse_from_str(&query.to, "%Y-%m-%d"),
+    ) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            "Format de date invalide. Utilisez YYYY-MM-DD.",
+        )
+            .into_response();
+    };
+    if to < from {
+        return (StatusCode::BAD_REQUEST, "La date de fin précède la date de début.").into_response();
+    }
+    if (to - from).num_days() >= MAX_PLANNING_RANGE_DAYS {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("Période trop longue (maximum {} jours).", MAX_PLANNING_RANGE_DAYS),
+        )
+            .into_response();
+    }
+
+    match fetch_planning_events_range(&state, &jwt_user.sub.to_string(), from, to).await {
+        Ok(events) => {
+            let response = EdsquarePlanningRangeResponse {
+                from: from.to_string(),
+                to: to.to_string(),
+                events,
+            };
+            (StatusCode::OK, Json(response)).into_response()
         }
-        if !validated.is_empty() {
-            parts.push(format!("✅ **Validés :** {}.", validated.join(", ")));
+        Err(e) => e.into_response(),
+    }
+}
+
+fn calendar_token_response(calendar_token: CalendarToken) -> CalendarTokenResponse {
+    CalendarTokenResponse {
+        feed_path: feed_path(&calendar_token.token),
+        token: calendar_token.token,
+        created_at: calendar_token.created_at.and_utc().to_rfc3339(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/edsquare/calendar/token",
+    description = "Get the current user's iCalendar feed token",
+    responses(
+        (status = 200, description = "Feed token", body = CalendarTokenResponse),
+        (status = 404, description = "No feed token yet"),
+        (status = 401, description = "Unauthorized"),
+    ),
+    tag = "EDSquare"
+)]
+pub async fn get_calendar_token_endpoint(
+    State(state): State<GlobalState>,
+    jwt_user: JwtClaims,
+) -> impl IntoResponse {
+    match get_calendar_token(&state, &jwt_user.sub.to_string()) {
+        Ok(Some(calendar_token)) => (StatusCode::OK, Json(calendar_token_response(calendar_token))).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Aucun flux calendrier").into_response(),
+        Err(e) => {
+            error!("Erreur lecture jeton calendrier: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, e).into_response()
         }
-        if !failed.is_empty() {
-            let failed_list: Vec<String> = failed
-                .iter()
-                .map(|(u, m)| format!("{} ({})", u, m))
-                .collect();
-            parts.push(format!("❌ **Échecs :** {}.", failed_list.join(" ; ")));
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/edsquare/calendar/token",
+    description = "Create or rotate the current user's iCalendar feed token (the previous feed URL stops working)",
+    responses(
+        (status = 200, description = "New feed token", body = CalendarTokenResponse),
+        (status = 401, description = "Unauthorized"),
+    ),
+    tag = "EDSquare"
+)]
+pub async fn rotate_calendar_token_endpoint(
+    State(state): State<GlobalState>,
+    jwt_user: JwtClaims,
+) -> impl IntoResponse {
+    match rotate_calendar_token(&state, &jwt_user.sub.to_string()) {
+        Ok(calendar_token) => {
+            info!("Nouveau jeton de flux calendrier pour l'utilisateur {}", jwt_user.sub);
+            (StatusCode::OK, Json(calendar_token_response(calendar_token))).into_response()
         }
-        let content = parts.join("\n");
-        let content = if content.len() > 2000 {
-            format!("{}…", &content[..1997])
-        } else {
-            content
-        };
-        serde_json::json!({ "content": content })
-    } else {
-        serde_json::json!({
-            "event": "edsquare_validation_multi",
-            "initiated_by": initiated_by,
-            "global_success": global_success,
-            "validated": validated,
-            "validated_codes": validated_codes,
-            "failed": failed.iter().map(|(u, m)| serde_json::json!({ "username": u, "message": m })).collect::<Vec<_>>(),
-        })
-    };
-    let client = match reqwest::Client::builder().build() {
-        Ok(c) => c,
         Err(e) => {
-            error!("Webhook EDSquare: impossible de créer le client HTTP: {}", e);
-            return;
+            error!("Erreur création jeton calendrier: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, e).into_response()
         }
-    };
-    match client.post(webhook_url).json(&body).send().await {
-        Ok(res) if !res.status().is_success() => {
-            warn!("Webhook EDSquare: statut {} pour {}", res.status(), webhook_url);
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/edsquare/calendar/token",
+    description = "Revoke the current user's iCalendar feed",
+    responses(
+        (status = 204, description = "Feed revoked"),
+        (status = 404, description = "No feed token"),
+        (status = 401, description = "Unauthorized"),
+    ),
+    tag = "EDSquare"
+)]
+pub async fn delete_calendar_token_endpoint(
+    State(state): State<GlobalState>,
+    jwt_user: JwtClaims,
+) -> impl IntoResponse {
+    match delete_calendar_token(&state, &jwt_user.sub.to_string()) {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => (StatusCode::NOT_FOUND, "Aucun flux calendrier").into_response(),
+        Err(e) => {
+            error!("Erreur suppression jeton calendrier: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, e).into_response()
         }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/calendar/{token}.ics",
+    description = "Public iCalendar feed of a user's EDSquare courses (the token in the URL replaces authentication)",
+    params(("token" = String, Path, description = "Feed token")),
+    responses(
+        (status = 200, description = "iCalendar feed", content_type = "text/calendar"),
+        (status = 404, description = "Unknown token or EDSquare session expired"),
+        (status = 502, description = "EDSquare unreachable or unexpected response"),
+    ),
+    tag = "EDSquare"
+)]
+pub async fn get_calendar_feed(
+    State(state): State<GlobalState>,
+    Path(token): Path<String>,
+) -> impl IntoResponse {
+    let token = token.strip_suffix(".ics").unwrap_or(&token);
+    let owner = match find_calendar_token_owner(&state, token) {
+        Ok(Some(owner)) => owner,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Flux calendrier inconnu").into_response(),
         Err(e) => {
-            warn!("Webhook EDSquare: erreur envoi vers {}: {}", webhook_url, e);
+            error!("Erreur lecture jeton calendrier: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response();
         }
-        _ => {
-            info!("Webhook EDSquare: bilan envoyé (validés: {}, échecs: {})", validated.len(), failed.len());
+    };
+
+    let today = state.today();
+    let from = today - chrono::Duration::days(FEED_DAYS_BEFORE);
+    let to = today + chrono::Duration::days(FEED_DAYS_AFTER);
+    let events = match fetch_planning_events_range(&state, &owner, from, to).await {
+        Ok(events) => events,
+        Err(e) => {
+            // Un statut d'erreur fait garder la dernière version du flux aux agendas
+            warn!("Flux calendrier indisponible pour l'utilisateur {}: {}", owner, e);
+            return e.into_response();
         }
+    };
+
+    let username = Ulid::from_string(&owner)
+        .ok()
+        .and_then(|owner_id| get_user_by_id(&state, &owner_id).ok().flatten())
+        .map(|user| user.username)
+        .unwrap_or_default();
+    let calendar_name = if username.is_empty() {
+        "EDSquare".to_string()
+    } else {
+        format!("EDSquare - {}", username)
+    };
+
+    (
+        StatusCode::OK,
+        [
+            (http::header::CONTENT_TYPE, "text/calendar; charset=utf-8"),
+            (http::header::CACHE_CONTROL, "private, max-age=300"),
+        ],
+        render_ics(&calendar_name, &events, chrono::Utc::now()),
+    )
+        .into_response()
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/edsquare/match-events",
+    description = "Find, in each user's own EDSquare planning, the course matching a reference event (or the course running now), without validating anything",
+    request_body = MatchPlanningEventsPayload,
+    responses(
+        (status = 200, description = "Match per user", body = MatchPlanningEventsResponse),
+        (status = 400, description = "Invalid reference event or empty user_ids"),
+        (status = 401, description = "Unauthorized"),
+    ),
+    tag = "EDSquare"
+)]
+pub async fn match_planning_events(
+    State(state): State<GlobalState>,
+    _jwt_user: JwtClaims,
+    Json(payload): Json<MatchPlanningEventsPayload>,
+) -> impl IntoResponse {
+    if payload.user_ids.is_empty() {
+        return (StatusCode::BAD_REQUEST, "user_ids must not be empty").into_response();
     }
+    let reference = match MatchReference::from_payload(payload.reference_event.as_ref(), chrono::Utc::now(), state.edsquare_event_grace) {
+        Ok(reference) => reference,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+
+    let matches: Vec<UserEventMatch> = run_bounded(
+        payload.user_ids.clone(),
+        state.edsquare_concurrency,
+        state.edsquare_user_timeout,
+        |user_id| {
+            let state = &state;
+            let reference = &reference;
+            async move {
+                let username = Ulid::from_string(&user_id)
+                    .ok()
+                    .and_then(|id| get_user_by_id(state, &id).ok().flatten())
+                    .map(|u| u.username)
+                    .unwrap_or_else(|| "<unknown>".to_string());
+                match resolve_planning_event_for_user(state, &user_id, reference).await {
+                    Ok(event_match) => UserEventMatch {
+                        user_id,
+                        username,
+                        status: event_match.status(),
+                        planning_event_id: match &event_match {
+                            EventMatch::Matched(event) => Some(event.id.to_string()),
+                            _ => None,
+                        },
+                        candidates: event_match.candidates(),
+                        message: event_match.failure_message(),
+                    },
+                    Err(e) => UserEventMatch {
+                        user_id,
+                        username,
+                        status: EventMatchStatus::Error,
+                        planning_event_id: None,
+                        candidates: Vec::new(),
+                        message: Some(e.to_string()),
+                    },
+                }
+            }
+        },
+        |user_id| UserEventMatch {
+            user_id,
+            username: "<timeout>".to_string(),
+            status: EventMatchStatus::Error,
+            planning_event_id: None,
+            candidates: Vec::new(),
+            message: Some(format!("Délai dépassé ({}s)", state.edsquare_user_timeout.as_secs())),
+        },
+    )
+    .await;
+
+    (StatusCode::OK, Json(MatchPlanningEventsResponse { matches })).into_response()
 }
@@ -0,0 +1,274 @@
+use std::fmt;
+
+use axum::response::{IntoResponse, Response};
+use http::StatusCode;
+use regex::Regex;
+
+/// Erreurs du client EDSquare. Le code HTTP renvoyé au frontend est décidé ici (`status_code`).
+#[derive(Debug, Clone, PartialEq)]
+pub enum EdsquareError {
+    /// Cookies absents, expirés ou rejetés (redirection vers `/users/sign_in`)
+    SessionExpired,
+    /// Email ou mot de passe refusé à la connexion
+    InvalidCredentials,
+    /// Aucun identifiant enregistré pour la reconnexion automatique
+    NoSavedCredentials,
+    /// Reconnexion automatique suspendue après trop d'échecs avec les identifiants enregistrés
+    CredentialsSuspended,
+    /// Code secret refusé (mauvais code ou mauvais format)
+    InvalidCode(String),
+    /// Code secret qui n'est plus valide (fenêtre de signature dépassée)
+    CodeExpired(String),
+    /// Événement du planning introuvable
+    EventNotFound(String),
+    /// Jeton CSRF introuvable dans la page EDSquare
+    CsrfMissing,
+    /// Erreur réseau vers EDSquare
+    Network(String),
+    /// Réponse EDSquare non reconnue
+    UnexpectedResponse(String),
+    /// Erreur de lecture/écriture en base (cookies, identifiants)
+    Storage(String),
+}
+
+impl fmt::Display for EdsquareError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EdsquareError::SessionExpired => {
+                write!(f, "Session EDSquare expirée. Veuillez vous reconnecter à EDSquare.")
+            }
+            EdsquareError::InvalidCredentials => write!(f, "Échec de la connexion: identifiants invalides"),
+            EdsquareError::NoSavedCredentials => {
+                write!(f, "Aucun identifiant EDSquare enregistré pour cet utilisateur.")
+            }
+            EdsquareError::CredentialsSuspended => write!(
+                f,
+                "Reconnexion EDSquare automatique suspendue après plusieurs échecs : ressaisissez votre mot de passe EDSquare."
+            ),
+            EdsquareError::InvalidCode(msg) => write!(f, "Code invalide: {}", msg),
+            EdsquareError::CodeExpired(msg) => write!(f, "Code expiré: {}", msg),
+            EdsquareError::EventNotFound(msg) => write!(f, "Événement introuvable: {}", msg),
+            EdsquareError::CsrfMissing => write!(f, "Impossible de trouver le CSRF token EDSquare"),
+            EdsquareError::Network(msg) => write!(f, "Erreur réseau EDSquare: {}", msg),
+            EdsquareError::UnexpectedResponse(msg) => write!(f, "Réponse EDSquare inattendue: {}", msg),
+            EdsquareError::Storage(msg) => write!(f, "Erreur de stockage EDSquare: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for EdsquareError {}
+
+impl EdsquareError {
+    /// Code HTTP renvoyé par l'API. Jamais 401 : le frontend le réserve à la session epi-sign.
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            EdsquareError::SessionExpired => StatusCode::NOT_FOUND,
+            EdsquareError::InvalidCredentials
+            | EdsquareError::NoSavedCredentials
+            | EdsquareError::InvalidCode(_) => StatusCode::BAD_REQUEST,
+            EdsquareError::CredentialsSuspended => StatusCode::LOCKED,
+            EdsquareError::CodeExpired(_) => StatusCode::GONE,
+            EdsquareError::EventNotFound(_) => StatusCode::NOT_FOUND,
+            EdsquareError::CsrfMissing
+            | EdsquareError::Network(_)
+            | EdsquareError::UnexpectedResponse(_) => StatusCode::BAD_GATEWAY,
+            EdsquareError::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Une reconnexion puis un nouvel essai peuvent résoudre l'erreur.
+    pub fn is_session_expired(&self) -> bool {
+        matches!(self, EdsquareError::SessionExpired)
+    }
+
+    /// Classe le message d'un `toastr.error(...)` EDSquare.
+    pub fn from_toastr_message(message: &str) -> Self {
+        let lower = message.to_lowercase();
+        if lower.contains("introuvable") {
+            EdsquareError::EventNotFound(message.to_string())
+        } else if lower.contains("connecter") || lower.contains("session") {
+            // Avant « expiré » : « Session expirée » doit déclencher la reconnexion, pas un 410
+            EdsquareError::SessionExpired
+        } else if lower.contains("plus valide") || lower.contains("expiré") {
+            EdsquareError::CodeExpired(message.to_string())
+        } else if lower.contains("code") {
+            EdsquareError::InvalidCode(message.to_string())
+        } else {
+            EdsquareError::UnexpectedResponse(message.to_string())
+        }
+    }
+}
+
+impl IntoResponse for EdsquareError {
+    fn into_response(self) -> Response {
+        (self.status_code(), self.to_string()).into_response()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ToastrLevel {
+    Success,
+    Info,
+    Warning,
+    Error,
+}
+
+/// Notification `toastr.<niveau>("message")` trouvée dans une réponse JS d'EDSquare.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Toastr {
+    pub level: ToastrLevel,
+    pub message: String,
+}
+
+/// Analyse une réponse JS EDSquare (rails-ujs) : notifications toastr dans l'ordre,
+/// messages décodés des entités HTML et des échappements JS.
+pub fn parse_toastr(body: &str) -> Vec<Toastr> {
+    let Ok(re) = Regex::new(r#"toastr\.(success|info|warning|error)\(\s*(?:"((?:[^"\\]|\\.)*)"|'((?:[^'\\]|\\.)*)')"#)
+    else {
+        return Vec::new();
+    };
+    re.captures_iter(body)
+        .map(|caps| {
+            let level = match &caps[1] {
+                "success" => ToastrLevel::Success,
+                "info" => ToastrLevel::Info,
+                "warning" => ToastrLevel::Warning,
+                _ => ToastrLevel::Error,
+            };
+            let raw = caps.get(2).or_else(|| caps.get(3)).map(|m| m.as_str()).unwrap_or_default();
+            Toastr {
+                level,
+                message: decode_message(raw),
+            }
+        })
+        .collect()
+}
+
+/// La réponse JS redirige vers la page de connexion (session expirée).
+pub fn redirects_to_sign_in(body: &str) -> bool {
+    Regex::new(r#"(?:window\.)?location(?:\.href)?\s*=\s*["'][^"']*/users/sign_in"#)
+        .map(|re| re.is_match(body))
+        .unwrap_or(false)
+}
+
+/// Première erreur toastr de la réponse, ou session expirée si la réponse redirige vers la connexion.
+pub fn error_from_js_response(body: &str) -> Option<EdsquareError> {
+    if redirects_to_sign_in(body) {
+        return Some(EdsquareError::SessionExpired);
+    }
+    parse_toastr(body)
+        .into_iter()
+        .find(|t| t.level == ToastrLevel::Error)
+        .map(|t| EdsquareError::from_toastr_message(&t.message))
+}
+
+fn decode_message(raw: &str) -> String {
+    let unescaped = raw
+        .replace("\\'", "'")
+        .replace("\\\"", "\"")
+        .replace("\\n", " ")
+        .replace("\\/", "/");
+    unescaped
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&quot;", "\"")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_error_with_html_entities() {
+        let body = r##"$("#modal").modal("hide"); toastr.error("Le code saisi n&#39;est plus valide");"##;
+        assert_eq!(
+            parse_toastr(body),
+            vec![Toastr {
+                level: ToastrLevel::Error,
+                message: "Le code saisi n'est plus valide".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn parses_single_quotes_and_escapes() {
+        let body = r#"toastr.success('Signature enregistrée'); toastr.warning("Attention \"test\"");"#;
+        let toasts = parse_toastr(body);
+        assert_eq!(toasts.len(), 2);
+        assert_eq!(toasts[0].level, ToastrLevel::Success);
+        assert_eq!(toasts[1].message, "Attention \"test\"");
+    }
+
+    #[test]
+    fn classifies_error_messages() {
+        assert_eq!(
+            error_from_js_response(r#"toastr.error("Le code saisi n&#39;est plus valide")"#),
+            Some(EdsquareError::CodeExpired("Le code saisi n'est plus valide".to_string()))
+        );
+        assert_eq!(
+            error_from_js_response(r#"toastr.error("Code secret incorrect")"#),
+            Some(EdsquareError::InvalidCode("Code secret incorrect".to_string()))
+        );
+        assert!(matches!(
+            error_from_js_response(r#"toastr.error("Événement introuvable")"#),
+            Some(EdsquareError::EventNotFound(_))
+        ));
+        assert_eq!(
+            error_from_js_response(r#"toastr.error("Vous devez vous connecter")"#),
+            Some(EdsquareError::SessionExpired)
+        );
+        assert!(matches!(
+            error_from_js_response(r#"toastr.error("Oups")"#),
+            Some(EdsquareError::UnexpectedResponse(_))
+        ));
+    }
+
+    #[test]
+    fn expired_session_is_not_an_expired_code() {
+        let error = EdsquareError::from_toastr_message("Session expirée, veuillez vous reconnecter");
+        assert_eq!(error, EdsquareError::SessionExpired);
+        assert!(error.is_session_expired());
+        assert_eq!(
+            EdsquareError::from_toastr_message("Le code a expiré"),
+            EdsquareError::CodeExpired("Le code a expiré".to_string())
+        );
+    }
+
+    #[test]
+    fn detects_sign_in_redirect() {
+        assert_eq!(
+            error_from_js_response(r#"window.location.href = "https://app.edsquare.fr/users/sign_in";"#),
+            Some(EdsquareError::SessionExpired)
+        );
+    }
+
+    #[test]
+    fn ignores_success_only_responses() {
+        assert_eq!(error_from_js_response(r#"toastr.success("Signature enregistrée")"#), None);
+        assert_eq!(error_from_js_response(""), None);
+    }
+
+    #[test]
+    fn status_codes_never_use_unauthorized() {
+        for err in [
+            EdsquareError::SessionExpired,
+            EdsquareError::InvalidCredentials,
+            EdsquareError::NoSavedCredentials,
+            EdsquareError::CredentialsSuspended,
+            EdsquareError::InvalidCode(String::new()),
+            EdsquareError::CodeExpired(String::new()),
+            EdsquareError::EventNotFound(String::new()),
+            EdsquareError::CsrfMissing,
+            EdsquareError::Network(String::new()),
+            EdsquareError::UnexpectedResponse(String::new()),
+            EdsquareError::Storage(String::new()),
+        ] {
+            assert_ne!(err.status_code(), StatusCode::UNAUTHORIZED, "{:?}", err);
+        }
+    }
+}
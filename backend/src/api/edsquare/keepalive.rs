@@ -0,0 +1,416 @@
+use std::collections::HashSet;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use tracing::{error, info, warn};
+
+use crate::api::edsquare::errors::EdsquareError;
+use crate::api::edsquare::models::{EdsquarePlanningEvent, EdsquareSessionHealth};
+use crate::api::edsquare::services::{
+    check_edsquare_session, fetch_planning_events, get_edsquare_cookies, get_edsquare_credentials, jar_expires_at,
+    mark_edsquare_cookies_verified, reconnect_edsquare, refresh_edsquare_session,
+};
+use crate::api::users::get_all_users;
+use crate::misc::{GlobalState, ServerEvent, run_bounded};
+
+/// Marge avant l'expiration des cookies à partir de laquelle on se reconnecte par avance.
+const REFRESH_MARGIN_HOURS: i64 = 3;
+/// Seuls les utilisateurs ayant un cours EDSquare en cours ou commençant dans ce délai sont contrôlés.
+const UPCOMING_COURSE_HOURS: i64 = 3;
+
+/// Lit les jours de session (une date jj/mm/aa par ligne, lignes invalides ignorées).
+pub fn parse_session_dates(content: &str) -> HashSet<NaiveDate> {
+    content
+        .lines()
+        .filter_map(|line| NaiveDate::parse_from_str(line.trim(), "%d/%m/%y").ok())
+        .collect()
+}
+
+/// Aujourd'hui est-il un jour de session ? Sans fichier (ou fichier illisible), tous les jours le sont.
+fn is_session_day(state: &GlobalState, today: NaiveDate) -> bool {
+    let Some(path) = &state.session_dates_file else {
+        return true;
+    };
+    match std::fs::read_to_string(path) {
+        Ok(content) => parse_session_dates(&content).contains(&today),
+        Err(e) => {
+            warn!("Fichier des jours de session illisible ({}): {}, contrôle effectué quand même", path, e);
+            true
+        }
+    }
+}
+
+/// Un cours est-il en cours ou commence-t-il dans les `UPCOMING_COURSE_HOURS` ? Un cours aux horaires illisibles
+/// compte comme prévu.
+fn has_upcoming_course(events: &[EdsquarePlanningEvent], now: DateTime<Utc>) -> bool {
+    let horizon = now + chrono::Duration::hours(UPCOMING_COURSE_HOURS);
+    events.iter().any(|event| {
+        match (DateTime::parse_from_rfc3339(&event.start), DateTime::parse_from_rfc3339(&event.end)) {
+            (Ok(start), Ok(end)) => start <= horizon && end >= now,
+            _ => true,
+        }
+    })
+}
+
+/// L'utilisateur a-t-il un cours EDSquare bientôt ? Si le planning est illisible (session expirée, EDSquare
+/// injoignable...), la session est contrôlée quand même : le contrôle dira quoi corriger.
+async fn has_upcoming_course_for(state: &GlobalState, user_id: &str) -> bool {
+    match fetch_planning_events(state, user_id, state.today()).await {
+        Ok(events) => has_upcoming_course(&events, Utc::now()),
+        Err(e) => {
+            info!("Maintien de session EDSquare: planning de {} indisponible ({}), contrôle quand même", user_id, e);
+            true
+        }
+    }
+}
+
+/// Issue du contrôle de la session d'un utilisateur.
+#[derive(Debug, PartialEq)]
+enum SessionCheck {
+    /// Session ouverte (éventuellement après reconnexion)
+    Healthy { refreshed: bool, message: String },
+    /// Session expirée ou sur le point de l'être, sans reconnexion possible
+    Failed(String),
+    /// Rien à contrôler (ni cookies ni identifiants) ou EDSquare injoignable : on ne conclut pas
+    Skipped,
+}
+
+async fn check_user_session(state: &GlobalState, user_id: &str) -> SessionCheck {
+    let has_credentials = matches!(get_edsquare_credentials(state, user_id), Ok(Some(_)));
+    let cookies = match get_edsquare_cookies(state, user_id) {
+        Ok(cookies) => cookies,
+        Err(e) => {
+            error!("Maintien de session EDSquare: lecture des cookies de {}: {}", user_id, e);
+            return SessionCheck::Skipped;
+        }
+    };
+
+    let Some(cookies) = cookies else {
+        if !has_credentials {
+            return SessionCheck::Skipped;
+        }
+        return match reconnect_edsquare(state, user_id, None).await {
+            Ok(_) => SessionCheck::Healthy {
+                refreshed: true,
+                message: "Session EDSquare rouverte avec les identifiants sauvegardés".to_string(),
+            },
+            Err(e) => failed_refresh(e),
+        };
+    };
+
+    let expires_soon = jar_expires_at(&cookies)
+        .is_some_and(|expiry| expiry - Utc::now().naive_utc() < chrono::Duration::hours(REFRESH_MARGIN_HOURS));
+
+    if !expires_soon {
+        match check_edsquare_session(&state.edsquare, &cookies).await {
+            Ok(()) => {
+                if let Err(e) = mark_edsquare_cookies_verified(state, user_id) {
+                    warn!("Impossible de mettre à jour la vérification des cookies EDSquare: {}", e);
+                }
+                return SessionCheck::Healthy {
+                    refreshed: false,
+                    message: "Session EDSquare active".to_string(),
+                };
+            }
+            Err(e) if e.is_session_expired() => {
+                if !has_credentials {
+                    return SessionCheck::Failed(
+                        "Session EDSquare expirée et aucun identifiant sauvegardé : reconnectez-vous".to_string(),
+                    );
+                }
+                return match reconnect_edsquare(state, user_id, Some(&cookies)).await {
+                    Ok(_) => SessionCheck::Healthy {
+                        refreshed: true,
+                        message: "Session EDSquare expirée, reconnexion effectuée".to_string(),
+                    },
+                    Err(e) => failed_refresh(e),
+                };
+            }
+            Err(e) => {
+                warn!("Maintien de session EDSquare pour {}: {}", user_id, e);
+                return SessionCheck::Skipped;
+            }
+        }
+    }
+
+    if !has_credentials {
+        return SessionCheck::Failed(
+            "Session EDSquare sur le point d'expirer et aucun identifiant sauvegardé : reconnectez-vous".to_string(),
+        );
+    }
+    match refresh_edsquare_session(state, user_id).await {
+        Ok(_) => SessionCheck::Healthy {
+            refreshed: true,
+            message: "Session EDSquare renouvelée avant expiration".to_string(),
+        },
+        Err(e) => failed_refresh(e),
+    }
+}
+
+fn failed_refresh(e: EdsquareError) -> SessionCheck {
+    match e {
+        EdsquareError::Network(_) => SessionCheck::Skipped,
+        e => SessionCheck::Failed(format!("Reconnexion EDSquare impossible: {}", e)),
+    }
+}
+
+/// Contrôle les sessions EDSquare des utilisateurs qui ont un cours bientôt et alerte ceux dont la session n'a pas pu
+/// être rafraîchie.
+pub async fn run_edsquare_keepalive(state: &GlobalState) {
+    let users = match get_all_users(state) {
+        Ok(users) => users,
+        Err(e) => {
+            error!("Maintien de session EDSquare: impossible de lister les utilisateurs: {:?}", e);
+            return;
+        }
+    };
+
+    let users: Vec<(String, String)> = users.into_iter().map(|u| (u.id, u.username)).collect();
+    let checks = run_bounded(
+        users,
+        state.edsquare_concurrency,
+        state.edsquare_user_timeout,
+        |(user_id, username)| async move {
+            let check = if has_upcoming_course_for(state, &user_id).await {
+                check_user_session(state, &user_id).await
+            } else {
+                SessionCheck::Skipped
+            };
+            (user_id, username, check)
+        },
+        |(user_id, username)| (user_id, username, SessionCheck::Skipped),
+    )
+    .await;
+
+    let checked_at = Utc::now().to_rfc3339();
+    let (mut healthy, mut refreshed, mut failed) = (0, 0, 0);
+    for (user_id, username, check) in checks {
+        let health = match check {
+            SessionCheck::Skipped => continue,
+            SessionCheck::Healthy { refreshed: was_refreshed, message } => {
+                healthy += 1;
+                if was_refreshed {
+                    refreshed += 1;
+                }
+                EdsquareSessionHealth {
+                    user_id: user_id.clone(),
+                    username,
+                    healthy: true,
+                    refreshed: was_refreshed,
+                    message,
+                    checked_at: checked_at.clone(),
+                }
+            }
+            SessionCheck::Failed(message) => {
+                failed += 1;
+                warn!("Session EDSquare à corriger pour {} ({}): {}", username, user_id, message);
+                EdsquareSessionHealth {
+                    user_id: user_id.clone(),
+                    username,
+                    healthy: false,
+                    refreshed: false,
+                    message,
+                    checked_at: checked_at.clone(),
+                }
+            }
+        };
+        record_session_health(state, health);
+    }
+    info!(
+        "Maintien de session EDSquare: {} session(s) active(s) dont {} renouvelée(s), {} à corriger",
+        healthy, refreshed, failed
+    );
+}
+
+/// Enregistre le résultat d'un contrôle ; une nouvelle alerte est diffusée (SSE) à la première défaillance.
+fn record_session_health(state: &GlobalState, health: EdsquareSessionHealth) {
+    let mut map = state
+        .edsquare_session_health
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let was_healthy = map.get(&health.user_id).is_none_or(|previous| previous.healthy);
+    if was_healthy && !health.healthy {
+        state.events.publish(ServerEvent::EdsquareSessionAlert {
+            user_id: health.user_id.clone(),
+            username: health.username.clone(),
+            message: health.message.clone(),
+        });
+    }
+    map.insert(health.user_id.clone(), health);
+}
+
+/// Dernier contrôle de session d'un utilisateur.
+pub fn session_health_for(state: &GlobalState, user_id: &str) -> Option<EdsquareSessionHealth> {
+    state
+        .edsquare_session_health
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .get(user_id)
+        .cloned()
+}
+
+/// Utilisateurs dont la session n'a pas pu être rafraîchie lors du dernier contrôle.
+pub fn session_alerts(state: &GlobalState) -> Vec<EdsquareSessionHealth> {
+    let mut alerts: Vec<EdsquareSessionHealth> = state
+        .edsquare_session_health
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .values()
+        .filter(|health| !health.healthy)
+        .cloned()
+        .collect();
+    alerts.sort_by(|a, b| a.username.cmp(&b.username));
+    alerts
+}
+
+/// Oublie l'alerte d'un utilisateur qui vient de se reconnecter lui-même.
+pub fn clear_session_alert(state: &GlobalState, user_id: &str) {
+    let mut map = state
+        .edsquare_session_health
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if map.get(user_id).is_some_and(|health| !health.healthy) {
+        map.remove(user_id);
+    }
+}
+
+/// Tâche de fond : contrôle les sessions EDSquare les jours de session, pendant et avant les cours prévus.
+pub fn spawn_edsquare_keepalive(state: GlobalState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(state.edsquare_keepalive_interval);
+        loop {
+            interval.tick().await;
+            if !is_session_day(&state, state.today()) {
+                continue;
+            }
+            run_edsquare_keepalive(&state).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::edsquare::EdsquareClient;
+    use crate::api::edsquare::models::EdsquareCookieItem;
+    use crate::api::edsquare::services::{save_edsquare_cookies, save_edsquare_credentials};
+    use crate::api::users::delete_user_account;
+
+    /// Cookie de session accepté par l'EDSquare de démo (sauf `value` = "expired"), expirant dans `expires_in`.
+    fn demo_cookies(value: &str, expires_in: Option<chrono::Duration>) -> Vec<EdsquareCookieItem> {
+        vec![EdsquareCookieItem {
+            name: "_edsquare_session".to_string(),
+            value: value.to_string(),
+            domain: "127.0.0.1".to_string(),
+            path: "/".to_string(),
+            expires: expires_in.map(|d| (Utc::now() + d).timestamp()),
+            http_only: true,
+            secure: false,
+            same_site: None,
+        }]
+    }
+
+    fn course(start: DateTime<Utc>, hours: i64) -> EdsquarePlanningEvent {
+        EdsquarePlanningEvent {
+            id: 1,
+            title: "Algorithmique".to_string(),
+            target: None,
+            start: start.to_rfc3339(),
+            end: (start + chrono::Duration::hours(hours)).to_rfc3339(),
+            event_type: None,
+            registrable: None,
+        }
+    }
+
+    fn health(user_id: &str, healthy: bool) -> EdsquareSessionHealth {
+        EdsquareSessionHealth {
+            user_id: user_id.to_string(),
+            username: "jdoe".to_string(),
+            healthy,
+            refreshed: false,
+            message: if healthy { "ok" } else { "expirée" }.to_string(),
+            checked_at: Utc::now().to_rfc3339(),
+        }
+    }
+
+    #[test]
+    fn only_current_and_upcoming_courses_need_a_session() {
+        let now = Utc::now();
+        assert!(!has_upcoming_course(&[], now));
+        assert!(has_upcoming_course(&[course(now + chrono::Duration::hours(2), 3)], now));
+        assert!(has_upcoming_course(&[course(now - chrono::Duration::hours(1), 3)], now));
+        assert!(!has_upcoming_course(&[course(now + chrono::Duration::hours(UPCOMING_COURSE_HOURS + 1), 3)], now));
+        assert!(!has_upcoming_course(&[course(now - chrono::Duration::hours(4), 3)], now));
+    }
+
+    #[tokio::test]
+    async fn session_checks_refresh_report_or_skip() {
+        let Some(db_url) = crate::misc::test_database_url() else {
+            return;
+        };
+        let demo = crate::misc::start_demo_providers().unwrap();
+        let state = GlobalState::for_tests(&db_url, EdsquareClient::new(&demo.edsquare_base_url).unwrap());
+        let user = ulid::Ulid::new().to_string();
+        let soon = Some(chrono::Duration::hours(1));
+
+        // Ni cookies ni identifiants : rien à contrôler
+        assert_eq!(check_user_session(&state, &user).await, SessionCheck::Skipped);
+
+        // Session expirée sans identifiants sauvegardés : l'utilisateur doit se reconnecter
+        save_edsquare_cookies(&state, &user, &demo_cookies("expired", None)).unwrap();
+        assert!(matches!(check_user_session(&state, &user).await, SessionCheck::Failed(_)));
+        save_edsquare_cookies(&state, &user, &demo_cookies("demo-session", soon)).unwrap();
+        assert!(matches!(check_user_session(&state, &user).await, SessionCheck::Failed(_)));
+
+        // Session sur le point d'expirer avec identifiants : renouvelée par avance
+        save_edsquare_credentials(&state, &user, "jane.doe@epitech.eu", "hunter2").unwrap();
+        assert!(matches!(
+            check_user_session(&state, &user).await,
+            SessionCheck::Healthy { refreshed: true, .. }
+        ));
+        assert!(matches!(
+            check_user_session(&state, &user).await,
+            SessionCheck::Healthy { refreshed: false, .. }
+        ));
+
+        // EDSquare injoignable : on ne conclut pas
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let closed = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+        let unreachable = GlobalState::for_tests(&db_url, EdsquareClient::new(&closed).unwrap());
+        assert_eq!(check_user_session(&unreachable, &user).await, SessionCheck::Skipped);
+        save_edsquare_cookies(&state, &user, &demo_cookies("demo-session", soon)).unwrap();
+        assert_eq!(check_user_session(&unreachable, &user).await, SessionCheck::Skipped);
+
+        delete_user_account(&state, &user).unwrap();
+    }
+
+    #[test]
+    fn alerts_only_on_the_first_failure() {
+        let state = GlobalState::for_tests("postgres://unused", EdsquareClient::new("http://127.0.0.1:9").unwrap());
+        let mut events = state.events.subscribe();
+        let alerts = |events: &mut tokio::sync::broadcast::Receiver<ServerEvent>| {
+            std::iter::from_fn(|| events.try_recv().ok())
+                .filter(|e| matches!(e, ServerEvent::EdsquareSessionAlert { .. }))
+                .count()
+        };
+
+        record_session_health(&state, health("u1", false));
+        record_session_health(&state, health("u1", false));
+        assert_eq!(alerts(&mut events), 1);
+        assert_eq!(session_alerts(&state).len(), 1);
+
+        // Rétablie puis de nouveau en échec : nouvelle alerte
+        record_session_health(&state, health("u1", true));
+        assert!(session_alerts(&state).is_empty());
+        record_session_health(&state, health("u1", false));
+        assert_eq!(alerts(&mut events), 1);
+    }
+
+    #[test]
+    fn parses_session_dates_file() {
+        let dates = parse_session_dates("09/06/25\n10/06/25\r\n\n  16/06/25  \npas une date\n");
+        assert_eq!(dates.len(), 3);
+        assert!(dates.contains(&NaiveDate::from_ymd_opt(2025, 6, 16).unwrap()));
+        assert!(!dates.contains(&NaiveDate::from_ymd_opt(2025, 6, 11).unwrap()));
+    }
+}
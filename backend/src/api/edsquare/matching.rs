@@ -0,0 +1,297 @@
+use chrono::{DateTime, Duration, Utc};
+use tracing::info;
+
+use crate::api::edsquare::errors::EdsquareError;
+use crate::api::edsquare::models::{EdsquarePlanningEvent, EventMatchStatus, ReferencePlanningEvent};
+use crate::api::edsquare::services::fetch_planning_events;
+use crate::misc::GlobalState;
+
+/// Ce qu'on cherche dans le planning de chaque utilisateur.
+#[derive(Debug, Clone)]
+pub enum MatchReference {
+    /// Le même cours qu'un cours de référence (vu dans le planning de l'initiateur par exemple)
+    Event {
+        title: Option<String>,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+    },
+    /// Le cours en cours à cet instant, ou qui commence dans moins de `grace` (on signe souvent un peu avant)
+    At { at: DateTime<Utc>, grace: Duration },
+}
+
+/// Résultat de la recherche du cours d'un utilisateur.
+#[derive(Debug, Clone)]
+pub enum EventMatch {
+    Matched(EdsquarePlanningEvent),
+    Ambiguous(Vec<EdsquarePlanningEvent>),
+    Unmatched,
+}
+
+impl EventMatch {
+    pub fn status(&self) -> EventMatchStatus {
+        match self {
+            EventMatch::Matched(_) => EventMatchStatus::Matched,
+            EventMatch::Ambiguous(_) => EventMatchStatus::Ambiguous,
+            EventMatch::Unmatched => EventMatchStatus::Unmatched,
+        }
+    }
+
+    pub fn candidates(&self) -> Vec<EdsquarePlanningEvent> {
+        match self {
+            EventMatch::Matched(event) => vec![event.clone()],
+            EventMatch::Ambiguous(events) => events.clone(),
+            EventMatch::Unmatched => Vec::new(),
+        }
+    }
+
+    /// Message lisible pour les cas où aucun cours n'a pu être retenu.
+    pub fn failure_message(&self) -> Option<String> {
+        match self {
+            EventMatch::Matched(_) => None,
+            EventMatch::Ambiguous(events) => Some(format!(
+                "Plusieurs cours correspondent dans le planning EDSquare ({}) : précisez l'event ID",
+                events
+                    .iter()
+                    .map(|e| format!("{} #{}", e.title, e.id))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+            EventMatch::Unmatched => Some("Aucun cours correspondant dans le planning EDSquare".to_string()),
+        }
+    }
+}
+
+impl MatchReference {
+    /// Construit la référence depuis le payload ; sans cours de référence on cherche le cours en cours maintenant.
+    pub fn from_payload(
+        reference: Option<&ReferencePlanningEvent>,
+        now: DateTime<Utc>,
+        grace: Duration,
+    ) -> Result<Self, String> {
+        let Some(reference) = reference else {
+            return Ok(MatchReference::At { at: now, grace });
+        };
+        let parse = |value: &Option<String>, field: &str| -> Result<Option<DateTime<Utc>>, String> {
+            value
+                .as_deref()
+                .map(|v| {
+                    DateTime::parse_from_rfc3339(v)
+                        .map(|dt| dt.with_timezone(&Utc))
+                        .map_err(|_| format!("reference_event.{} invalide (RFC 3339 attendu)", field))
+                })
+                .transpose()
+        };
+        let title = reference.title.clone().filter(|t| !t.trim().is_empty());
+        let start = parse(&reference.start, "start")?;
+        let end = parse(&reference.end, "end")?;
+        if title.is_none() && start.is_none() {
+            return Err("reference_event doit contenir au moins title ou start".to_string());
+        }
+        Ok(MatchReference::Event { title, start, end })
+    }
+
+    /// Instant qui détermine le jour de planning à consulter.
+    fn anchor(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            MatchReference::Event { start, .. } => start.unwrap_or(now),
+            MatchReference::At { at, .. } => *at,
+        }
+    }
+}
+
+fn normalize_title(title: &str) -> String {
+    title.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+fn bounds(event: &EdsquarePlanningEvent) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    let start = DateTime::parse_from_rfc3339(&event.start).ok()?.with_timezone(&Utc);
+    let end = DateTime::parse_from_rfc3339(&event.end).ok()?.with_timezone(&Utc);
+    Some((start, end))
+}
+
+/// Cherche dans `events` le cours correspondant à la référence.
+pub fn match_planning_event(events: &[EdsquarePlanningEvent], reference: &MatchReference) -> EventMatch {
+    let candidates: Vec<&EdsquarePlanningEvent> = match reference {
+        MatchReference::At { at, grace } => {
+            // Cours en cours ou imminents, hors cours explicitement fermés à l'inscription
+            let current: Vec<(&EdsquarePlanningEvent, DateTime<Utc>)> = events
+                .iter()
+                .filter(|e| e.registrable != Some(false))
+                .filter_map(|e| bounds(e).map(|(start, end)| (e, start, end)))
+                .filter(|(_, start, end)| *start - *grace <= *at && *at < *end)
+                .map(|(e, start, _)| (e, start))
+                .collect();
+            // Plusieurs cours se chevauchent : on retient celui dont le début est le plus proche
+            // (le cours qui commence dans la marge l'emporte sur celui qui se termine)
+            let closest = current.iter().map(|(_, start)| (*start - *at).abs()).min();
+            current
+                .into_iter()
+                .filter(|(_, start)| Some((*start - *at).abs()) == closest)
+                .map(|(e, _)| e)
+                .collect()
+        }
+        MatchReference::Event { title, start, end } => {
+            let mut candidates: Vec<&EdsquarePlanningEvent> = events
+                .iter()
+                .filter(|e| match (start, bounds(e)) {
+                    (None, _) => true,
+                    (Some(_), None) => false,
+                    (Some(ref_start), Some((e_start, e_end))) => {
+                        let ref_end = end.unwrap_or(*ref_start + Duration::minutes(1));
+                        e_start < ref_end && *ref_start < e_end
+                    }
+                })
+                .collect();
+
+            if let Some(title) = title {
+                let wanted = normalize_title(title);
+                let exact: Vec<&EdsquarePlanningEvent> = candidates
+                    .iter()
+                    .copied()
+                    .filter(|e| normalize_title(&e.title) == wanted)
+                    .collect();
+                candidates = if !exact.is_empty() {
+                    exact
+                } else {
+                    candidates
+                        .into_iter()
+                        .filter(|e| {
+                            let other = normalize_title(&e.title);
+                            other.contains(&wanted) || wanted.contains(&other)
+                        })
+                        .collect()
+                };
+            }
+
+            // Départage par horaires identiques
+            if candidates.len() > 1
+                && let (Some(ref_start), Some(ref_end)) = (start, end)
+            {
+                let same_slot: Vec<&EdsquarePlanningEvent> = candidates
+                    .iter()
+                    .copied()
+                    .filter(|e| bounds(e) == Some((*ref_start, *ref_end)))
+                    .collect();
+                if same_slot.len() == 1 {
+                    candidates = same_slot;
+                }
+            }
+            candidates
+        }
+    };
+
+    match candidates.as_slice() {
+        [] => EventMatch::Unmatched,
+        [event] => EventMatch::Matched((*event).clone()),
+        _ => EventMatch::Ambiguous(candidates.into_iter().cloned().collect()),
+    }
+}
+
+/// Cherche le cours correspondant dans le planning EDSquare de l'utilisateur (jour de la référence).
+pub async fn resolve_planning_event_for_user(
+    state: &GlobalState,
+    user_id: &str,
+    reference: &MatchReference,
+) -> Result<EventMatch, EdsquareError> {
+    let date = reference.anchor(Utc::now()).with_timezone(&state.school_timezone).date_naive();
+    let events = fetch_planning_events(state, user_id, date).await?;
+    let event_match = match_planning_event(&events, reference);
+    info!(
+        "Correspondance de cours EDSquare pour l'utilisateur {} ({}): {:?}",
+        user_id,
+        date,
+        event_match.status()
+    );
+    Ok(event_match)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn event(id: i64, title: &str, start: &str, end: &str, registrable: Option<bool>) -> EdsquarePlanningEvent {
+        EdsquarePlanningEvent {
+            id,
+            title: title.to_string(),
+            target: None,
+            start: start.to_string(),
+            end: end.to_string(),
+            event_type: None,
+            registrable,
+        }
+    }
+
+    fn planning() -> Vec<EdsquarePlanningEvent> {
+        vec![
+            event(1, "Algorithmique", "2026-10-19T09:00:00+02:00", "2026-10-19T12:00:00+02:00", Some(true)),
+            event(2, "Anglais", "2026-10-19T14:00:00+02:00", "2026-10-19T16:00:00+02:00", Some(true)),
+            event(3, "Permanence", "2026-10-19T14:00:00+02:00", "2026-10-19T18:00:00+02:00", Some(false)),
+            event(4, "Anglais - groupe B", "2026-10-19T16:00:00+02:00", "2026-10-19T18:00:00+02:00", Some(true)),
+        ]
+    }
+
+    fn utc(h: u32, m: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 10, 19, h, m, 0).unwrap()
+    }
+
+    fn matched_id(m: EventMatch) -> Option<i64> {
+        match m {
+            EventMatch::Matched(e) => Some(e.id),
+            _ => None,
+        }
+    }
+
+    fn now(at: DateTime<Utc>) -> MatchReference {
+        MatchReference::At { at, grace: Duration::minutes(15) }
+    }
+
+    #[test]
+    fn current_time_matches_running_or_imminent_event() {
+        // 08:50 Paris = 06:50 UTC, dans la marge avant Algorithmique
+        assert_eq!(matched_id(match_planning_event(&planning(), &now(utc(6, 50)))), Some(1));
+        // 08:40 Paris : hors de la marge
+        assert!(matches!(match_planning_event(&planning(), &now(utc(6, 40))), EventMatch::Unmatched));
+        // 14:30 Paris : Anglais et Permanence se chevauchent, seul Anglais est ouvert à l'inscription
+        assert_eq!(matched_id(match_planning_event(&planning(), &now(utc(12, 30)))), Some(2));
+        // 13:00 Paris : rien
+        assert!(matches!(match_planning_event(&planning(), &now(utc(11, 0))), EventMatch::Unmatched));
+    }
+
+    #[test]
+    fn grace_window_prefers_the_event_about_to_start() {
+        // 15:50 Paris : Anglais se termine à 16:00, Anglais - groupe B commence à 16:00
+        assert_eq!(matched_id(match_planning_event(&planning(), &now(utc(13, 50)))), Some(4));
+        // Sans marge, seul le cours en cours est candidat
+        let no_grace = MatchReference::At { at: utc(13, 50), grace: Duration::zero() };
+        assert_eq!(matched_id(match_planning_event(&planning(), &no_grace)), Some(2));
+    }
+
+    #[test]
+    fn reference_event_matches_by_title_and_slot() {
+        let reference = MatchReference::Event {
+            title: Some("  anglais ".to_string()),
+            start: Some(utc(12, 0)),
+            end: Some(utc(14, 0)),
+        };
+        assert_eq!(matched_id(match_planning_event(&planning(), &reference)), Some(2));
+
+        // Titre seul : "Anglais" exact l'emporte sur "Anglais - groupe B"
+        let title_only = MatchReference::Event { title: Some("Anglais".to_string()), start: None, end: None };
+        assert_eq!(matched_id(match_planning_event(&planning(), &title_only)), Some(2));
+    }
+
+    #[test]
+    fn reports_ambiguous_and_unmatched() {
+        let afternoon = MatchReference::Event { title: None, start: Some(utc(11, 0)), end: Some(utc(16, 0)) };
+        match match_planning_event(&planning(), &afternoon) {
+            EventMatch::Ambiguous(events) => {
+                assert_eq!(events.iter().map(|e| e.id).collect::<Vec<_>>(), vec![2, 3, 4]);
+            }
+            other => panic!("attendu ambigu, obtenu {:?}", other),
+        }
+
+        let unknown = MatchReference::Event { title: Some("Physique".to_string()), start: None, end: None };
+        assert!(matches!(match_planning_event(&planning(), &unknown), EventMatch::Unmatched));
+    }
+}
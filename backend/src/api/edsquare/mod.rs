@@ -1,7 +1,18 @@
+pub mod calendar;
+pub mod client;
 pub mod endpoints;
+pub mod errors;
+pub mod keepalive;
+pub mod matching;
 pub mod models;
+mod provider;
 pub mod services;
 
+pub use client::EdsquareClient;
+pub use keepalive::spawn_edsquare_keepalive;
+pub use provider::EdsquareProvider;
+pub use services::spawn_edsquare_cookie_gc;
+
 use axum::Router;
 use crate::misc::GlobalState;
 
@@ -13,8 +24,24 @@ pub fn get_routes(state: GlobalState) -> Router {
         .route("/login", axum::routing::post(endpoints::login_edsquare_endpoint))
         .route("/login-saved", axum::routing::post(endpoints::login_edsquare_with_saved_endpoint))
         .route("/status", axum::routing::get(endpoints::get_edsquare_status))
+        .route("/session-alerts", axum::routing::get(endpoints::get_edsquare_session_alerts))
         .route("/eligible-users", axum::routing::get(endpoints::get_edsquare_eligible_users))
         .route("/planning-events", axum::routing::get(endpoints::get_planning_events))
+        .route("/planning-events/range", axum::routing::get(endpoints::get_planning_events_range))
+        .route("/match-events", axum::routing::post(endpoints::match_planning_events))
         .route("/planning-events-for-users", axum::routing::post(endpoints::get_planning_events_for_users))
+        .route(
+            "/calendar/token",
+            axum::routing::get(endpoints::get_calendar_token_endpoint)
+                .post(endpoints::rotate_calendar_token_endpoint)
+                .delete(endpoints::delete_calendar_token_endpoint),
+        )
+        .with_state(state)
+}
+
+/// Flux iCalendar public : le jeton dans l'URL tient lieu d'authentification.
+pub fn get_calendar_routes(state: GlobalState) -> Router {
+    Router::new()
+        .route("/{token}", axum::routing::get(endpoints::get_calendar_feed))
         .with_state(state)
 }
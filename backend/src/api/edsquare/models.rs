@@ -1,13 +1,19 @@
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
+use crate::api::attendance::UserAttendanceResult;
 use crate::api::sign::CookieItem;
 
 #[derive(Deserialize, ToSchema, Debug)]
 pub struct ValidateEdsquarePayload {
     #[schema(example = "000000")]
     pub code: String,
+    /// Facultatif : à défaut, le cours ouvert à l'inscription en cours ou qui commence bientôt
     #[schema(example = "199289")]
-    pub planning_event_id: String,
+    #[serde(default)]
+    pub planning_event_id: Option<String>,
+    /// Relire EDSquare après l'envoi pour confirmer que la signature est enregistrée
+    #[serde(default)]
+    pub verify: bool,
 }
 
 #[derive(Serialize, ToSchema, Debug)]
@@ -16,6 +22,22 @@ pub struct ValidateEdsquareResponse {
     pub message: String,
     pub code: String,
     pub planning_event_id: Option<String>,
+    /// Cours retenu automatiquement (quand planning_event_id n'était pas fourni)
+    pub planning_event: Option<EdsquarePlanningEvent>,
+    /// Résultat de la vérification (si demandée)
+    pub verification: Option<SignatureVerification>,
+}
+
+/// Résultat de la vérification d'une signature EDSquare après envoi
+#[derive(Serialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SignatureVerification {
+    /// EDSquare affiche ce cours comme signé
+    Verified,
+    /// Impossible de relire l'état sur EDSquare, ou le cours n'y apparaît pas comme signé
+    Unverified,
+    /// EDSquare propose encore de signer ce cours : la signature n'a pas été prise en compte
+    Contradicted,
 }
 
 /// Payload pour la validation multi-utilisateurs côté EDSquare
@@ -23,7 +45,9 @@ pub struct ValidateEdsquareResponse {
 pub struct ValidateEdsquareMultiPayload {
     #[schema(example = "000000")]
     pub code: String,
+    /// Event ID commun ; facultatif si `auto_match` ou `user_planning_event_ids` couvre tout le monde.
     #[schema(example = "199289")]
+    #[serde(default)]
     pub planning_event_id: String,
     /// Liste des IDs d'utilisateurs à valider
     pub user_ids: Vec<String>,
@@ -33,17 +57,56 @@ pub struct ValidateEdsquareMultiPayload {
     /// Code EDSquare par utilisateur (quand les cours diffèrent, le code diffère aussi). Clé = user_id, valeur = code 6 chiffres.
     #[serde(default)]
     pub user_codes: Option<std::collections::HashMap<String, String>>,
+    /// Retrouve l'event ID de chaque utilisateur dans son propre planning (sauf ceux présents dans user_planning_event_ids).
+    #[serde(default)]
+    pub auto_match: bool,
+    /// Cours à retrouver pour `auto_match` ; à défaut, le cours en cours maintenant. Implique `auto_match`.
+    #[serde(default)]
+    pub reference_event: Option<ReferencePlanningEvent>,
+    /// Relire EDSquare après l'envoi pour confirmer chaque signature
+    #[serde(default)]
+    pub verify: bool,
 }
 
-/// Résultat de validation pour un utilisateur donné
-#[derive(Serialize, ToSchema)]
-pub struct EdsquareUserValidationResult {
-    pub user_id: String,
-    pub username: String,
-    pub success: bool,
-    pub message: String,
+/// Cours de référence pour retrouver le même cours dans le planning de chaque utilisateur
+#[derive(Deserialize, ToSchema, Debug, Clone)]
+pub struct ReferencePlanningEvent {
+    #[schema(example = "Algorithmique")]
+    pub title: Option<String>,
+    /// Début (RFC 3339)
+    #[schema(example = "2026-10-19T09:00:00+02:00")]
+    pub start: Option<String>,
+    /// Fin (RFC 3339)
+    #[schema(example = "2026-10-19T12:00:00+02:00")]
+    pub end: Option<String>,
 }
 
+/// Issue de la recherche du cours d'un utilisateur dans son planning
+#[derive(Serialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EventMatchStatus {
+    Matched,
+    Ambiguous,
+    Unmatched,
+    Error,
+}
+
+/// Détail EDSquare du résultat de validation d'un utilisateur (voir `UserAttendanceResult`)
+#[derive(Serialize, ToSchema, Default)]
+pub struct EdsquareValidationDetail {
+    /// Event ID utilisé pour cet utilisateur
+    pub planning_event_id: Option<String>,
+    /// Issue de la recherche automatique du cours (absent si l'event ID était fourni)
+    pub event_match: Option<EventMatchStatus>,
+    /// Cours candidats quand la recherche est ambiguë
+    pub candidates: Vec<EdsquarePlanningEvent>,
+    /// Résultat de la vérification (si demandée)
+    pub verification: Option<SignatureVerification>,
+}
+
+/// Résultat de validation pour un utilisateur donné
+pub type EdsquareUserValidationResult = UserAttendanceResult<EdsquareValidationDetail>;
+
 /// Réponse globale pour la validation multi-utilisateurs
 #[derive(Serialize, ToSchema)]
 pub struct ValidateEdsquareMultiResponse {
@@ -79,7 +142,15 @@ pub struct EdsquareStatusResponse {
     pub has_signature: bool,
     pub has_cookies: bool,
     pub has_saved_credentials: bool,
+    /// Reconnexion automatique suspendue après trop de refus des identifiants : ressaisir le mot de passe
+    pub credentials_suspended: bool,
+    /// Refus consécutifs des identifiants sauvegardés
+    pub failed_login_count: i32,
+    /// Date du dernier refus (RFC 3339), absente depuis la dernière connexion réussie
+    pub last_failed_login_at: Option<String>,
     pub is_ready: bool,
+    /// Dernier contrôle de session par la tâche de maintien (absent si jamais contrôlé)
+    pub session_health: Option<EdsquareSessionHealth>,
 }
 
 /// Utilisateur éligible pour la signature EDSquare (signature + cookies valides)
@@ -94,6 +165,24 @@ pub struct EdsquareEligibleUsersResponse {
     pub users: Vec<EdsquareEligibleUser>,
 }
 
+/// Dernier contrôle de la session EDSquare d'un utilisateur par la tâche de maintien de session
+#[derive(Serialize, ToSchema, Debug, Clone)]
+pub struct EdsquareSessionHealth {
+    pub user_id: String,
+    pub username: String,
+    /// false : la session est expirée (ou sur le point de l'être) et n'a pas pu être rafraîchie
+    pub healthy: bool,
+    /// true si une reconnexion avec les identifiants sauvegardés a été faite lors du contrôle
+    pub refreshed: bool,
+    pub message: String,
+    pub checked_at: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct EdsquareSessionAlertsResponse {
+    pub alerts: Vec<EdsquareSessionHealth>,
+}
+
 /// Événement du planning EDSquare (json_dashboard)
 #[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
 pub struct EdsquarePlanningEvent {
@@ -134,3 +223,44 @@ pub struct UserPlanningEvents {
 pub struct PlanningEventsForUsersResponse {
     pub user_events: Vec<UserPlanningEvents>,
 }
+
+/// Cours EDSquare d'une période (bornes incluses)
+#[derive(Serialize, ToSchema)]
+pub struct EdsquarePlanningRangeResponse {
+    pub from: String,
+    pub to: String,
+    pub events: Vec<EdsquarePlanningEvent>,
+}
+
+/// Jeton du flux iCalendar personnel (l'URL du flux suffit pour y accéder, sans JWT)
+#[derive(Serialize, ToSchema)]
+pub struct CalendarTokenResponse {
+    pub token: String,
+    /// Chemin du flux, à préfixer par l'URL publique du backend
+    pub feed_path: String,
+    pub created_at: String,
+}
+
+/// Payload pour retrouver le cours de plusieurs utilisateurs sans valider
+#[derive(Deserialize, ToSchema, Debug)]
+pub struct MatchPlanningEventsPayload {
+    pub user_ids: Vec<String>,
+    #[serde(default)]
+    pub reference_event: Option<ReferencePlanningEvent>,
+}
+
+/// Cours retrouvé (ou non) pour un utilisateur
+#[derive(Serialize, ToSchema)]
+pub struct UserEventMatch {
+    pub user_id: String,
+    pub username: String,
+    pub status: EventMatchStatus,
+    pub planning_event_id: Option<String>,
+    pub candidates: Vec<EdsquarePlanningEvent>,
+    pub message: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct MatchPlanningEventsResponse {
+    pub matches: Vec<UserEventMatch>,
+}
@@ -0,0 +1,271 @@
+use std::{collections::HashSet, time::Duration};
+
+use chrono::NaiveDate;
+use tracing::{error, info, warn};
+
+use crate::api::attendance::{AttendanceEvent, AttendanceProvider, Readiness, UserAttendanceResult, WebhookBilan};
+use crate::api::edsquare::{
+    matching::{EventMatch, MatchReference, resolve_planning_event_for_user},
+    models::{
+        EdsquareUserValidationResult, EdsquareValidationDetail, EventMatchStatus, SignatureVerification,
+        ValidateEdsquareMultiPayload,
+    },
+    services::{
+        fetch_planning_events, get_edsquare_cookies, get_edsquare_credential_status, validate_edsquare_code,
+        verify_edsquare_signature,
+    },
+};
+use crate::api::users::{User, get_signature_for_submission, has_enabled_signature, record_signature_use};
+use crate::misc::{GlobalState, ServerEvent, UserResultEvent};
+
+/// Message quand EDSquare propose encore de signer le cours après l'envoi.
+pub(super) const CONTRADICTED_MESSAGE: &str = "Code envoyé mais EDSquare propose encore de signer ce cours : signature non enregistrée";
+
+/// Dimensions du PNG de signature envoyé à EDSquare (largeur, hauteur).
+pub(super) const SIGNATURE_SIZE: (u32, u32) = (800, 300);
+
+/// Validation de présence EDSquare (code à 6 chiffres + signature manuscrite de l'utilisateur).
+pub struct EdsquareProvider;
+
+/// Validation EDSquare à soumettre pour chaque utilisateur.
+pub struct EdsquareSubmission {
+    pub payload: ValidateEdsquareMultiPayload,
+    /// Cours de référence quand l'event ID est retrouvé dans le planning de chaque utilisateur (`auto_match`)
+    pub reference: Option<MatchReference>,
+}
+
+impl EdsquareSubmission {
+    /// Code pour cet utilisateur : override par user_codes si présent (cours différents = code différent)
+    fn code_for(&self, user_id: &str) -> &str {
+        self.payload
+            .user_codes
+            .as_ref()
+            .and_then(|m| m.get(user_id))
+            .map(|s| s.as_str())
+            .unwrap_or(self.payload.code.as_str())
+    }
+}
+
+impl AttendanceProvider for EdsquareProvider {
+    type Submission = EdsquareSubmission;
+    type Detail = EdsquareValidationDetail;
+
+    fn name(&self) -> &'static str {
+        "edsquare"
+    }
+
+    fn limits(&self, state: &GlobalState) -> (usize, Duration) {
+        (state.edsquare_concurrency, state.edsquare_user_timeout)
+    }
+
+    fn readiness(&self, state: &GlobalState, user: &User) -> Readiness {
+        if !has_enabled_signature(state, &user.id).unwrap_or(false) {
+            return Readiness::not_ready("Aucune signature active");
+        }
+
+        let has_cookies = match get_edsquare_cookies(state, &user.id) {
+            Ok(cookies) => cookies.is_some(),
+            Err(e) => {
+                error!("Error fetching EDSquare cookies for user {} ({}): {}", user.username, user.id, e);
+                false
+            }
+        };
+        // Identifiants suspendus : la reconnexion automatique échouerait
+        let can_reconnect = get_edsquare_credential_status(state, &user.id)
+            .ok()
+            .flatten()
+            .is_some_and(|s| !s.suspended);
+
+        if has_cookies || can_reconnect {
+            Readiness::ready()
+        } else {
+            Readiness::not_ready("Session EDSquare absente ou expirée")
+        }
+    }
+
+    /// Avec une référence (`auto_match`), l'event ID est retrouvé dans le planning de l'utilisateur.
+    async fn submit_for_user(
+        &self,
+        state: &GlobalState,
+        user: &User,
+        submission: &EdsquareSubmission,
+    ) -> EdsquareUserValidationResult {
+        let failure = |message: String, detail: EdsquareValidationDetail| UserAttendanceResult {
+            user_id: user.id.clone(),
+            username: user.username.clone(),
+            success: false,
+            message,
+            detail,
+        };
+
+        // Choisir une signature selon la rotation de l'utilisateur, rendue au format EDSquare
+        let (width, height) = SIGNATURE_SIZE;
+        let signature = match get_signature_for_submission(state, user, width, height).await {
+            Ok(Some(sig)) => sig,
+            Ok(None) => {
+                warn!("Aucune signature pour user {} ({}) in multi-validate", user.username, user.id);
+                return failure("Signature not set. Please create a signature first.".to_string(), Default::default());
+            }
+            Err(_) => return failure("Error fetching signature.".to_string(), Default::default()),
+        };
+
+        let code = submission.code_for(&user.id);
+        if code.len() != 6 {
+            return failure(
+                format!("Le code doit contenir 6 chiffres, reçu: {} caractères", code.len()),
+                Default::default(),
+            );
+        }
+
+        // Event ID pour cet utilisateur : override par user_planning_event_ids si présent,
+        // sinon recherche dans son propre planning (auto_match), sinon l'event ID commun
+        let explicit_event_id = submission
+            .payload
+            .user_planning_event_ids
+            .as_ref()
+            .and_then(|m| m.get(&user.id))
+            .cloned();
+        let (planning_event_id, event_match) = match (explicit_event_id, submission.reference.as_ref()) {
+            (Some(event_id), _) => (event_id, None),
+            (None, Some(reference)) => match resolve_planning_event_for_user(state, &user.id, reference).await {
+                Ok(EventMatch::Matched(event)) => (event.id.to_string(), Some(EventMatchStatus::Matched)),
+                Ok(other) => {
+                    warn!("Cours EDSquare introuvable pour {} ({}): {:?}", user.username, user.id, other.status());
+                    return failure(
+                        other.failure_message().unwrap_or_default(),
+                        EdsquareValidationDetail {
+                            event_match: Some(other.status()),
+                            candidates: other.candidates(),
+                            ..Default::default()
+                        },
+                    );
+                }
+                Err(e) => {
+                    error!("Erreur planning EDSquare pour {} ({}): {}", user.username, user.id, e);
+                    return failure(
+                        format!("Impossible de retrouver le cours: {}", e),
+                        EdsquareValidationDetail {
+                            event_match: Some(EventMatchStatus::Error),
+                            ..Default::default()
+                        },
+                    );
+                }
+            },
+            (None, None) => (submission.payload.planning_event_id.clone(), None),
+        };
+
+        match validate_edsquare_code(code, &planning_event_id, &signature.data_url, &user.id, state).await {
+            Ok(_resp) => {
+                info!("EDSquare code validated successfully for user {} ({})", user.username, user.id);
+                let verification = if submission.payload.verify {
+                    Some(verify_edsquare_signature(state, &user.id, &planning_event_id).await)
+                } else {
+                    None
+                };
+                let contradicted = verification == Some(SignatureVerification::Contradicted);
+                if !contradicted && let Err(e) = record_signature_use(state, &signature.signature_id) {
+                    warn!("Utilisation de la signature {} non comptée: {:?}", signature.signature_id, e);
+                }
+                UserAttendanceResult {
+                    user_id: user.id.clone(),
+                    username: user.username.clone(),
+                    success: !contradicted,
+                    message: if contradicted {
+                        CONTRADICTED_MESSAGE.to_string()
+                    } else {
+                        "Code validé avec succès".to_string()
+                    },
+                    detail: EdsquareValidationDetail {
+                        verification,
+                        planning_event_id: Some(planning_event_id),
+                        event_match,
+                        ..Default::default()
+                    },
+                }
+            }
+            Err(e) => {
+                error!("Error validating EDSquare code for user {} ({}): {}", user.username, user.id, e);
+                failure(
+                    e.to_string(),
+                    EdsquareValidationDetail {
+                        planning_event_id: Some(planning_event_id),
+                        event_match,
+                        ..Default::default()
+                    },
+                )
+            }
+        }
+    }
+
+    async fn fetch_events(&self, state: &GlobalState, user: &User, date: NaiveDate) -> Result<Vec<AttendanceEvent>, String> {
+        let events = fetch_planning_events(state, &user.id, date).await.map_err(|e| e.to_string())?;
+        Ok(events
+            .into_iter()
+            .map(|event| AttendanceEvent {
+                id: event.id.to_string(),
+                title: event.title,
+                start: event.start,
+                end: event.end,
+            })
+            .collect())
+    }
+
+    fn results_event(
+        &self,
+        initiated_by: &str,
+        _submission: &EdsquareSubmission,
+        results: &[EdsquareUserValidationResult],
+        user_results: Vec<UserResultEvent>,
+    ) -> ServerEvent {
+        ServerEvent::EdsquareValidation {
+            initiated_by: initiated_by.to_string(),
+            global_success: results.iter().all(|r| r.success),
+            results: user_results,
+        }
+    }
+
+    fn webhook_bilan(
+        &self,
+        state: &GlobalState,
+        initiated_by: &str,
+        submission: &EdsquareSubmission,
+        results: &[EdsquareUserValidationResult],
+    ) -> Option<(String, WebhookBilan)> {
+        let webhook_url = state.edsquare_webhook_url.clone()?;
+        let validated: Vec<String> = results.iter().filter(|r| r.success).map(|r| r.username.clone()).collect();
+        if validated.is_empty() {
+            return None;
+        }
+        let validated_codes: Vec<String> = results
+            .iter()
+            .filter(|r| r.success)
+            .map(|r| submission.code_for(&r.user_id).to_string())
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+
+        let mut context = Vec::new();
+        if !validated_codes.is_empty() {
+            context.push(("Code(s) validé(s)".to_string(), validated_codes.join(", ")));
+        }
+        let mut extra = serde_json::Map::new();
+        extra.insert("global_success".to_string(), serde_json::json!(results.iter().all(|r| r.success)));
+        extra.insert("validated_codes".to_string(), serde_json::json!(validated_codes));
+        Some((
+            webhook_url,
+            WebhookBilan {
+                title: "Bilan EDSquare".to_string(),
+                event: "edsquare_validation_multi".to_string(),
+                initiated_by: initiated_by.to_string(),
+                context,
+                validated,
+                failed: results
+                    .iter()
+                    .filter(|r| !r.success)
+                    .map(|r| (r.username.clone(), r.message.clone()))
+                    .collect(),
+                extra,
+            },
+        ))
+    }
+}
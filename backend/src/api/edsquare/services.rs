@@ -1,15 +1,19 @@
 use crate::api::{
+    edsquare::client::EdsquareClient,
+    edsquare::errors::{EdsquareError, error_from_js_response},
+    edsquare::keepalive::clear_session_alert,
     edsquare::models::{
         ValidateEdsquareResponse, EdsquareCookieItem, LoginEdsquareResponse,
-        EdsquarePlanningEvent,
+        EdsquarePlanningEvent, SignatureVerification,
     },
     sign::CookieItem,
 };
-use crate::misc::{GlobalState, PlanningEventsCacheEntry};
+use crate::misc::{GlobalState, PlanningEventsCacheEntry, ServerEvent};
 use http::header::COOKIE;
 use http::StatusCode;
 use tracing::{error, info, warn, debug};
-use chrono::NaiveDate;
+use chrono::{NaiveDate, NaiveDateTime};
+use chrono_tz::Tz;
 use serde_json::Value;
 use diesel::prelude::*;
 use urlencoding::encode;
@@ -18,12 +22,45 @@ use std::time::{SystemTime, Duration};
 #[derive(diesel::Queryable, diesel::Selectable)]
 #[diesel(table_name = crate::schema::edsquare_cookies)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
-#[allow(dead_code)] // Les champs id, date et user_id sont utilisés dans les requêtes Diesel
+#[allow(dead_code)] // Les champs id et user_id sont utilisés dans les requêtes Diesel
 struct EdsquareCookie {
     id: String,
     user_id: String,
-    date: NaiveDate,
     cookie_data: Value,
+    expires_at: Option<NaiveDateTime>,
+    last_verified_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+}
+
+/// Durée pendant laquelle un jeu de cookies sans date d'expiration (cookies de session) reste utilisable
+/// après sa dernière utilisation réussie.
+const SESSION_COOKIES_MAX_IDLE_HOURS: i64 = 24;
+/// Fréquence du nettoyage des cookies EDSquare périmés.
+const COOKIE_GC_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+impl EdsquareCookie {
+    fn is_stale(&self, now: NaiveDateTime) -> bool {
+        match self.expires_at {
+            Some(expires_at) => expires_at <= now,
+            None => self.last_verified_at + chrono::Duration::hours(SESSION_COOKIES_MAX_IDLE_HOURS) <= now,
+        }
+    }
+}
+
+/// Cookies qui portent la session EDSquare (Devise). Les autres (mesure d'audience, préférences)
+/// peuvent expirer sans que la session ne soit perdue.
+const SESSION_COOKIE_NAMES: [&str; 2] = ["_edsquare_session", "remember_user_token"];
+
+/// Expiration de la session : première expiration parmi les cookies de session qui en ont une
+/// (`None` si ce sont des cookies de navigateur, sans date).
+pub(crate) fn jar_expires_at(cookie_items: &[EdsquareCookieItem]) -> Option<NaiveDateTime> {
+    cookie_items
+        .iter()
+        .filter(|c| SESSION_COOKIE_NAMES.contains(&c.name.as_str()))
+        .filter_map(|c| c.expires)
+        .min()
+        .and_then(|ts| chrono::DateTime::from_timestamp(ts, 0))
+        .map(|dt| dt.naive_utc())
 }
 
 #[derive(diesel::Queryable, diesel::Selectable)]
@@ -35,6 +72,17 @@ struct EdsquareCredential {
     user_id: String,
     email: String,
     password: String,
+    failed_login_count: i32,
+    last_failed_login_at: Option<NaiveDateTime>,
+    suspended_at: Option<NaiveDateTime>,
+}
+
+/// État des identifiants sauvegardés vis-à-vis de la reconnexion automatique.
+#[derive(Debug, Clone)]
+pub struct EdsquareCredentialStatus {
+    pub failed_login_count: i32,
+    pub last_failed_login_at: Option<NaiveDateTime>,
+    pub suspended: bool,
 }
 
 /// Sauvegarde ou met à jour les identifiants EDSquare pour un utilisateur.
@@ -67,8 +115,15 @@ pub fn save_edsquare_credentials(
             "Mise à jour des identifiants EDSquare pour l'utilisateur {}",
             user_id_param
         );
+        // Identifiants (re)validés par une connexion réussie : on réarme la reconnexion automatique
         diesel::update(edsquare_credentials.filter(id.eq(cred.id)))
-            .set((email.eq(email_param), password.eq(password_param)))
+            .set((
+                email.eq(email_param),
+                password.eq(password_param),
+                failed_login_count.eq(0),
+                last_failed_login_at.eq(None::<NaiveDateTime>),
+                suspended_at.eq(None::<NaiveDateTime>),
+            ))
             .execute(&mut conn)
             .map_err(|e| {
                 error!("Erreur lors de la mise à jour des identifiants EDSquare: {}", e);
@@ -122,33 +177,90 @@ pub fn get_edsquare_credentials(
     Ok(existing.map(|cred| (cred.email, cred.password)))
 }
 
+/// Compteur d'échecs et suspension des identifiants sauvegardés (`None` si aucun identifiant).
+pub fn get_edsquare_credential_status(
+    state: &GlobalState,
+    user_id_param: &str,
+) -> Result<Option<EdsquareCredentialStatus>, String> {
+    use crate::schema::edsquare_credentials::dsl::*;
+
+    let mut conn = state
+        .get_db_conn()
+        .map_err(|_| "Failed to get database connection".to_string())?;
+    let existing = edsquare_credentials
+        .filter(user_id.eq(user_id_param))
+        .select(EdsquareCredential::as_select())
+        .first::<EdsquareCredential>(&mut conn)
+        .optional()
+        .map_err(|e| format!("Database error when fetching EDSquare credentials: {}", e))?;
+
+    Ok(existing.map(|cred| EdsquareCredentialStatus {
+        failed_login_count: cred.failed_login_count,
+        last_failed_login_at: cred.last_failed_login_at,
+        suspended: cred.suspended_at.is_some(),
+    }))
+}
+
+/// Enregistre un refus des identifiants sauvegardés ; suspend la reconnexion automatique au-delà du seuil.
+/// Retourne le nombre d'échecs et si les identifiants viennent d'être suspendus.
+fn record_edsquare_login_failure(state: &GlobalState, user_id_param: &str) -> Result<(i32, bool), String> {
+    use crate::schema::edsquare_credentials::dsl::*;
+
+    let mut conn = state
+        .get_db_conn()
+        .map_err(|_| "Failed to get database connection".to_string())?;
+    let now = chrono::Utc::now().naive_utc();
+    let count: i32 = diesel::update(edsquare_credentials.filter(user_id.eq(user_id_param)))
+        .set((failed_login_count.eq(failed_login_count + 1), last_failed_login_at.eq(now)))
+        .returning(failed_login_count)
+        .get_result(&mut conn)
+        .map_err(|e| format!("Database error when updating EDSquare credentials: {}", e))?;
+
+    if count < state.edsquare_max_login_failures {
+        return Ok((count, false));
+    }
+    let suspended = diesel::update(
+        edsquare_credentials
+            .filter(user_id.eq(user_id_param))
+            .filter(suspended_at.is_null()),
+    )
+    .set(suspended_at.eq(now))
+    .execute(&mut conn)
+    .map_err(|e| format!("Database error when updating EDSquare credentials: {}", e))?;
+    Ok((count, suspended > 0))
+}
+
 pub fn get_edsquare_cookies(state: &GlobalState, user_id_param: &str) -> Result<Option<Vec<EdsquareCookieItem>>, String> {
     use crate::schema::edsquare_cookies;
     use crate::schema::edsquare_cookies::dsl::*;
 
-    let current_date = chrono::Utc::now().date_naive();
-
     let mut conn = match state.get_db_conn() {
         Ok(conn) => conn,
         Err(_) => return Err("Failed to get database connection".into()),
     };
 
-    info!("Récupération des cookies EDSquare pour l'utilisateur {} et la date: {}", user_id_param, current_date);
+    info!("Récupération des cookies EDSquare pour l'utilisateur {}", user_id_param);
 
     let cookie_record = edsquare_cookies::table
         .filter(user_id.eq(user_id_param))
-        .filter(date.eq(current_date))
         .select(EdsquareCookie::as_select())
         .first::<EdsquareCookie>(&mut conn)
         .optional();
 
     let cookie = match cookie_record {
+        Ok(Some(cookie)) if cookie.is_stale(chrono::Utc::now().naive_utc()) => {
+            warn!(
+                "Cookies EDSquare périmés pour l'utilisateur {} (expiration: {:?}, dernière vérification: {})",
+                user_id_param, cookie.expires_at, cookie.last_verified_at
+            );
+            return Ok(None);
+        }
         Ok(Some(cookie)) => {
-            info!("Cookies EDSquare trouvés pour l'utilisateur {} à la date {}", user_id_param, current_date);
+            info!("Cookies EDSquare trouvés pour l'utilisateur {} (expiration: {:?})", user_id_param, cookie.expires_at);
             cookie
         },
         Ok(None) => {
-            warn!("Aucun cookie EDSquare trouvé pour l'utilisateur {} à la date {}", user_id_param, current_date);
+            warn!("Aucun cookie EDSquare trouvé pour l'utilisateur {}", user_id_param);
             return Ok(None);
         }
         Err(e) => {
@@ -208,84 +320,188 @@ pub fn get_edsquare_cookies(state: &GlobalState, user_id_param: &str) -> Result<
     }
 }
 
-/// Invalide les cookies EDSquare stockés pour un utilisateur (date du jour). Force une reconnexion au prochain appel.
+/// Invalide les cookies EDSquare stockés pour un utilisateur. Force une reconnexion au prochain appel.
 pub fn clear_edsquare_cookies_for_user(state: &GlobalState, user_id_param: &str) -> Result<(), String> {
     use crate::schema::edsquare_cookies::dsl::*;
     use diesel::prelude::*;
 
-    let current_date = chrono::Utc::now().date_naive();
     let mut conn = match state.get_db_conn() {
         Ok(conn) => conn,
         Err(_) => return Err("Failed to get database connection".into()),
     };
 
-    diesel::delete(edsquare_cookies.filter(user_id.eq(user_id_param)).filter(date.eq(current_date)))
+    diesel::delete(edsquare_cookies.filter(user_id.eq(user_id_param)))
         .execute(&mut conn)
         .map_err(|e| {
             error!("Erreur lors de la suppression des cookies EDSquare: {}", e);
             format!("Failed to clear EDSquare cookies: {}", e)
         })?;
     info!("Cookies EDSquare invalidés pour l'utilisateur {} (session expirée)", user_id_param);
+    publish_edsquare_cookie_status(state, user_id_param, false);
+    Ok(())
+}
+
+/// Note que les cookies EDSquare de l'utilisateur viennent d'être acceptés par EDSquare.
+pub fn mark_edsquare_cookies_verified(state: &GlobalState, user_id_param: &str) -> Result<(), String> {
+    use crate::schema::edsquare_cookies::dsl::*;
+
+    let mut conn = state
+        .get_db_conn()
+        .map_err(|_| "Failed to get database connection".to_string())?;
+    diesel::update(edsquare_cookies.filter(user_id.eq(user_id_param)))
+        .set(last_verified_at.eq(chrono::Utc::now().naive_utc()))
+        .execute(&mut conn)
+        .map_err(|e| format!("Failed to update EDSquare cookies: {}", e))?;
     Ok(())
 }
 
+/// Supprime les jeux de cookies expirés (ou de session inutilisés depuis trop longtemps).
+/// Retourne les utilisateurs concernés.
+pub fn purge_stale_edsquare_cookies(state: &GlobalState) -> Result<Vec<String>, String> {
+    use crate::schema::edsquare_cookies::dsl::*;
+
+    let now = chrono::Utc::now().naive_utc();
+    let idle_limit = now - chrono::Duration::hours(SESSION_COOKIES_MAX_IDLE_HOURS);
+    let mut conn = state
+        .get_db_conn()
+        .map_err(|_| "Failed to get database connection".to_string())?;
+    diesel::delete(
+        edsquare_cookies.filter(
+            expires_at
+                .le(now)
+                .or(expires_at.is_null().and(last_verified_at.le(idle_limit))),
+        ),
+    )
+    .returning(user_id)
+    .get_results(&mut conn)
+    .map_err(|e| format!("Failed to purge EDSquare cookies: {}", e))
+}
+
+/// Lance le nettoyage périodique des cookies EDSquare périmés.
+pub fn spawn_edsquare_cookie_gc(state: GlobalState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(COOKIE_GC_INTERVAL);
+        loop {
+            interval.tick().await;
+            match purge_stale_edsquare_cookies(&state) {
+                Ok(users) if !users.is_empty() => {
+                    info!("{} jeu(x) de cookies EDSquare périmé(s) supprimé(s)", users.len());
+                    for uid in users {
+                        publish_edsquare_cookie_status(&state, &uid, false);
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => error!("Nettoyage des cookies EDSquare: {}", e),
+            }
+        }
+    });
+}
+
 /// Récupère les cookies EDSquare ; si aucun ou expirés, tente une reconnexion automatique avec les identifiants sauvegardés.
 pub async fn get_edsquare_cookies_or_reconnect(
     state: &GlobalState,
     user_id_param: &str,
-) -> Result<Vec<EdsquareCookieItem>, String> {
-    match get_edsquare_cookies(state, user_id_param) {
-        Ok(Some(cookies)) => return Ok(cookies),
+) -> Result<Vec<EdsquareCookieItem>, EdsquareError> {
+    match get_edsquare_cookies(state, user_id_param).map_err(EdsquareError::Storage) {
+        Ok(Some(cookies)) => Ok(cookies),
         Ok(None) => {
             info!(
                 "Pas de cookie EDSquare valide pour {}, tentative de reconnexion avec identifiants sauvegardés",
                 user_id_param
             );
-            if let Err(e) = login_edsquare_with_saved(user_id_param, state).await {
-                return Err(e);
-            }
-            match get_edsquare_cookies(state, user_id_param) {
-                Ok(Some(cookies)) => Ok(cookies),
-                Ok(None) => Err("Reconnexion EDSquare effectuée mais aucun cookie reçu.".into()),
-                Err(e) => Err(e),
-            }
+            reconnect_edsquare(state, user_id_param, None).await
         }
         Err(e) => Err(e),
     }
 }
 
-/// Effectue une tentative de validation EDSquare (cookies déjà récupérés ou à récupérer).
-async fn validate_edsquare_code_once(
-    code: &str,
-    planning_event_id: &str,
-    signature: &str,
-    user_id: &str,
+/// Reconnexion EDSquare partagée : un seul login à la fois par utilisateur.
+/// `stale` contient les cookies rejetés par EDSquare (`None` si aucun cookie). Si d'autres cookies
+/// ont été enregistrés pendant l'attente du verrou, un autre appel s'est déjà reconnecté : on les réutilise.
+pub async fn reconnect_edsquare(
     state: &GlobalState,
-) -> Result<ValidateEdsquareResponse, String> {
-    // Récupérer les cookies EDSquare (reconnexion auto si identifiants sauvegardés)
-    let cookies = get_edsquare_cookies_or_reconnect(state, user_id).await?;
+    user_id_param: &str,
+    stale: Option<&[EdsquareCookieItem]>,
+) -> Result<Vec<EdsquareCookieItem>, EdsquareError> {
+    let lock = state.edsquare_login_lock(user_id_param);
+    let _guard = lock.lock().await;
 
-    let client = match get_reqwest_client() {
-        Ok(client) => client,
-        Err(e) => {
-            return Err(format!("Failed to create HTTP client: {}", e));
-        }
-    };
+    if let Ok(Some(current)) = get_edsquare_cookies(state, user_id_param)
+        && stale.is_none_or(|stale| cookie_header(stale) != cookie_header(&current))
+    {
+        info!("Reconnexion EDSquare déjà effectuée pour {}, réutilisation des cookies", user_id_param);
+        return Ok(current);
+    }
 
-    // Construire la chaîne de cookies
-    let cookie_str = cookies
+    if stale.is_some() {
+        clear_edsquare_cookies_for_user(state, user_id_param).map_err(EdsquareError::Storage)?;
+    }
+    login_edsquare_with_saved(user_id_param, state).await?;
+    match get_edsquare_cookies(state, user_id_param) {
+        Ok(Some(cookies)) => Ok(cookies),
+        Ok(None) => Err(EdsquareError::UnexpectedResponse(
+            "Reconnexion EDSquare effectuée mais aucun cookie reçu.".into(),
+        )),
+        Err(e) => Err(EdsquareError::Storage(e)),
+    }
+}
+
+/// Reconnexion avec les identifiants sauvegardés avant l'expiration de la session, sans effacer les cookies
+/// encore valides (ils ne sont remplacés qu'en cas de succès).
+pub async fn refresh_edsquare_session(
+    state: &GlobalState,
+    user_id_param: &str,
+) -> Result<Vec<EdsquareCookieItem>, EdsquareError> {
+    let lock = state.edsquare_login_lock(user_id_param);
+    let _guard = lock.lock().await;
+
+    login_edsquare_with_saved(user_id_param, state).await?;
+    match get_edsquare_cookies(state, user_id_param) {
+        Ok(Some(cookies)) => Ok(cookies),
+        Ok(None) => Err(EdsquareError::UnexpectedResponse(
+            "Reconnexion EDSquare effectuée mais aucun cookie reçu.".into(),
+        )),
+        Err(e) => Err(EdsquareError::Storage(e)),
+    }
+}
+
+/// Vérifie que la session EDSquare est encore ouverte (page /apps/classrooms sans redirection vers la connexion).
+pub async fn check_edsquare_session(
+    client: &EdsquareClient,
+    cookies: &[EdsquareCookieItem],
+) -> Result<(), EdsquareError> {
+    match fetch_classrooms_page(client, &cookie_header(cookies)).await? {
+        Some(_) => Ok(()),
+        None => Err(EdsquareError::UnexpectedResponse(
+            "la page des cours EDSquare n'a pas pu être lue".into(),
+        )),
+    }
+}
+
+fn cookie_header(cookies: &[EdsquareCookieItem]) -> String {
+    cookies
         .iter()
         .map(|c| c.to_header_value())
         .collect::<Vec<_>>()
-        .join("; ");
+        .join("; ")
+}
+
+/// Effectue une tentative de validation EDSquare avec les cookies fournis.
+async fn validate_edsquare_code_once(
+    client: &EdsquareClient,
+    code: &str,
+    planning_event_id: &str,
+    signature: &str,
+    cookies: &[EdsquareCookieItem],
+) -> Result<ValidateEdsquareResponse, EdsquareError> {
+    // Construire la chaîne de cookies
+    let cookie_str = cookie_header(cookies);
 
     // Récupérer le CSRF token en appelant une page EDSquare qui le fournit dans le HTML
-    let csrf_token = match fetch_csrf_token_with_cookies(&cookie_str).await {
+    let csrf_token = match fetch_csrf_token_with_cookies(client, &cookie_str).await {
         Ok(token_opt) => token_opt,
+        Err(EdsquareError::SessionExpired) => return Err(EdsquareError::SessionExpired),
         Err(e) => {
-            if e.contains("Session EDSquare expirée") {
-                return Err(e);
-            }
             warn!("Impossible de récupérer le CSRF token EDSquare: {}", e);
             None
         }
@@ -301,7 +517,10 @@ async fn validate_edsquare_code_once(
     
     // Vérifier que le code a exactement 6 caractères
     if code.len() != 6 {
-        return Err(format!("Le code secret doit contenir exactement 6 chiffres, reçu: {} caractères", code.len()));
+        return Err(EdsquareError::InvalidCode(format!(
+            "le code secret doit contenir exactement 6 chiffres, reçu: {} caractères",
+            code.len()
+        )));
     }
     
     // Diviser le code en 6 parties
@@ -352,20 +571,21 @@ async fn validate_edsquare_code_once(
     );
     
     // Endpoint pour envoyer la signature (pas check_secret_code qui sert juste à vérifier le code)
-    let endpoint_url = "https://app.edsquare.fr/apps/course_user_signatures";
+    let endpoint_url = client.url("/apps/course_user_signatures");
     info!("Envoi de la requête vers: {}", endpoint_url);
     
     let mut request_builder = client
-        .post(endpoint_url)
+        .http()
+        .post(&endpoint_url)
         .header(COOKIE, &cookie_str)
         .header("Accept", "text/javascript, application/javascript, application/ecmascript, application/x-ecmascript, */*; q=0.01")
         .header("Accept-Language", "fr-FR,fr;q=0.9,en-US;q=0.8,en;q=0.7")
         .header("Cache-Control", "no-cache")
         .header("Content-Type", "application/x-www-form-urlencoded; charset=UTF-8")
-        .header("Origin", "https://app.edsquare.fr")
+        .header("Origin", client.base_url())
         .header("Pragma", "no-cache")
         .header("Priority", "u=1, i")
-        .header("Referer", "https://app.edsquare.fr/apps/classrooms")
+        .header("Referer", client.url("/apps/classrooms"))
         .header("Sec-Ch-Ua", r#""Not(A:Brand";v="8", "Chromium";v="144", "Google Chrome";v="144""#)
         .header("Sec-Ch-Ua-Mobile", "?0")
         .header("Sec-Ch-Ua-Platform", r#""macOS""#)
@@ -385,7 +605,7 @@ async fn validate_edsquare_code_once(
         Ok(resp) => resp,
         Err(e) => {
             error!("HTTP request failed: {}", e);
-            return Err(format!("Erreur lors de la requête HTTP: {}", e));
+            return Err(EdsquareError::Network(e.to_string()));
         }
     };
 
@@ -398,60 +618,64 @@ async fn validate_edsquare_code_once(
         info!("Réponse EDSquare brute: {}", response_text);
     }
 
+    let preview = if response_text.len() > 200 {
+        format!("{}...", response_text.chars().take(200).collect::<String>())
+    } else {
+        response_text.clone()
+    };
+
     match status {
         StatusCode::OK => {
             // Même en 200, EDSquare peut renvoyer un JS avec un toastr d'erreur
             // Exemple : toastr.error("Le code saisi n&#39;est plus valide")
             // "Événement introuvable" = déjà validé/signé par quelqu'un → on le traite comme succès
-            if let Ok(re) = regex::Regex::new(r#"toastr\.error\("([^"]+)""#) {
-                if let Some(caps) = re.captures(&response_text) {
-                    let raw_msg = caps.get(1).map(|m| m.as_str()).unwrap_or("Erreur EDSquare");
-                    if raw_msg.to_lowercase().contains("introuvable") {
-                        info!(
-                            "EDSquare: événement introuvable = déjà validé/signé, traité comme succès"
-                        );
-                        return Ok(ValidateEdsquareResponse {
-                            success: true,
-                            message: "Déjà validé (événement déjà traité)".to_string(),
-                            code: code.to_string(),
-                            planning_event_id: Some(planning_event_id.to_string()),
-                        });
-                    }
-                    error!(
-                        "EDSquare a renvoyé une erreur malgré le status 200: {}",
-                        raw_msg
-                    );
-                    return Err(format!("Erreur EDSquare: {}", raw_msg));
+            match error_from_js_response(&response_text) {
+                Some(EdsquareError::EventNotFound(_)) => {
+                    info!("EDSquare: événement introuvable = déjà validé/signé, traité comme succès");
+                    Ok(ValidateEdsquareResponse {
+                        success: true,
+                        message: "Déjà validé (événement déjà traité)".to_string(),
+                        code: code.to_string(),
+                        planning_event_id: Some(planning_event_id.to_string()),
+                        planning_event: None,
+                        verification: None,
+                    })
+                }
+                Some(err) => {
+                    error!("EDSquare a renvoyé une erreur malgré le status 200: {}", err);
+                    Err(err)
+                }
+                None => {
+                    info!("Code EDSquare validé avec succès");
+                    Ok(ValidateEdsquareResponse {
+                        success: true,
+                        message: "Code validé avec succès".to_string(),
+                        code: code.to_string(),
+                        planning_event_id: Some(planning_event_id.to_string()),
+                        planning_event: None,
+                        verification: None,
+                    })
                 }
             }
-
-            info!("Code EDSquare validé avec succès");
-            Ok(ValidateEdsquareResponse {
-                success: true,
-                message: "Code validé avec succès".to_string(),
-                code: code.to_string(),
-                planning_event_id: Some(planning_event_id.to_string()),
-            })
         }
         StatusCode::UNAUTHORIZED => {
             error!("Session EDSquare expirée (401)");
-            Err("Session EDSquare expirée. Veuillez vous reconnecter.".into())
+            Err(EdsquareError::SessionExpired)
         }
         StatusCode::NOT_FOUND => {
-            error!("Code invalide ou événement non trouvé (404). Réponse: {}", response_text);
-            Err(format!("Code invalide ou événement non trouvé. Vérifiez le code et le planning_event_id. Réponse: {}", 
-                if response_text.len() > 200 { format!("{}...", &response_text[..200]) } else { response_text }))
+            error!("Code invalide ou événement non trouvé (404). Réponse: {}", preview);
+            Err(EdsquareError::EventNotFound(format!(
+                "vérifiez le code et le planning_event_id {}",
+                planning_event_id
+            )))
         }
         StatusCode::BAD_REQUEST => {
-            error!("Requête invalide (400). Réponse: {}", response_text);
-            Err(format!("Requête invalide. Vérifiez le format des données. Réponse: {}", 
-                if response_text.len() > 200 { format!("{}...", &response_text[..200]) } else { response_text }))
+            error!("Requête invalide (400). Réponse: {}", preview);
+            Err(EdsquareError::UnexpectedResponse(format!("requête invalide (400): {}", preview)))
         }
         _ => {
-            error!("Unexpected status code: {} - Response: {}", status, 
-                if response_text.len() > 200 { format!("{}...", &response_text[..200]) } else { response_text.clone() });
-            Err(format!("Erreur lors de la validation: {} - {}", status, 
-                if response_text.len() > 200 { format!("{}...", &response_text[..200]) } else { response_text }))
+            error!("Unexpected status code: {} - Response: {}", status, preview);
+            Err(EdsquareError::UnexpectedResponse(format!("{} - {}", status, preview)))
         }
     }
 }
@@ -462,34 +686,41 @@ pub async fn validate_edsquare_code(
     signature: &str,
     user_id: &str,
     state: &GlobalState,
-) -> Result<ValidateEdsquareResponse, String> {
-    let result = validate_edsquare_code_once(code, planning_event_id, signature, user_id, state).await;
-    if result.is_ok() {
-        return result;
-    }
-    let err = result.unwrap_err();
-    let session_expired = err.contains("Session EDSquare expirée")
-        || err.contains("Veuillez vous reconnecter")
-        || err.to_lowercase().contains("cookie")
-        || err.to_lowercase().contains("session");
-    if !session_expired {
+) -> Result<ValidateEdsquareResponse, EdsquareError> {
+    // Récupérer les cookies EDSquare (reconnexion auto si identifiants sauvegardés)
+    let cookies = get_edsquare_cookies_or_reconnect(state, user_id).await?;
+    let err = match validate_edsquare_code_once(&state.edsquare, code, planning_event_id, signature, &cookies).await {
+        Ok(response) => {
+            if let Err(e) = mark_edsquare_cookies_verified(state, user_id) {
+                warn!("Impossible de mettre à jour la vérification des cookies EDSquare: {}", e);
+            }
+            return Ok(response);
+        }
+        Err(err) => err,
+    };
+    if !err.is_session_expired() {
         return Err(err);
     }
     info!(
         "Session EDSquare expirée pour {}, invalidation des cookies puis reconnexion et nouvel essai",
         user_id
     );
-    if let Err(e) = clear_edsquare_cookies_for_user(state, user_id) {
-        warn!("Impossible d'invalider les cookies EDSquare: {}", e);
-        return Err(err);
-    }
-    if let Err(reconnect_err) = login_edsquare_with_saved(user_id, state).await {
-        warn!("Reconnexion EDSquare échouée après session expirée: {}", reconnect_err);
-        return Err(err);
-    }
-    validate_edsquare_code_once(code, planning_event_id, signature, user_id, state).await
+    let cookies = match reconnect_edsquare(state, user_id, Some(&cookies)).await {
+        Ok(cookies) => cookies,
+        Err(reconnect_err) => {
+            // L'erreur de reconnexion (identifiants refusés, suspendus...) dit à l'utilisateur quoi corriger
+            warn!("Reconnexion EDSquare échouée après session expirée ({}): {}", err, reconnect_err);
+            return Err(reconnect_err);
+        }
+    };
+    validate_edsquare_code_once(&state.edsquare, code, planning_event_id, signature, &cookies).await
 }
 
+/// Durée de vie du cache des cours EDSquare.
+const PLANNING_CACHE_TTL: Duration = Duration::from_secs(5 * 60);
+/// Nombre maximal de jours pour une requête de planning sur une période.
+pub const MAX_PLANNING_RANGE_DAYS: i64 = 62;
+
 /// Récupère les événements du planning EDSquare pour une date donnée (json_dashboard).
 /// Utilise les cookies EDSquare de l'utilisateur (reconnexion auto si identifiants sauvegardés).
 /// Met en cache les résultats pendant 5 minutes pour éviter de spammer l'API EDSquare.
@@ -497,121 +728,231 @@ pub async fn fetch_planning_events(
     state: &GlobalState,
     user_id_param: &str,
     date: NaiveDate,
-) -> Result<Vec<EdsquarePlanningEvent>, String> {
+) -> Result<Vec<EdsquarePlanningEvent>, EdsquareError> {
     // Vérifier le cache d'abord
-    let cache_key = (user_id_param.to_string(), date);
-    {
-        let cache = state.edsquare_planning_cache.read().await;
-        if let Some(entry) = cache.get(&cache_key) {
-            // Vérifier si le cache est encore valide (5 minutes)
-            if let Ok(elapsed) = entry.cached_at.elapsed() {
-                if elapsed < Duration::from_secs(5 * 60) {
-                    info!(
-                        "Récupération depuis le cache: {} événement(s) EDSquare pour la date {} (user: {})",
-                        entry.events.len(),
-                        date,
-                        user_id_param
-                    );
-                    return Ok(entry.events.clone());
-                }
-            }
-        }
+    if let Some(events) = get_cached_planning_day(state, user_id_param, date).await {
+        info!(
+            "Récupération depuis le cache: {} événement(s) EDSquare pour la date {} (user: {})",
+            events.len(),
+            date,
+            user_id_param
+        );
+        return Ok(events);
     }
 
     // Cache expiré ou absent, faire la requête
-    let cookies = get_edsquare_cookies_or_reconnect(state, user_id_param).await?;
+    let (start, end) = state.day_bounds(date);
+    let events = request_planning(state, user_id_param, start, end).await?;
+    store_planning_days(state, user_id_param, vec![(date, events.clone())]).await;
 
-    let cookie_str = cookies
-        .iter()
-        .map(|c| c.to_header_value())
-        .collect::<Vec<_>>()
-        .join("; ");
+    info!(
+        "Récupération de {} événement(s) EDSquare pour la date {} (user: {}) - mis en cache",
+        events.len(),
+        date,
+        user_id_param
+    );
+    Ok(events)
+}
 
-    let start_str = format!("{}T00:00:00+01:00", date);
-    let end_date = date.succ_opt().unwrap_or(date);
-    let end_str = format!("{}T00:00:00+01:00", end_date);
+/// Récupère les cours EDSquare du `from` au `to` inclus, triés par début.
+/// Les jours déjà en cache sont réutilisés ; chaque suite de jours manquants est demandée en une seule requête
+/// puis découpée par jour (dans le fuseau de l'école) pour alimenter le cache journalier.
+pub async fn fetch_planning_events_range(
+    state: &GlobalState,
+    user_id_param: &str,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<Vec<EdsquarePlanningEvent>, EdsquareError> {
+    let days: Vec<NaiveDate> = from.iter_days().take_while(|d| *d <= to).collect();
+
+    let mut by_day: Vec<(NaiveDate, Option<Vec<EdsquarePlanningEvent>>)> = Vec::with_capacity(days.len());
+    for day in days {
+        by_day.push((day, get_cached_planning_day(state, user_id_param, day).await));
+    }
 
-    let url = format!(
-        "https://app.edsquare.fr/apps/planning/json_dashboard?start={}&end={}",
-        encode(&start_str),
-        encode(&end_str)
+    // Suites de jours consécutifs absents du cache
+    let mut missing_runs: Vec<(NaiveDate, NaiveDate)> = Vec::new();
+    for (day, cached) in &by_day {
+        if cached.is_some() {
+            continue;
+        }
+        match missing_runs.last_mut() {
+            Some((_, run_end)) if run_end.succ_opt() == Some(*day) => *run_end = *day,
+            _ => missing_runs.push((*day, *day)),
+        }
+    }
+    info!(
+        "Planning EDSquare du {} au {} (user: {}): {} jour(s) en cache, {} requête(s)",
+        from,
+        to,
+        user_id_param,
+        by_day.iter().filter(|(_, c)| c.is_some()).count(),
+        missing_runs.len()
     );
 
-    let client = match get_reqwest_client() {
-        Ok(c) => c,
-        Err(e) => return Err(format!("Failed to create HTTP client: {}", e)),
-    };
+    for (run_start, run_end) in missing_runs {
+        let (start, _) = state.day_bounds(run_start);
+        let (_, end) = state.day_bounds(run_end);
+        let events = request_planning(state, user_id_param, start, end).await?;
+
+        let mut fetched: Vec<(NaiveDate, Vec<EdsquarePlanningEvent>)> = run_start
+            .iter_days()
+            .take_while(|d| *d <= run_end)
+            .map(|d| (d, Vec::new()))
+            .collect();
+        for event in events {
+            let Some(day) = event_local_date(state.school_timezone, &event) else {
+                warn!("Cours EDSquare {} ignoré: début illisible ({})", event.id, event.start);
+                continue;
+            };
+            if let Some((_, day_events)) = fetched.iter_mut().find(|(d, _)| *d == day) {
+                day_events.push(event);
+            }
+        }
+        for (day, day_events) in &fetched {
+            if let Some(entry) = by_day.iter_mut().find(|(d, _)| d == day) {
+                entry.1 = Some(day_events.clone());
+            }
+        }
+        store_planning_days(state, user_id_param, fetched).await;
+    }
+
+    let mut events: Vec<EdsquarePlanningEvent> = by_day.into_iter().flat_map(|(_, e)| e.unwrap_or_default()).collect();
+    events.sort_by(|a, b| a.start.cmp(&b.start));
+    Ok(events)
+}
+
+/// Jour (dans le fuseau de l'école) auquel commence un cours EDSquare.
+pub fn event_local_date(tz: Tz, event: &EdsquarePlanningEvent) -> Option<NaiveDate> {
+    chrono::DateTime::parse_from_rfc3339(&event.start)
+        .ok()
+        .map(|dt| dt.with_timezone(&tz).date_naive())
+}
+
+async fn get_cached_planning_day(
+    state: &GlobalState,
+    user_id_param: &str,
+    date: NaiveDate,
+) -> Option<Vec<EdsquarePlanningEvent>> {
+    let cache = state.edsquare_planning_cache.read().await;
+    cache
+        .get(&(user_id_param.to_string(), date))
+        .filter(|entry| entry.cached_at.elapsed().is_ok_and(|elapsed| elapsed < PLANNING_CACHE_TTL))
+        .map(|entry| entry.events.clone())
+}
+
+async fn store_planning_days(
+    state: &GlobalState,
+    user_id_param: &str,
+    days: Vec<(NaiveDate, Vec<EdsquarePlanningEvent>)>,
+) {
+    let mut cache = state.edsquare_planning_cache.write().await;
+    for (date, events) in days {
+        cache.insert(
+            (user_id_param.to_string(), date),
+            PlanningEventsCacheEntry {
+                events,
+                cached_at: SystemTime::now(),
+            },
+        );
+    }
+    // Nettoyer les entrées expirées (garder le cache propre)
+    cache.retain(|_, entry| {
+        entry.cached_at.elapsed().map(|e| e < PLANNING_CACHE_TTL).unwrap_or(false)
+    });
+}
+
+/// Appelle json_dashboard pour l'intervalle `[start, end)`.
+async fn request_planning(
+    state: &GlobalState,
+    user_id_param: &str,
+    start: chrono::DateTime<Tz>,
+    end: chrono::DateTime<Tz>,
+) -> Result<Vec<EdsquarePlanningEvent>, EdsquareError> {
+    let cookies = get_edsquare_cookies_or_reconnect(state, user_id_param).await?;
+
+    info!("Requête EDSquare pour récupérer les cours (user: {}, du {} au {})", user_id_param, start, end);
+    let events = fetch_planning_json(&state.edsquare, &cookie_header(&cookies), start, end).await?;
+
+    if let Err(e) = mark_edsquare_cookies_verified(state, user_id_param) {
+        warn!("Impossible de mettre à jour la vérification des cookies EDSquare: {}", e);
+    }
+    Ok(events)
+}
+
+/// Requête json_dashboard brute avec les cookies fournis.
+async fn fetch_planning_json(
+    client: &EdsquareClient,
+    cookie_str: &str,
+    start: chrono::DateTime<Tz>,
+    end: chrono::DateTime<Tz>,
+) -> Result<Vec<EdsquarePlanningEvent>, EdsquareError> {
+    // Bornes dans le fuseau de l'école (décalage correct été comme hiver)
+    let url = client.url(&format!(
+        "/apps/planning/json_dashboard?start={}&end={}",
+        encode(&start.to_rfc3339()),
+        encode(&end.to_rfc3339())
+    ));
 
-    info!("Requête EDSquare pour récupérer les cours (user: {}, date: {})", user_id_param, date);
     let response = client
+        .http()
         .get(&url)
-        .header(COOKIE, &cookie_str)
+        .header(COOKIE, cookie_str)
         .header("Accept", "*/*")
         .header("Accept-Language", "fr-FR,fr;q=0.9,en-US;q=0.8,en;q=0.7")
         .header("Cache-Control", "no-cache")
-        .header("Referer", "https://app.edsquare.fr/home")
+        .header("Referer", client.url("/home"))
         .send()
         .await
-        .map_err(|e| format!("Erreur lors de la requête planning EDSquare: {}", e))?;
+        .map_err(|e| EdsquareError::Network(e.to_string()))?;
 
     let status = response.status();
     let body = response.text().await.unwrap_or_default();
 
     if !status.is_success() {
         if status == StatusCode::UNAUTHORIZED || body.contains("sign_in") {
-            return Err("Session EDSquare expirée. Veuillez vous reconnecter à EDSquare.".into());
+            return Err(EdsquareError::SessionExpired);
         }
-        return Err(format!(
-            "EDSquare planning a répondu avec le statut {}",
+        return Err(EdsquareError::UnexpectedResponse(format!(
+            "le planning a répondu avec le statut {}",
             status
-        ));
+        )));
     }
 
     let events: Vec<EdsquarePlanningEvent> = serde_json::from_str(&body).map_err(|e| {
         error!("Erreur parsing JSON planning EDSquare: {}", e);
-        format!("Réponse EDSquare invalide: {}", e)
+        EdsquareError::UnexpectedResponse(format!("planning invalide: {}", e))
     })?;
-
-    // Mettre en cache le résultat
-    {
-        let mut cache = state.edsquare_planning_cache.write().await;
-        cache.insert(
-            cache_key,
-            PlanningEventsCacheEntry {
-                events: events.clone(),
-                cached_at: SystemTime::now(),
-            },
-        );
-        // Nettoyer les entrées expirées (garder le cache propre)
-        cache.retain(|_, entry| {
-            entry.cached_at.elapsed().map(|e| e < Duration::from_secs(5 * 60)).unwrap_or(false)
-        });
-    }
-
-    info!(
-        "Récupération de {} événement(s) EDSquare pour la date {} (user: {}) - mis en cache",
-        events.len(),
-        date,
-        user_id_param
-    );
     Ok(events)
 }
 
 async fn fetch_csrf_token_with_cookies(
+    client: &EdsquareClient,
     cookie_str: &str,
-) -> Result<Option<String>, String> {
-    // Créer un client temporaire qui ne suit pas les redirections pour détecter les sessions expirées
-    let no_redirect_client = reqwest::Client::builder()
-        .redirect(reqwest::redirect::Policy::none())
-        .build()
-        .map_err(|e| format!("Failed to create HTTP client without redirects: {}", e))?;
-
-    let response = no_redirect_client
-        .get("https://app.edsquare.fr/apps/classrooms")
+) -> Result<Option<String>, EdsquareError> {
+    let Some(html) = fetch_classrooms_page(client, cookie_str).await? else {
+        return Ok(None);
+    };
+
+    if let Some(token) = extract_csrf_token_from_html(&html) {
+        info!("CSRF token EDSquare récupéré depuis /apps/classrooms");
+        Ok(Some(token))
+    } else {
+        warn!("Impossible de trouver le CSRF token dans la page /apps/classrooms");
+        Ok(None)
+    }
+}
+
+/// Page /apps/classrooms (formulaires de signature, CSRF). `None` si la page n'a pas pu être lue.
+async fn fetch_classrooms_page(client: &EdsquareClient, cookie_str: &str) -> Result<Option<String>, EdsquareError> {
+    // Client qui ne suit pas les redirections pour détecter les sessions expirées
+    let response = client
+        .no_redirect()
+        .get(client.url("/apps/classrooms"))
         .header(COOKIE, cookie_str)
         .send()
         .await
-        .map_err(|e| format!("Erreur lors de la récupération de la page classrooms: {}", e))?;
+        .map_err(|e| EdsquareError::Network(e.to_string()))?;
 
     let status = response.status();
     let final_url = response.url().to_string();
@@ -619,28 +960,100 @@ async fn fetch_csrf_token_with_cookies(
     // Vérifier si on est redirigé vers la page de login (session expirée)
     if status.is_redirection() || final_url.contains("/users/sign_in") {
         warn!("Session EDSquare expirée : redirection vers /users/sign_in détectée");
-        return Err("Session EDSquare expirée. Veuillez vous reconnecter à EDSquare.".into());
+        return Err(EdsquareError::SessionExpired);
     }
 
     let html = response.text().await.unwrap_or_default();
 
     if !status.is_success() {
         warn!(
-            "Échec de la récupération de la page classrooms pour CSRF (status: {}, url: {})",
+            "Échec de la récupération de la page classrooms (status: {}, url: {})",
             status, final_url
         );
         return Ok(None);
     }
 
-    if let Some(token) = extract_csrf_token_from_html(&html) {
-        info!("CSRF token EDSquare récupéré depuis /apps/classrooms");
-        Ok(Some(token))
-    } else {
-        warn!("Impossible de trouver le CSRF token dans la page /apps/classrooms");
-        Ok(None)
+    Ok(Some(html))
+}
+
+/// Vérifie après coup que la signature a bien été enregistrée : relit la page /apps/classrooms
+/// et regarde si EDSquare propose encore de signer ce cours ou l'affiche comme signé.
+/// Une réponse 200 sans toastr d'erreur (ou "introuvable") ne garantit pas à elle seule la présence,
+/// l'absence de formulaire non plus (cours absent de la page, mise en page modifiée).
+pub async fn verify_edsquare_signature(
+    state: &GlobalState,
+    user_id_param: &str,
+    planning_event_id: &str,
+) -> SignatureVerification {
+    let cookies = match get_edsquare_cookies(state, user_id_param) {
+        Ok(Some(cookies)) => cookies,
+        Ok(None) => return SignatureVerification::Unverified,
+        Err(e) => {
+            warn!("Vérification EDSquare impossible (cookies): {}", e);
+            return SignatureVerification::Unverified;
+        }
+    };
+
+    match fetch_classrooms_page(&state.edsquare, &cookie_header(&cookies)).await {
+        Ok(Some(html)) if has_pending_signature_form(&html, planning_event_id) => {
+            warn!(
+                "EDSquare propose encore de signer l'événement {} pour {} après envoi",
+                planning_event_id, user_id_param
+            );
+            SignatureVerification::Contradicted
+        }
+        Ok(Some(html)) if shows_event_as_signed(&html, planning_event_id) => {
+            info!("Signature EDSquare confirmée pour {} (événement {})", user_id_param, planning_event_id);
+            SignatureVerification::Verified
+        }
+        Ok(Some(_)) => {
+            info!(
+                "Événement {} ni à signer ni signé sur EDSquare pour {} : signature non vérifiée",
+                planning_event_id, user_id_param
+            );
+            SignatureVerification::Unverified
+        }
+        Ok(None) => SignatureVerification::Unverified,
+        Err(e) => {
+            warn!("Vérification EDSquare impossible pour {}: {}", user_id_param, e);
+            SignatureVerification::Unverified
+        }
     }
 }
 
+/// La page contient-elle encore un formulaire (ou un lien) de signature pour cet événement ?
+fn has_pending_signature_form(html: &str, planning_event_id: &str) -> bool {
+    let id = regex::escape(planning_event_id.trim());
+    let patterns = [
+        // <input name="course_user_signature[planning_event_id]" value="ID">, dans un sens ou dans l'autre
+        format!(r#"name="course_user_signature\[planning_event_id\]"[^>]*value="{}""#, id),
+        format!(r#"value="{}"[^>]*name="course_user_signature\[planning_event_id\]""#, id),
+        // Lien vers la modale de signature : /apps/course_user_signatures/new?planning_event_id=ID
+        format!(r#"course_user_signatures/new\?[^"'\s]*planning_event_id={}\b"#, id),
+    ];
+    patterns
+        .iter()
+        .filter_map(|p| regex::Regex::new(p).ok())
+        .any(|re| re.is_match(html))
+}
+
+/// Marqueurs de présence enregistrée dans le bloc d'un cours (comparés en minuscules).
+const SIGNED_MARKERS: [&str; 3] = ["signé", "présence enregistrée", "présent"];
+/// Marqueurs d'un cours encore à signer, qui contiennent aussi « signé » ou « présent ».
+const UNSIGNED_MARKERS: [&str; 3] = ["non signé", "pas signé", "absent"];
+
+/// La page affiche-t-elle le cours comme signé ? On regarde le bloc qui suit
+/// `data-planning-event-id="ID"` (jusqu'au cours suivant).
+fn shows_event_as_signed(html: &str, planning_event_id: &str) -> bool {
+    let marker = format!(r#"data-planning-event-id="{}""#, planning_event_id.trim());
+    html.match_indices(&marker).any(|(start, _)| {
+        let block = &html[start + marker.len()..];
+        let end = block.find("data-planning-event-id=").unwrap_or(block.len());
+        let block = block[..end].to_lowercase();
+        SIGNED_MARKERS.iter().any(|m| block.contains(m)) && !UNSIGNED_MARKERS.iter().any(|m| block.contains(m))
+    })
+}
+
 pub fn save_edsquare_cookies(
     state: &GlobalState,
     user_id_param: &str,
@@ -652,8 +1065,8 @@ pub fn save_edsquare_cookies(
     use ulid::Ulid;
     use serde_json::json;
 
-    let current_date = chrono::Utc::now().date_naive();
-    let cookie_id = Ulid::new().to_string();
+    let now = chrono::Utc::now().naive_utc();
+    let jar_expiry = jar_expires_at(cookie_items);
 
     let mut conn = match state.get_db_conn() {
         Ok(conn) => conn,
@@ -663,72 +1076,46 @@ pub fn save_edsquare_cookies(
     // Convertir les cookies en JSON
     let cookie_json = json!(cookie_items);
 
-    // Vérifier si des cookies existent déjà pour cet utilisateur aujourd'hui
-    let existing = edsquare_cookies::table
-        .filter(user_id.eq(user_id_param))
-        .filter(date.eq(current_date))
-        .select(EdsquareCookie::as_select())
-        .first::<EdsquareCookie>(&mut conn)
-        .optional()
-        .map_err(|e| format!("Database error: {}", e))?;
-
-    match existing {
-        Some(_) => {
-            // Mettre à jour les cookies existants
-            info!("Mise à jour des cookies EDSquare existants pour l'utilisateur {} et la date: {}", user_id_param, current_date);
-            diesel::update(edsquare_cookies::table
-                .filter(user_id.eq(user_id_param))
-                .filter(date.eq(current_date)))
-                .set(cookie_data.eq(cookie_json))
-                .execute(&mut conn)
-                .map_err(|e| {
-                    error!("Erreur lors de la mise à jour des cookies: {}", e);
-                    format!("Failed to update cookies: {}", e)
-                })?;
-            info!("Cookies EDSquare mis à jour avec succès");
-        }
-        None => {
-            // Insérer de nouveaux cookies
-            info!("Insertion de nouveaux cookies EDSquare pour l'utilisateur {} et la date: {}", user_id_param, current_date);
-            diesel::insert_into(edsquare_cookies::table)
-                .values((
-                    id.eq(&cookie_id),
-                    user_id.eq(user_id_param),
-                    date.eq(current_date),
-                    cookie_data.eq(cookie_json),
-                ))
-                .execute(&mut conn)
-                .map_err(|e| {
-                    error!("Erreur lors de l'insertion des cookies: {}", e);
-                    format!("Failed to insert cookies: {}", e)
-                })?;
-            info!("Cookies EDSquare insérés avec succès");
-        }
-    }
+    // Un seul jeu de cookies par utilisateur : on remplace le précédent
+    info!(
+        "Enregistrement des cookies EDSquare pour l'utilisateur {} (expiration: {:?})",
+        user_id_param, jar_expiry
+    );
+    diesel::insert_into(edsquare_cookies::table)
+        .values((
+            id.eq(Ulid::new().to_string()),
+            user_id.eq(user_id_param),
+            cookie_data.eq(&cookie_json),
+            expires_at.eq(jar_expiry),
+            last_verified_at.eq(now),
+            updated_at.eq(now),
+        ))
+        .on_conflict(user_id)
+        .do_update()
+        .set((
+            cookie_data.eq(&cookie_json),
+            expires_at.eq(jar_expiry),
+            last_verified_at.eq(now),
+            updated_at.eq(now),
+        ))
+        .execute(&mut conn)
+        .map_err(|e| {
+            error!("Erreur lors de l'enregistrement des cookies: {}", e);
+            format!("Failed to save cookies: {}", e)
+        })?;
+    info!("Cookies EDSquare enregistrés avec succès");
 
+    publish_edsquare_cookie_status(state, user_id_param, true);
+    clear_session_alert(state, user_id_param);
     Ok(())
 }
 
-fn get_reqwest_client() -> Result<reqwest::Client, String> {
-    let mut headers = reqwest::header::HeaderMap::new();
-    headers.insert(
-        reqwest::header::USER_AGENT,
-        reqwest::header::HeaderValue::from_static("Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/144.0.0.0 Safari/537.36"),
-    );
-    headers.insert(
-        reqwest::header::ACCEPT,
-        reqwest::header::HeaderValue::from_static("*/*"),
-    );
-    headers.insert(
-        reqwest::header::ACCEPT_LANGUAGE,
-        reqwest::header::HeaderValue::from_static("fr-FR,fr;q=0.9,en-US;q=0.8,en;q=0.7"),
-    );
-
-    reqwest::Client::builder()
-        .cookie_store(true)
-        .default_headers(headers)
-        .build()
-        .map_err(|e| format!("Failed to build HTTP client: {}", e))
+fn publish_edsquare_cookie_status(state: &GlobalState, user_id_param: &str, available: bool) {
+    state.events.publish(ServerEvent::CookieStatusChanged {
+        provider: "edsquare".to_string(),
+        user_id: Some(user_id_param.to_string()),
+        available,
+    });
 }
 
 pub async fn login_edsquare(
@@ -736,34 +1123,62 @@ pub async fn login_edsquare(
     password: &str,
     user_id_param: &str,
     state: &GlobalState,
-) -> Result<LoginEdsquareResponse, String> {
+) -> Result<LoginEdsquareResponse, EdsquareError> {
     info!("Tentative de connexion EDSquare pour: {}", email);
-    
-    let client = match get_reqwest_client() {
-        Ok(client) => client,
-        Err(e) => return Err(format!("Failed to create HTTP client: {}", e)),
-    };
+
+    let cookie_items = open_edsquare_session(&state.edsquare, email, password).await?;
+
+    // Étape 4: Sauvegarder les cookies
+    match save_edsquare_cookies(state, user_id_param, &cookie_items) {
+        Ok(_) => {
+            info!("Cookies EDSquare sauvegardés avec succès pour l'utilisateur {}", user_id_param);
+            // Sauvegarder également les identifiants pour permettre des reconnexions automatiques
+            if let Err(e) = save_edsquare_credentials(state, user_id_param, email, password) {
+                warn!("Connexion OK mais échec de la sauvegarde des identifiants EDSquare: {}", e);
+            }
+            Ok(LoginEdsquareResponse {
+                success: true,
+                message: "Connexion EDSquare réussie et cookies sauvegardés".to_string(),
+            })
+        },
+        Err(e) => {
+            error!("Erreur lors de la sauvegarde des cookies: {}", e);
+            Err(EdsquareError::Storage(e))
+        },
+    }
+}
+
+/// Se connecte à EDSquare (page de login, formulaire, vérification de /home) et retourne les cookies de session.
+async fn open_edsquare_session(
+    edsquare: &EdsquareClient,
+    email: &str,
+    password: &str,
+) -> Result<Vec<EdsquareCookieItem>, EdsquareError> {
+    let client = edsquare.session().map_err(EdsquareError::Network)?;
+    let cookie_domain = edsquare.cookie_domain().to_string();
 
     // Étape 1: Récupérer la page de login pour obtenir le CSRF token
     let login_page = match client
-        .get("https://app.edsquare.fr/users/sign_in")
+        .get(edsquare.url("/users/sign_in"))
         .send()
         .await
     {
         Ok(response) => {
             if !response.status().is_success() {
-                return Err(format!("Failed to fetch login page: {}", response.status()));
+                return Err(EdsquareError::UnexpectedResponse(format!(
+                    "page de login en erreur: {}",
+                    response.status()
+                )));
             }
             response.text().await.unwrap_or_default()
         }
         Err(e) => {
-            return Err(format!("Erreur lors de la récupération de la page de login: {}", e));
+            return Err(EdsquareError::Network(e.to_string()));
         }
     };
 
     // Extraire le CSRF token depuis le HTML
-    let csrf_token = extract_csrf_token_from_html(&login_page)
-        .ok_or_else(|| "Impossible de trouver le CSRF token dans la page de login".to_string())?;
+    let csrf_token = extract_csrf_token_from_html(&login_page).ok_or(EdsquareError::CsrfMissing)?;
 
     // Étape 2: Faire le POST de login
     let form_data = format!(
@@ -774,17 +1189,17 @@ pub async fn login_edsquare(
     );
 
     let login_response = match client
-        .post("https://app.edsquare.fr/users/sign_in")
+        .post(edsquare.url("/users/sign_in"))
         .header("Content-Type", "application/x-www-form-urlencoded")
-        .header("Origin", "https://app.edsquare.fr")
-        .header("Referer", "https://app.edsquare.fr/")
+        .header("Origin", edsquare.base_url())
+        .header("Referer", edsquare.url("/"))
         .body(form_data)
         .send()
         .await
     {
         Ok(response) => response,
         Err(e) => {
-            return Err(format!("Erreur lors de la connexion: {}", e));
+            return Err(EdsquareError::Network(e.to_string()));
         }
     };
 
@@ -797,7 +1212,7 @@ pub async fn login_edsquare(
         login_cookies.push(CookieItem {
             name: cookie.name().to_string(),
             value: cookie.value().to_string(),
-            domain: cookie.domain().map(|d| d.to_string()).unwrap_or_else(|| "app.edsquare.fr".to_string()),
+            domain: cookie.domain().map(|d| d.to_string()).unwrap_or_else(|| cookie_domain.clone()),
             path: cookie.path().map(|p| p.to_string()).unwrap_or_else(|| "/".to_string()),
             expires: cookie.expires().and_then(|e| e.duration_since(std::time::SystemTime::UNIX_EPOCH).ok())
                 .map(|d| d.as_secs() as i64),
@@ -821,23 +1236,23 @@ pub async fn login_edsquare(
             || response_text.contains("incorrect")
             || response_text.contains("erreur")
             || (response_text.contains("sign_in") && !response_text.contains("/home")) {
-            return Err("Échec de la connexion: identifiants invalides".to_string());
+            return Err(EdsquareError::InvalidCredentials);
         }
         // Si on reste sur la page de login sans redirection, c'est un échec
         if login_url.contains("sign_in") && !login_url.contains("/home") {
-            return Err("Échec de la connexion: identifiants invalides".to_string());
+            return Err(EdsquareError::InvalidCredentials);
         }
     }
 
     // Étape 3: Faire une requête vers /home pour vérifier la connexion et récupérer les cookies
     let home_response = match client
-        .get("https://app.edsquare.fr/home")
+        .get(edsquare.url("/home"))
         .send()
         .await
     {
         Ok(response) => response,
         Err(e) => {
-            return Err(format!("Erreur lors de la récupération de la page d'accueil: {}", e));
+            return Err(EdsquareError::Network(e.to_string()));
         }
     };
 
@@ -853,13 +1268,13 @@ pub async fn login_edsquare(
     
     // Si on est redirigé vers sign_in, la connexion a échoué
     if home_url.contains("sign_in") {
-        return Err("Échec de la connexion: identifiants invalides ou session expirée".to_string());
+        return Err(EdsquareError::InvalidCredentials);
     }
     
-    if let Some(ref location) = location_header {
-        if location.contains("sign_in") {
-            return Err("Échec de la connexion: identifiants invalides".to_string());
-        }
+    if let Some(ref location) = location_header
+        && location.contains("sign_in")
+    {
+        return Err(EdsquareError::InvalidCredentials);
     }
     
     // Vérifier que la connexion a vraiment réussi
@@ -875,7 +1290,7 @@ pub async fn login_edsquare(
     if !connection_successful {
         warn!("Connexion EDSquare échouée: home_status={}, home_url={}, location={:?}", 
             home_status, home_url, location_header);
-        return Err("Échec de la connexion: identifiants invalides".to_string());
+        return Err(EdsquareError::InvalidCredentials);
     }
     
     info!("Connexion EDSquare réussie, vérification de /home OK (status: {}, url: {})", home_status, home_url);
@@ -893,7 +1308,7 @@ pub async fn login_edsquare(
             cookie_items.push(CookieItem {
                 name: cookie.name().to_string(),
                 value: cookie.value().to_string(),
-                domain: cookie.domain().map(|d| d.to_string()).unwrap_or_else(|| "app.edsquare.fr".to_string()),
+                domain: cookie.domain().map(|d| d.to_string()).unwrap_or_else(|| cookie_domain.clone()),
                 path: cookie.path().map(|p| p.to_string()).unwrap_or_else(|| "/".to_string()),
                 expires: cookie.expires().and_then(|e| e.duration_since(std::time::SystemTime::UNIX_EPOCH).ok())
                     .map(|d| d.as_secs() as i64),
@@ -906,13 +1321,17 @@ pub async fn login_edsquare(
     
     // Vérifier qu'on a au moins un cookie de session important (comme _session_id ou similaire)
     if cookie_items.is_empty() {
-        return Err("Aucun cookie reçu après la connexion. La connexion a peut-être échoué.".to_string());
+        return Err(EdsquareError::UnexpectedResponse(
+            "aucun cookie reçu après la connexion".to_string(),
+        ));
     }
 
     // Vérifier qu'on a des cookies valides (au moins un cookie avec une valeur non vide)
     let has_valid_cookies = cookie_items.iter().any(|c| !c.value.is_empty() && c.value.len() > 10);
     if !has_valid_cookies {
-        return Err("Les cookies reçus semblent invalides. La connexion a peut-être échoué.".to_string());
+        return Err(EdsquareError::UnexpectedResponse(
+            "les cookies reçus après la connexion semblent invalides".to_string(),
+        ));
     }
 
     info!("Connexion EDSquare réussie, {} cookies à sauvegarder", cookie_items.len());
@@ -921,43 +1340,64 @@ pub async fn login_edsquare(
     let cookie_names: Vec<String> = cookie_items.iter().map(|c| c.name.clone()).collect();
     info!("Cookies reçus: {:?}", cookie_names);
 
-    // Étape 4: Sauvegarder les cookies
-    match save_edsquare_cookies(state, user_id_param, &cookie_items) {
-        Ok(_) => {
-            info!("Cookies EDSquare sauvegardés avec succès pour la date: {}", chrono::Utc::now().date_naive());
-            // Sauvegarder également les identifiants pour permettre des reconnexions automatiques
-            if let Err(e) = save_edsquare_credentials(state, user_id_param, email, password) {
-                warn!("Connexion OK mais échec de la sauvegarde des identifiants EDSquare: {}", e);
-            }
-            Ok(LoginEdsquareResponse {
-                success: true,
-                message: "Connexion EDSquare réussie et cookies sauvegardés".to_string(),
-            })
-        },
-        Err(e) => {
-            error!("Erreur lors de la sauvegarde des cookies: {}", e);
-            Err(format!("Connexion réussie mais erreur lors de la sauvegarde des cookies: {}", e))
-        },
-    }
+    Ok(cookie_items)
 }
 
 /// Relance une connexion EDSquare en utilisant les identifiants sauvegardés en base.
+/// Les refus successifs sont comptés : au-delà de `EDSQUARE_MAX_LOGIN_FAILURES`, la reconnexion automatique
+/// est suspendue (pour ne pas faire verrouiller le compte EDSquare) jusqu'à ce que l'utilisateur se reconnecte.
 pub async fn login_edsquare_with_saved(
     user_id_param: &str,
     state: &GlobalState,
-) -> Result<LoginEdsquareResponse, String> {
+) -> Result<LoginEdsquareResponse, EdsquareError> {
+    match get_edsquare_credential_status(state, user_id_param) {
+        Ok(Some(status)) if status.suspended => {
+            warn!(
+                "Reconnexion EDSquare automatique suspendue pour {} ({} échec(s))",
+                user_id_param, status.failed_login_count
+            );
+            return Err(EdsquareError::CredentialsSuspended);
+        }
+        Ok(_) => {}
+        Err(e) => return Err(EdsquareError::Storage(e)),
+    }
+
     let creds = match get_edsquare_credentials(state, user_id_param) {
         Ok(Some(c)) => c,
         Ok(None) => {
-            return Err("Aucun identifiant EDSquare enregistré pour cet utilisateur.".into());
+            return Err(EdsquareError::NoSavedCredentials);
         }
         Err(e) => {
-            return Err(e);
+            return Err(EdsquareError::Storage(e));
         }
     };
 
     let (email, password) = creds;
-    login_edsquare(&email, &password, user_id_param, state).await
+    // En cas de succès, login_edsquare réenregistre les identifiants, ce qui remet le compteur à zéro
+    match login_edsquare(&email, &password, user_id_param, state).await {
+        Ok(response) => Ok(response),
+        Err(EdsquareError::InvalidCredentials) => {
+            match record_edsquare_login_failure(state, user_id_param) {
+                Ok((count, true)) => {
+                    warn!(
+                        "Identifiants EDSquare refusés {} fois pour {} : reconnexion automatique suspendue",
+                        count, user_id_param
+                    );
+                    state.events.publish(ServerEvent::EdsquareCredentialsSuspended {
+                        user_id: user_id_param.to_string(),
+                        failed_login_count: count,
+                    });
+                    return Err(EdsquareError::CredentialsSuspended);
+                }
+                Ok((count, false)) => {
+                    warn!("Identifiants EDSquare refusés pour {} ({} échec(s))", user_id_param, count);
+                }
+                Err(e) => error!("Impossible d'enregistrer l'échec de connexion EDSquare: {}", e),
+            }
+            Err(EdsquareError::InvalidCredentials)
+        }
+        Err(e) => Err(e),
+    }
 }
 
 fn extract_csrf_token_from_html(html: &str) -> Option<String> {
@@ -972,11 +1412,361 @@ fn extract_csrf_token_from_html(html: &str) -> Option<String> {
 
     // Alternative: chercher dans un meta tag
     let meta_pattern = r#"<meta\s+name="csrf-token"\s+content="([^"]+)""#;
-    if let Ok(meta_re) = regex::Regex::new(meta_pattern) {
-        if let Some(captures) = meta_re.captures(html) {
-            return captures.get(1).map(|m| m.as_str().to_string());
-        }
+    if let Ok(meta_re) = regex::Regex::new(meta_pattern)
+        && let Some(captures) = meta_re.captures(html)
+    {
+        return captures.get(1).map(|m| m.as_str().to_string());
     }
 
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn detects_pending_signature_form_for_event() {
+        let form = r#"<form action="/apps/course_user_signatures" method="post">
+            <input type="hidden" name="course_user_signature[planning_event_id]" value="199289" id="x" />
+        </form>"#;
+        assert!(has_pending_signature_form(form, "199289"));
+        assert!(!has_pending_signature_form(form, "19928"));
+
+        let reversed = r#"<input value="42" type="hidden" name="course_user_signature[planning_event_id]">"#;
+        assert!(has_pending_signature_form(reversed, "42"));
+
+        let link = r#"<a data-remote="true" href="/apps/course_user_signatures/new?planning_event_id=77&amp;x=1">Signer</a>"#;
+        assert!(has_pending_signature_form(link, "77"));
+        assert!(!has_pending_signature_form(link, "7"));
+    }
+
+    #[test]
+    fn signed_event_has_no_pending_form() {
+        let html = r#"<div class="event" data-planning-event-id="199289"><span class="badge">Signé</span></div>"#;
+        assert!(!has_pending_signature_form(html, "199289"));
+        assert!(shows_event_as_signed(html, "199289"));
+    }
+
+    #[test]
+    fn missing_or_unsigned_event_is_not_shown_as_signed() {
+        let html = r#"<div data-planning-event-id="199289"><span class="badge">Non signé</span></div>
+            <div data-planning-event-id="42"><span class="badge">Signé</span></div>"#;
+        assert!(!shows_event_as_signed(html, "199289"));
+        assert!(shows_event_as_signed(html, "42"));
+        assert!(!shows_event_as_signed(html, "4"));
+        assert!(!shows_event_as_signed("<html><body>Aucun cours</body></html>", "199289"));
+    }
+
+    #[test]
+    fn jar_expiry_ignores_tracking_cookies() {
+        let cookie = |name: &str, expires: Option<i64>| EdsquareCookieItem {
+            name: name.to_string(),
+            value: "x".to_string(),
+            domain: "app.edsquare.fr".to_string(),
+            path: "/".to_string(),
+            expires,
+            http_only: true,
+            secure: true,
+            same_site: None,
+        };
+        let in_one_hour = chrono::Utc::now().timestamp() + 3600;
+        let in_two_weeks = chrono::Utc::now().timestamp() + 14 * 24 * 3600;
+
+        let browser_session = vec![cookie("_edsquare_session", None), cookie("_ga_tracking", Some(in_one_hour))];
+        assert_eq!(jar_expires_at(&browser_session), None);
+
+        let remembered = vec![
+            cookie("_edsquare_session", None),
+            cookie("remember_user_token", Some(in_two_weeks)),
+            cookie("_ga_tracking", Some(in_one_hour)),
+        ];
+        assert_eq!(
+            jar_expires_at(&remembered),
+            chrono::DateTime::from_timestamp(in_two_weeks, 0).map(|dt| dt.naive_utc())
+        );
+    }
+
+    /// Faux EDSquare local : login Rails (CSRF + cookie de session), page des cours, signature et planning.
+    mod mock {
+        use std::collections::HashMap;
+
+        use axum::{
+            Form, Router,
+            extract::RawQuery,
+            http::{HeaderMap, StatusCode, header},
+            response::{IntoResponse, Redirect, Response},
+            routing::{get, post},
+        };
+
+        use crate::api::edsquare::client::EdsquareClient;
+
+        pub const EMAIL: &str = "jane.doe@epitech.eu";
+        pub const PASSWORD: &str = "hunter2";
+        pub const SESSION: &str = "_edsquare_session=mock-session-0123456789";
+        pub const LOGIN_CSRF: &str = "login-csrf-token";
+        pub const PAGE_CSRF: &str = "page-csrf-token";
+        pub const CODE: &str = "123456";
+        pub const EVENT_ID: &str = "199289";
+
+        fn has_session(headers: &HeaderMap) -> bool {
+            headers
+                .get(header::COOKIE)
+                .and_then(|v| v.to_str().ok())
+                .is_some_and(|cookies| cookies.split("; ").any(|c| c == SESSION))
+        }
+
+        async fn sign_in_page() -> Response {
+            let html = format!(
+                r#"<html><head><meta name="csrf-token" content="{0}" /></head><body>
+                <form action="/users/sign_in" method="post">
+                <input type="hidden" name="authenticity_token" value="{0}" autocomplete="off" />
+                </form></body></html>"#,
+                LOGIN_CSRF
+            );
+            ([(header::CONTENT_TYPE, "text/html")], html).into_response()
+        }
+
+        async fn sign_in(Form(form): Form<HashMap<String, String>>) -> Response {
+            let field = |name: &str| form.get(name).map(String::as_str);
+            if field("authenticity_token") != Some(LOGIN_CSRF)
+                || field("user[email]") != Some(EMAIL)
+                || field("user[password]") != Some(PASSWORD)
+            {
+                let html = r#"<div class="alert">Email ou mot de passe invalide</div><form action="/users/sign_in"></form>"#;
+                return ([(header::CONTENT_TYPE, "text/html")], html).into_response();
+            }
+            (
+                [(header::SET_COOKIE, format!("{}; path=/; HttpOnly", SESSION))],
+                Redirect::to("/home"),
+            )
+                .into_response()
+        }
+
+        async fn home(headers: HeaderMap) -> Response {
+            if !has_session(&headers) {
+                return Redirect::to("/users/sign_in").into_response();
+            }
+            (
+                [(header::SET_COOKIE, format!("{}; path=/; HttpOnly", SESSION))],
+                "<html><body>Accueil</body></html>",
+            )
+                .into_response()
+        }
+
+        async fn classrooms(headers: HeaderMap) -> Response {
+            if !has_session(&headers) {
+                return Redirect::to("/users/sign_in").into_response();
+            }
+            let html = format!(
+                r#"<html><head><meta name="csrf-token" content="{}" /></head><body>
+                <input type="hidden" name="course_user_signature[planning_event_id]" value="{}" />
+                </body></html>"#,
+                PAGE_CSRF, EVENT_ID
+            );
+            ([(header::CONTENT_TYPE, "text/html")], html).into_response()
+        }
+
+        async fn course_user_signatures(headers: HeaderMap, Form(form): Form<HashMap<String, String>>) -> Response {
+            if !has_session(&headers) {
+                return (StatusCode::OK, r#"window.location.href = "/users/sign_in";"#).into_response();
+            }
+            let csrf_header = headers.get("X-CSRF-Token").and_then(|v| v.to_str().ok());
+            if csrf_header != Some(PAGE_CSRF) || form.get("authenticity_token").map(String::as_str) != Some(PAGE_CSRF) {
+                return (StatusCode::UNPROCESSABLE_ENTITY, "CSRF invalide").into_response();
+            }
+            if form.get("course_user_signature[planning_event_id]").map(String::as_str) != Some(EVENT_ID) {
+                return (StatusCode::OK, r#"toastr.error("Événement introuvable");"#).into_response();
+            }
+            let code: String = (1..=6)
+                .filter_map(|i| form.get(&format!("secret_code_part_{}", i)).cloned())
+                .collect();
+            if code != CODE {
+                return (StatusCode::OK, r#"toastr.error("Le code saisi est invalide");"#).into_response();
+            }
+            (StatusCode::OK, r##"$("#modal").modal("hide"); toastr.success("Votre présence a été enregistrée");"##)
+                .into_response()
+        }
+
+        async fn json_dashboard(headers: HeaderMap, RawQuery(query): RawQuery) -> Response {
+            if !has_session(&headers) {
+                return StatusCode::UNAUTHORIZED.into_response();
+            }
+            if !query.is_some_and(|q| q.contains("start=") && q.contains("end=")) {
+                return StatusCode::BAD_REQUEST.into_response();
+            }
+            let events = format!(
+                r#"[{{"id":{},"title":"Algorithmique","start":"2026-10-19T09:00:00+02:00","end":"2026-10-19T12:00:00+02:00","registrable":true}}]"#,
+                EVENT_ID
+            );
+            ([(header::CONTENT_TYPE, "application/json")], events).into_response()
+        }
+
+        /// Démarre le serveur sur un port libre et retourne un client qui pointe dessus.
+        pub async fn start() -> EdsquareClient {
+            let app = Router::new()
+                .route("/users/sign_in", get(sign_in_page).post(sign_in))
+                .route("/home", get(home))
+                .route("/apps/classrooms", get(classrooms))
+                .route("/apps/course_user_signatures", post(course_user_signatures))
+                .route("/apps/planning/json_dashboard", get(json_dashboard));
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+            EdsquareClient::new(&format!("http://{}/", addr)).unwrap()
+        }
+    }
+
+    fn session_cookies() -> Vec<EdsquareCookieItem> {
+        let (name, value) = mock::SESSION.split_once('=').unwrap();
+        vec![CookieItem {
+            name: name.to_string(),
+            value: value.to_string(),
+            domain: "127.0.0.1".to_string(),
+            path: "/".to_string(),
+            expires: None,
+            http_only: true,
+            secure: false,
+            same_site: None,
+        }]
+    }
+
+    fn expired_cookies() -> Vec<EdsquareCookieItem> {
+        let mut cookies = session_cookies();
+        cookies[0].value = "expired".to_string();
+        cookies
+    }
+
+    #[tokio::test]
+    async fn login_flow_returns_session_cookies() {
+        let client = mock::start().await;
+        let cookies = open_edsquare_session(&client, mock::EMAIL, mock::PASSWORD).await.unwrap();
+        let session = cookies.iter().find(|c| c.name == "_edsquare_session").unwrap();
+        assert_eq!(session.to_header_value(), mock::SESSION);
+        assert_eq!(session.domain, "127.0.0.1");
+
+        let refused = open_edsquare_session(&client, mock::EMAIL, "wrong").await;
+        assert!(matches!(refused, Err(EdsquareError::InvalidCredentials)), "{:?}", refused.map(|c| c.len()));
+    }
+
+    #[tokio::test]
+    async fn saved_credentials_are_suspended_after_repeated_refusals() {
+        let Some(db_url) = crate::misc::test_database_url() else {
+            return;
+        };
+        let state = GlobalState::for_tests(&db_url, mock::start().await);
+        let user = ulid::Ulid::new().to_string();
+        save_edsquare_credentials(&state, &user, mock::EMAIL, "wrong").unwrap();
+
+        for attempt in 1..state.edsquare_max_login_failures {
+            let refused = login_edsquare_with_saved(&user, &state).await;
+            assert!(matches!(refused, Err(EdsquareError::InvalidCredentials)), "{:?}", refused.err());
+            let status = get_edsquare_credential_status(&state, &user).unwrap().unwrap();
+            assert_eq!(status.failed_login_count, attempt);
+            assert!(status.last_failed_login_at.is_some());
+            assert!(!status.suspended);
+        }
+
+        let mut events = state.events.subscribe();
+        let suspended = login_edsquare_with_saved(&user, &state).await;
+        assert!(matches!(suspended, Err(EdsquareError::CredentialsSuspended)), "{:?}", suspended.err());
+        assert!(matches!(
+            events.try_recv(),
+            Ok(ServerEvent::EdsquareCredentialsSuspended { failed_login_count: 3, .. })
+        ));
+
+        // Suspendu : plus aucune tentative auprès d'EDSquare, le compteur ne bouge plus
+        let still_suspended = login_edsquare_with_saved(&user, &state).await;
+        assert!(matches!(still_suspended, Err(EdsquareError::CredentialsSuspended)));
+        let status = get_edsquare_credential_status(&state, &user).unwrap().unwrap();
+        assert_eq!(status.failed_login_count, state.edsquare_max_login_failures);
+        assert!(status.suspended);
+
+        // Une connexion manuelle réussie réarme la reconnexion automatique
+        login_edsquare(mock::EMAIL, mock::PASSWORD, &user, &state).await.unwrap();
+        let status = get_edsquare_credential_status(&state, &user).unwrap().unwrap();
+        assert_eq!(status.failed_login_count, 0);
+        assert_eq!(status.last_failed_login_at, None);
+        assert!(!status.suspended);
+        assert!(login_edsquare_with_saved(&user, &state).await.is_ok());
+
+        crate::api::users::delete_user_account(&state, &user).unwrap();
+    }
+
+    #[tokio::test]
+    async fn expired_session_reports_why_the_reconnection_failed() {
+        let Some(db_url) = crate::misc::test_database_url() else {
+            return;
+        };
+        let state = GlobalState::for_tests(&db_url, mock::start().await);
+        let user = ulid::Ulid::new().to_string();
+        let signature = "data:image/png;base64,iVBORw0KGgo=";
+        save_edsquare_credentials(&state, &user, mock::EMAIL, "wrong").unwrap();
+
+        // Session expirée et mot de passe sauvegardé refusé : l'utilisateur doit corriger ses identifiants
+        save_edsquare_cookies(&state, &user, &expired_cookies()).unwrap();
+        let refused = validate_edsquare_code(mock::CODE, mock::EVENT_ID, signature, &user, &state).await;
+        assert!(matches!(refused, Err(EdsquareError::InvalidCredentials)), "{:?}", refused);
+
+        for _ in 1..state.edsquare_max_login_failures {
+            let _ = login_edsquare_with_saved(&user, &state).await;
+        }
+        save_edsquare_cookies(&state, &user, &expired_cookies()).unwrap();
+        let suspended = validate_edsquare_code(mock::CODE, mock::EVENT_ID, signature, &user, &state).await;
+        assert!(matches!(suspended, Err(EdsquareError::CredentialsSuspended)), "{:?}", suspended);
+
+        crate::api::users::delete_user_account(&state, &user).unwrap();
+    }
+
+    #[tokio::test]
+    async fn csrf_token_is_read_from_classrooms_page() {
+        let client = mock::start().await;
+        let token = fetch_csrf_token_with_cookies(&client, &cookie_header(&session_cookies())).await;
+        assert_eq!(token, Ok(Some(mock::PAGE_CSRF.to_string())));
+
+        let expired = fetch_csrf_token_with_cookies(&client, &cookie_header(&expired_cookies())).await;
+        assert_eq!(expired, Err(EdsquareError::SessionExpired));
+        assert_eq!(check_edsquare_session(&client, &expired_cookies()).await, Err(EdsquareError::SessionExpired));
+        assert_eq!(check_edsquare_session(&client, &session_cookies()).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn validation_flow_sends_code_and_csrf() {
+        let client = mock::start().await;
+        let signature = "data:image/png;base64,iVBORw0KGgo=";
+
+        let response = validate_edsquare_code_once(&client, mock::CODE, mock::EVENT_ID, signature, &session_cookies())
+            .await
+            .unwrap();
+        assert!(response.success);
+        assert_eq!(response.planning_event_id.as_deref(), Some(mock::EVENT_ID));
+
+        let wrong_code = validate_edsquare_code_once(&client, "654321", mock::EVENT_ID, signature, &session_cookies()).await;
+        assert!(matches!(wrong_code, Err(EdsquareError::InvalidCode(_))), "{:?}", wrong_code);
+
+        // "Événement introuvable" signifie que la présence est déjà enregistrée
+        let unknown_event = validate_edsquare_code_once(&client, mock::CODE, "1", signature, &session_cookies())
+            .await
+            .unwrap();
+        assert!(unknown_event.success);
+
+        let expired = validate_edsquare_code_once(&client, mock::CODE, mock::EVENT_ID, signature, &expired_cookies()).await;
+        assert!(matches!(expired, Err(EdsquareError::SessionExpired)));
+    }
+
+    #[tokio::test]
+    async fn planning_flow_parses_events() {
+        let client = mock::start().await;
+        let start = chrono_tz::Europe::Paris.with_ymd_and_hms(2026, 10, 19, 0, 0, 0).unwrap();
+        let end = start + chrono::Duration::days(1);
+
+        let events = fetch_planning_json(&client, &cookie_header(&session_cookies()), start, end).await.unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].id.to_string(), mock::EVENT_ID);
+        assert_eq!(events[0].title, "Algorithmique");
+
+        let expired = fetch_planning_json(&client, &cookie_header(&expired_cookies()), start, end).await;
+        assert!(matches!(expired, Err(EdsquareError::SessionExpired)));
+    }
+}
@@ -0,0 +1,107 @@
+use std::{convert::Infallible, time::Duration};
+
+use axum::{
+    extract::State,
+    response::{
+        IntoResponse,
+        sse::{Event, KeepAlive, Sse},
+    },
+};
+use futures_util::{Stream, StreamExt};
+use tokio_stream::wrappers::{BroadcastStream, errors::BroadcastStreamRecvError};
+use tracing::{info, warn};
+
+use crate::{
+    api::auth::JwtClaims,
+    misc::{EventBus, GlobalState, ServerEvent},
+};
+
+#[utoipa::path(
+    get,
+    path = "/api/events/stream",
+    description = "Server-Sent Events stream: `sign_results`, `edsquare_validation`, `reservation_changed` and `cookie_status_changed`. Each event's data is a JSON `ServerEvent`. Per-user events (EDSquare cookie status, session alerts, suspended credentials) are only sent to that user.",
+    responses(
+        (status = 200, description = "Event stream (text/event-stream)", body = crate::misc::ServerEvent, content_type = "text/event-stream"),
+        (status = 401, description = "Unauthorized"),
+    ),
+    tag = "Events"
+)]
+pub async fn stream(State(state): State<GlobalState>, jwt_user: JwtClaims) -> impl IntoResponse {
+    info!("Abonnement SSE ouvert pour {}", jwt_user.sub);
+    let events = events_for_user(&state.events, jwt_user.sub.to_string()).filter_map(|message| async move {
+        match message {
+            Ok(event) => match Event::default().event(event.name()).json_data(&event) {
+                Ok(sse_event) => Some(Ok::<_, Infallible>(sse_event)),
+                Err(e) => {
+                    warn!("SSE: impossible de sérialiser l'événement: {}", e);
+                    None
+                }
+            },
+            // Abonné trop lent : on signale la perte pour que le client recharge son état
+            Err(BroadcastStreamRecvError::Lagged(skipped)) => {
+                Some(Ok(Event::default().event("lagged").data(skipped.to_string())))
+            }
+        }
+    });
+    Sse::new(events).keep_alive(KeepAlive::new().interval(Duration::from_secs(15)))
+}
+
+/// Événements reçus par `user_id` : ceux diffusés à tous et ses événements personnels.
+fn events_for_user(
+    bus: &EventBus,
+    user_id: String,
+) -> impl Stream<Item = Result<ServerEvent, BroadcastStreamRecvError>> + use<> {
+    BroadcastStream::new(bus.subscribe()).filter(move |message| {
+        let visible = match message {
+            Ok(event) => event.recipient().is_none_or(|recipient| recipient == user_id),
+            Err(_) => true,
+        };
+        std::future::ready(visible)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cookie_status(user_id: Option<&str>) -> ServerEvent {
+        ServerEvent::CookieStatusChanged {
+            provider: if user_id.is_some() { "edsquare" } else { "intra" }.to_string(),
+            user_id: user_id.map(str::to_string),
+            available: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn per_user_events_only_reach_their_user() {
+        let bus = EventBus::new();
+        let alice = events_for_user(&bus, "alice".to_string());
+        bus.publish(cookie_status(Some("bob")));
+        bus.publish(ServerEvent::EdsquareCredentialsSuspended {
+            user_id: "bob".to_string(),
+            failed_login_count: 3,
+        });
+        bus.publish(cookie_status(Some("alice")));
+        bus.publish(cookie_status(None));
+        bus.publish(ServerEvent::RoomClosed {
+            room_id: "room".to_string(),
+        });
+        drop(bus);
+
+        let received: Vec<(String, Option<String>)> = alice
+            .map(|event| {
+                let event = event.unwrap();
+                (event.name().to_string(), event.recipient().map(str::to_string))
+            })
+            .collect()
+            .await;
+        assert_eq!(
+            received,
+            vec![
+                ("cookie_status_changed".to_string(), Some("alice".to_string())),
+                ("cookie_status_changed".to_string(), None),
+                ("room_closed".to_string(), None),
+            ]
+        );
+    }
+}
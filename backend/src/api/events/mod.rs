@@ -0,0 +1,4 @@
+pub mod endpoints;
+mod services;
+
+pub use services::{get_routes, spawn_cookie_status_watcher};
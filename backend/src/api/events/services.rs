@@ -0,0 +1,69 @@
+use std::time::Duration;
+
+use axum::{Router, routing::get};
+use tracing::{error, info};
+
+use crate::{
+    api::sign::check_cookie_exists,
+    misc::{GlobalState, ServerEvent},
+};
+
+/// Fréquence de vérification des cookies intra (alimentés par un process externe).
+const COOKIE_WATCH_INTERVAL: Duration = Duration::from_secs(30);
+
+pub fn get_routes(state: GlobalState) -> Router {
+    Router::new()
+        .route("/stream", get(super::endpoints::stream))
+        .with_state(state)
+}
+
+/// Surveille la présence des cookies intra du jour et publie un événement à chaque changement.
+pub fn spawn_cookie_status_watcher(state: GlobalState) {
+    tokio::spawn(async move {
+        let mut last: Option<bool> = None;
+        let mut interval = tokio::time::interval(COOKIE_WATCH_INTERVAL);
+        loop {
+            interval.tick().await;
+            let today = state.today();
+            let available = match check_cookie_exists(&state, today) {
+                Ok(available) => available,
+                Err(e) => {
+                    error!("Cookie watcher: {}", e);
+                    continue;
+                }
+            };
+            if let Some(event) = intra_cookie_change(last, available) {
+                info!("Cookies intra du jour: disponibles={}", available);
+                state.events.publish(event);
+            }
+            last = Some(available);
+        }
+    });
+}
+
+/// Événement à publier quand la disponibilité des cookies intra change ; rien au premier relevé (démarrage).
+fn intra_cookie_change(last: Option<bool>, available: bool) -> Option<ServerEvent> {
+    last.is_some_and(|previous| previous != available)
+        .then_some(ServerEvent::CookieStatusChanged {
+            provider: "intra".to_string(),
+            user_id: None,
+            available,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn publishes_only_intra_cookie_changes() {
+        assert!(intra_cookie_change(None, true).is_none());
+        assert!(intra_cookie_change(Some(true), true).is_none());
+        let Some(ServerEvent::CookieStatusChanged { provider, user_id, available }) = intra_cookie_change(Some(true), false)
+        else {
+            panic!("expected a cookie status event");
+        };
+        assert_eq!((provider.as_str(), user_id, available), ("intra", None, false));
+        assert!(intra_cookie_change(Some(false), true).is_some_and(|event| event.recipient().is_none()));
+    }
+}
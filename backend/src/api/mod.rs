@@ -1,6 +1,10 @@
 pub mod admin;
+pub mod attendance;
 pub mod auth;
 pub mod edsquare;
+pub mod events;
+pub mod reservations;
+pub mod rooms;
 pub mod sign;
 pub mod swagger;
 pub mod users;
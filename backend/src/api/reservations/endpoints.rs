@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    response::IntoResponse,
+};
+use chrono::NaiveDate;
+use http::StatusCode;
+use tracing::{error, info};
+use ulid::Ulid;
+
+use crate::{
+    api::{
+        auth::JwtClaims,
+        reservations::{
+            models::{CreateReservationPayload, ReservationResponse},
+            services::{
+                cancel_reservation as cancel, create_reservation as create,
+                get_reservations_for_date, normalize_event_code,
+            },
+        },
+        users::{get_user_by_id, get_users_by_ulids},
+    },
+    misc::GlobalState,
+};
+
+#[utoipa::path(
+    post,
+    path = "/api/reservations",
+    description = "Reserve a session day (and optionally an intra event) to ask to be signed first",
+    request_body = CreateReservationPayload,
+    responses(
+        (status = 201, description = "Reservation created (or existing pending reservation returned)", body = ReservationResponse),
+        (status = 400, description = "Invalid date or event"),
+        (status = 401, description = "Unauthorized"),
+    ),
+    tag = "Reservations"
+)]
+pub async fn create_reservation(
+    State(state): State<GlobalState>,
+    jwt_user: JwtClaims,
+    Json(payload): Json<CreateReservationPayload>,
+) -> impl IntoResponse {
+    let today = state.today();
+    let date = match payload.date.as_deref().filter(|d| !d.trim().is_empty()) {
+        Some(d) => match NaiveDate::parse_from_str(d.trim(), "%Y-%m-%d") {
+            Ok(date) => date,
+            Err(_) => {
+                return (StatusCode::BAD_REQUEST, "Format de date invalide. Utilisez YYYY-MM-DD.").into_response();
+            }
+        },
+        None => today,
+    };
+    if date < today {
+        return (StatusCode::BAD_REQUEST, "Impossible de réserver une date passée").into_response();
+    }
+
+    let event = match payload.event.as_deref().filter(|e| !e.trim().is_empty()) {
+        Some(e) => match normalize_event_code(e) {
+            Some(code) => Some(code),
+            None => {
+                return (StatusCode::BAD_REQUEST, "Événement invalide (attendu: event-123456)").into_response();
+            }
+        },
+        None => None,
+    };
+
+    let user = match get_user_by_id(&state, &jwt_user.sub) {
+        Ok(Some(user)) => user,
+        Ok(None) => return (StatusCode::NOT_FOUND, "User not found").into_response(),
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Error fetching user").into_response(),
+    };
+
+    match create(&state, &user.id, date, event.as_deref()) {
+        Ok(reservation) => {
+            info!("Réservation {} pour {} le {} ({:?})", reservation.id, user.username, date, event);
+            state.events.publish(reservation.to_event());
+            let response = ReservationResponse::from_reservation(reservation, user.username);
+            (StatusCode::CREATED, Json(response)).into_response()
+        }
+        Err(e) => {
+            error!("Error creating reservation: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Error creating reservation").into_response()
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/reservations/today",
+    description = "List today's reservations (pending and fulfilled), oldest first",
+    responses(
+        (status = 200, description = "Today's reservations", body = Vec<ReservationResponse>),
+        (status = 401, description = "Unauthorized"),
+    ),
+    tag = "Reservations"
+)]
+pub async fn get_today_reservations(
+    State(state): State<GlobalState>,
+    _jwt_user: JwtClaims,
+) -> impl IntoResponse {
+    let today = state.today();
+    let reservations = match get_reservations_for_date(&state, today) {
+        Ok(reservations) => reservations,
+        Err(e) => {
+            error!("Error fetching reservations: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Error fetching reservations").into_response();
+        }
+    };
+
+    let ulids: Vec<Ulid> = reservations
+        .iter()
+        .filter_map(|r| Ulid::from_string(&r.user_id).ok())
+        .collect();
+    let username_by_id: HashMap<String, String> = get_users_by_ulids(&state, &ulids)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|u| (u.id, u.username))
+        .collect();
+
+    let response: Vec<ReservationResponse> = reservations
+        .into_iter()
+        .map(|r| {
+            let username = username_by_id
+                .get(&r.user_id)
+                .cloned()
+                .unwrap_or_else(|| "<unknown>".to_string());
+            ReservationResponse::from_reservation(r, username)
+        })
+        .collect();
+    (StatusCode::OK, Json(response)).into_response()
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/reservations/{id}",
+    description = "Cancel one of the current user's pending reservations",
+    params(("id" = String, Path, description = "Reservation id")),
+    responses(
+        (status = 204, description = "Reservation cancelled"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "No pending reservation with this id"),
+    ),
+    tag = "Reservations"
+)]
+pub async fn cancel_reservation(
+    State(state): State<GlobalState>,
+    jwt_user: JwtClaims,
+    Path(reservation_id): Path<String>,
+) -> impl IntoResponse {
+    match cancel(&state, &reservation_id, &jwt_user.sub.to_string()) {
+        Ok(Some(reservation)) => {
+            state.events.publish(reservation.to_event());
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Ok(None) => (StatusCode::NOT_FOUND, "No pending reservation with this id").into_response(),
+        Err(e) => {
+            error!("Error cancelling reservation {}: {}", reservation_id, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Error cancelling reservation").into_response()
+        }
+    }
+}
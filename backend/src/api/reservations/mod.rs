@@ -0,0 +1,5 @@
+pub mod endpoints;
+mod models;
+mod services;
+
+pub use services::{fulfill_reservations, get_reserved_user_ids, get_routes};
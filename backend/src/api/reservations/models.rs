@@ -0,0 +1,72 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::misc::ServerEvent;
+
+#[derive(Debug, Queryable, Selectable)]
+#[diesel(table_name = crate::schema::sign_reservations)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Reservation {
+    pub id: String,
+    pub user_id: String,
+    pub date: NaiveDate,
+    pub event: Option<String>,
+    pub status: String,
+    pub created_at: NaiveDateTime,
+    pub fulfilled_at: Option<NaiveDateTime>,
+}
+
+impl Reservation {
+    /// Événement temps réel correspondant à l'état courant de la réservation.
+    pub fn to_event(&self) -> ServerEvent {
+        ServerEvent::ReservationChanged {
+            id: self.id.clone(),
+            user_id: self.user_id.clone(),
+            date: self.date.to_string(),
+            event: self.event.clone(),
+            status: self.status.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateReservationPayload {
+    /// Jour de session (YYYY-MM-DD). Par défaut : aujourd'hui.
+    #[schema(example = "2026-01-30")]
+    #[serde(default)]
+    pub date: Option<String>,
+    /// Événement intra visé (`event-123456`, `123456` ou lien de l'événement). Par défaut : n'importe quel événement du jour.
+    #[schema(example = "event-123456")]
+    #[serde(default)]
+    pub event: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReservationResponse {
+    pub id: String,
+    pub user_id: String,
+    pub username: String,
+    pub date: NaiveDate,
+    pub event: Option<String>,
+    /// `pending`, `fulfilled` ou `cancelled`
+    pub status: String,
+    pub created_at: NaiveDateTime,
+    pub fulfilled_at: Option<NaiveDateTime>,
+}
+
+impl ReservationResponse {
+    pub fn from_reservation(reservation: Reservation, username: String) -> Self {
+        Self {
+            id: reservation.id,
+            user_id: reservation.user_id,
+            username,
+            date: reservation.date,
+            event: reservation.event,
+            status: reservation.status,
+            created_at: reservation.created_at,
+            fulfilled_at: reservation.fulfilled_at,
+        }
+    }
+}
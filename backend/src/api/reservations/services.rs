@@ -0,0 +1,229 @@
+use std::collections::HashSet;
+
+use axum::{
+    Router,
+    routing::{delete, get, post},
+};
+use chrono::{NaiveDate, Utc};
+use diesel::prelude::*;
+use regex::Regex;
+use ulid::Ulid;
+
+use super::models::Reservation;
+use crate::misc::GlobalState;
+
+pub const STATUS_PENDING: &str = "pending";
+pub const STATUS_FULFILLED: &str = "fulfilled";
+pub const STATUS_CANCELLED: &str = "cancelled";
+
+pub fn get_routes(state: GlobalState) -> Router {
+    Router::new()
+        .route("/", post(super::endpoints::create_reservation))
+        .route("/today", get(super::endpoints::get_today_reservations))
+        .route("/{id}", delete(super::endpoints::cancel_reservation))
+        .with_state(state)
+}
+
+/// Normalise un identifiant d'événement intra : `event-123456`, `123456` ou un lien contenant `event-123456`.
+pub fn normalize_event_code(input: &str) -> Option<String> {
+    let input = input.trim();
+    if !input.is_empty() && input.chars().all(|c| c.is_ascii_digit()) {
+        return Some(format!("event-{}", input));
+    }
+    let re = Regex::new(r"(?i)event-(\d+)").ok()?;
+    re.captures(input).map(|caps| format!("event-{}", &caps[1]))
+}
+
+/// Crée une réservation, ou retourne la réservation en attente identique si elle existe déjà.
+pub fn create_reservation(
+    state: &GlobalState,
+    user_id_param: &str,
+    date_param: NaiveDate,
+    event_param: Option<&str>,
+) -> Result<Reservation, String> {
+    use crate::schema::sign_reservations::dsl::*;
+
+    let mut conn = match state.get_db_conn() {
+        Ok(conn) => conn,
+        Err(_) => return Err("Failed to get database connection".into()),
+    };
+
+    let mut existing_query = sign_reservations
+        .filter(user_id.eq(user_id_param))
+        .filter(date.eq(date_param))
+        .filter(status.eq(STATUS_PENDING))
+        .into_boxed();
+    existing_query = match event_param {
+        Some(e) => existing_query.filter(event.eq(e)),
+        None => existing_query.filter(event.is_null()),
+    };
+    if let Some(existing) = existing_query
+        .select(Reservation::as_select())
+        .first(&mut conn)
+        .optional()
+        .map_err(|e| format!("Database error when fetching reservation: {}", e))?
+    {
+        return Ok(existing);
+    }
+
+    diesel::insert_into(sign_reservations)
+        .values((
+            id.eq(Ulid::new().to_string()),
+            user_id.eq(user_id_param),
+            date.eq(date_param),
+            event.eq(event_param),
+            status.eq(STATUS_PENDING),
+            created_at.eq(Utc::now().naive_utc()),
+        ))
+        .returning(Reservation::as_returning())
+        .get_result(&mut conn)
+        .map_err(|e| format!("Database error when creating reservation: {}", e))
+}
+
+/// Réservations (en attente ou honorées) pour une journée, les plus anciennes d'abord.
+pub fn get_reservations_for_date(
+    state: &GlobalState,
+    date_param: NaiveDate,
+) -> Result<Vec<Reservation>, String> {
+    use crate::schema::sign_reservations::dsl::*;
+
+    let mut conn = match state.get_db_conn() {
+        Ok(conn) => conn,
+        Err(_) => return Err("Failed to get database connection".into()),
+    };
+    sign_reservations
+        .filter(date.eq(date_param))
+        .filter(status.ne(STATUS_CANCELLED))
+        .order(created_at.asc())
+        .select(Reservation::as_select())
+        .load(&mut conn)
+        .map_err(|e| format!("Database error when fetching reservations: {}", e))
+}
+
+/// Utilisateurs ayant une réservation en attente pour la journée.
+pub fn get_reserved_user_ids(
+    state: &GlobalState,
+    date_param: NaiveDate,
+) -> Result<HashSet<String>, String> {
+    use crate::schema::sign_reservations::dsl::*;
+
+    let mut conn = match state.get_db_conn() {
+        Ok(conn) => conn,
+        Err(_) => return Err("Failed to get database connection".into()),
+    };
+    let ids: Vec<String> = sign_reservations
+        .filter(date.eq(date_param))
+        .filter(status.eq(STATUS_PENDING))
+        .select(user_id)
+        .load(&mut conn)
+        .map_err(|e| format!("Database error when fetching reservations: {}", e))?;
+    Ok(ids.into_iter().collect())
+}
+
+/// Annule une réservation en attente de l'utilisateur. Retourne `None` si elle n'existe pas.
+pub fn cancel_reservation(
+    state: &GlobalState,
+    reservation_id: &str,
+    user_id_param: &str,
+) -> Result<Option<Reservation>, String> {
+    use crate::schema::sign_reservations::dsl::*;
+
+    let mut conn = match state.get_db_conn() {
+        Ok(conn) => conn,
+        Err(_) => return Err("Failed to get database connection".into()),
+    };
+    diesel::update(
+        sign_reservations
+            .filter(id.eq(reservation_id))
+            .filter(user_id.eq(user_id_param))
+            .filter(status.eq(STATUS_PENDING)),
+    )
+    .set(status.eq(STATUS_CANCELLED))
+    .returning(Reservation::as_returning())
+    .get_result(&mut conn)
+    .optional()
+    .map_err(|e| format!("Database error when cancelling reservation: {}", e))
+}
+
+/// Marque comme honorées les réservations en attente des utilisateurs signés avec succès
+/// pour cet événement (ou sans événement précis). Retourne les réservations mises à jour.
+pub fn fulfill_reservations(
+    state: &GlobalState,
+    user_ids_param: &[String],
+    date_param: NaiveDate,
+    event_param: &str,
+) -> Result<Vec<Reservation>, String> {
+    use crate::schema::sign_reservations::dsl::*;
+
+    if user_ids_param.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut conn = match state.get_db_conn() {
+        Ok(conn) => conn,
+        Err(_) => return Err("Failed to get database connection".into()),
+    };
+    diesel::update(
+        sign_reservations
+            .filter(user_id.eq_any(user_ids_param))
+            .filter(date.eq(date_param))
+            .filter(status.eq(STATUS_PENDING))
+            .filter(event.is_null().or(event.eq(event_param))),
+    )
+    .set((status.eq(STATUS_FULFILLED), fulfilled_at.eq(Utc::now().naive_utc())))
+    .returning(Reservation::as_returning())
+    .get_results(&mut conn)
+    .map_err(|e| format!("Database error when fulfilling reservations: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::edsquare::{EdsquareClient, client::DEFAULT_EDSQUARE_BASE_URL};
+    use crate::api::users::{User, create_user, delete_user_account};
+
+    #[test]
+    fn reservations_are_reserved_cancelled_and_fulfilled() {
+        let Some(db_url) = crate::misc::test_database_url() else {
+            return;
+        };
+        let state = GlobalState::for_tests(&db_url, EdsquareClient::new(DEFAULT_EDSQUARE_BASE_URL).unwrap());
+        let [alice, bob, carol] = ["alice", "bob", "carol"].map(|name| {
+            let user = User::new(format!("{}-{}", name, Ulid::new()), "password".to_string());
+            let user_id = user.id.clone();
+            create_user(&state, user).unwrap();
+            user_id
+        });
+        let day = NaiveDate::from_ymd_opt(2099, 1, 5).unwrap();
+
+        // Réserver deux fois la même chose retourne la réservation existante
+        let any_event = create_reservation(&state, &alice, day, None).unwrap();
+        assert_eq!(create_reservation(&state, &alice, day, None).unwrap().id, any_event.id);
+        let event_1 = create_reservation(&state, &bob, day, Some("event-1")).unwrap();
+        let event_2 = create_reservation(&state, &carol, day, Some("event-2")).unwrap();
+        let reserved = get_reserved_user_ids(&state, day).unwrap();
+        assert!([&alice, &bob, &carol].iter().all(|id| reserved.contains(*id)));
+
+        // Seul l'utilisateur annule sa réservation, et une seule fois
+        assert!(cancel_reservation(&state, &event_2.id, &alice).unwrap().is_none());
+        assert_eq!(cancel_reservation(&state, &event_2.id, &carol).unwrap().unwrap().status, STATUS_CANCELLED);
+        assert!(cancel_reservation(&state, &event_2.id, &carol).unwrap().is_none());
+        assert!(!get_reserved_user_ids(&state, day).unwrap().contains(&carol));
+
+        // event-1 honore la réservation sans événement et celle d'event-1, pas celles d'autres utilisateurs
+        let other_event = create_reservation(&state, &carol, day, Some("event-2")).unwrap();
+        let signed = [alice.clone(), bob.clone(), carol.clone()];
+        let mut fulfilled: Vec<String> = fulfill_reservations(&state, &signed, day, "event-1").unwrap().into_iter().map(|r| r.id).collect();
+        fulfilled.sort();
+        let mut expected = vec![any_event.id.clone(), event_1.id.clone()];
+        expected.sort();
+        assert_eq!(fulfilled, expected);
+        let reserved = get_reserved_user_ids(&state, day).unwrap();
+        assert!(reserved.contains(&carol) && !reserved.contains(&alice) && !reserved.contains(&bob));
+        assert!(fulfill_reservations(&state, &[], day, "event-2").unwrap().is_empty());
+        assert_eq!(fulfill_reservations(&state, &signed, day, "event-2").unwrap()[0].id, other_event.id);
+
+        for user in [&alice, &bob, &carol] {
+            delete_user_account(&state, user).unwrap();
+        }
+    }
+}
@@ -0,0 +1,396 @@
+use axum::{
+    Json,
+    extract::{Path, State},
+    response::IntoResponse,
+};
+use chrono::Utc;
+use http::StatusCode;
+use tracing::{info, warn};
+use ulid::Ulid;
+
+use crate::{
+    api::{
+        attendance::{send_results_webhook, submit_for_users},
+        auth::JwtClaims,
+        rooms::{
+            models::{
+                CreateRoomPayload, JoinRoomPayload, RoomMember, RoomMemberResult, RoomResponse,
+                RoomScan, ScanRoomPayload, SignRoom,
+            },
+            services::{
+                ScanReservation, finish_room_signing, get_active_room, publish_room_updated, remove_room,
+                reserve_room_scan,
+            },
+        },
+        sign::{
+            IntraProvider, IntraSubmission, endpoints::fulfill_signed_reservations, get_cookies, url_parser::SignUrl,
+        },
+        users::{get_user_by_id, get_users_by_ulids},
+    },
+    misc::{GlobalState, ServerEvent, UserResultEvent},
+};
+
+/// Durée par défaut d'une salle (une demi-journée de cours).
+const DEFAULT_ROOM_DURATION_HOURS: i64 = 4;
+/// Durée maximale d'une salle.
+const MAX_ROOM_DURATION_HOURS: i64 = 24;
+
+#[utoipa::path(
+    post,
+    path = "/api/rooms",
+    description = "Create a sign room for a session. The creator joins it with consent.",
+    request_body = CreateRoomPayload,
+    responses(
+        (status = 201, description = "Room created", body = RoomResponse),
+        (status = 400, description = "Invalid name or expiration"),
+        (status = 401, description = "Unauthorized"),
+    ),
+    tag = "Rooms"
+)]
+pub async fn create_room(
+    State(state): State<GlobalState>,
+    jwt_user: JwtClaims,
+    Json(payload): Json<CreateRoomPayload>,
+) -> impl IntoResponse {
+    let name = payload.name.trim();
+    if name.is_empty() {
+        return (StatusCode::BAD_REQUEST, "name must not be empty").into_response();
+    }
+    let now = Utc::now();
+    let expires_at = payload
+        .expires_at
+        .unwrap_or_else(|| now + chrono::Duration::hours(DEFAULT_ROOM_DURATION_HOURS));
+    if expires_at <= now || expires_at > now + chrono::Duration::hours(MAX_ROOM_DURATION_HOURS) {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("expires_at must be in the next {} hours", MAX_ROOM_DURATION_HOURS),
+        )
+            .into_response();
+    }
+
+    let user = match get_user_by_id(&state, &jwt_user.sub) {
+        Ok(Some(user)) => user,
+        Ok(None) => return (StatusCode::NOT_FOUND, "User not found").into_response(),
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Error fetching user").into_response(),
+    };
+
+    let room = SignRoom {
+        id: Ulid::new().to_string(),
+        name: name.to_string(),
+        created_by: user.id.clone(),
+        created_at: now,
+        expires_at,
+        members: vec![RoomMember {
+            user_id: user.id,
+            username: user.username.clone(),
+            consent: true,
+            joined_at: now,
+        }],
+        signing_event: None,
+        scans: Vec::new(),
+    };
+    info!("Salle {} ({}) créée par {} jusqu'à {}", room.id, room.name, user.username, expires_at);
+    state.sign_rooms.write().await.insert(room.id.clone(), room.clone());
+    publish_room_updated(&state, &room);
+
+    (StatusCode::CREATED, Json(RoomResponse::from(room))).into_response()
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/rooms",
+    description = "List active sign rooms, soonest to expire first",
+    responses(
+        (status = 200, description = "Active rooms", body = Vec<RoomResponse>),
+        (status = 401, description = "Unauthorized"),
+    ),
+    tag = "Rooms"
+)]
+pub async fn get_rooms(State(state): State<GlobalState>, _jwt_user: JwtClaims) -> impl IntoResponse {
+    let now = Utc::now();
+    let mut rooms: Vec<SignRoom> = state
+        .sign_rooms
+        .read()
+        .await
+        .values()
+        .filter(|room| room.expires_at > now)
+        .cloned()
+        .collect();
+    rooms.sort_by_key(|room| room.expires_at);
+    let response: Vec<RoomResponse> = rooms.into_iter().map(RoomResponse::from).collect();
+    (StatusCode::OK, Json(response)).into_response()
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/rooms/{id}",
+    description = "Get a sign room with its members and scan results",
+    params(("id" = String, Path, description = "Room id")),
+    responses(
+        (status = 200, description = "Room", body = RoomResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Room not found or expired"),
+    ),
+    tag = "Rooms"
+)]
+pub async fn get_room(
+    State(state): State<GlobalState>,
+    _jwt_user: JwtClaims,
+    Path(room_id): Path<String>,
+) -> impl IntoResponse {
+    match get_active_room(&state, &room_id).await {
+        Some(room) => (StatusCode::OK, Json(RoomResponse::from(room))).into_response(),
+        None => (StatusCode::NOT_FOUND, "Room not found").into_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/rooms/{id}",
+    description = "Close a sign room before its expiration (creator only)",
+    params(("id" = String, Path, description = "Room id")),
+    responses(
+        (status = 204, description = "Room closed"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Only the creator can close the room"),
+        (status = 404, description = "Room not found or expired"),
+    ),
+    tag = "Rooms"
+)]
+pub async fn close_room(
+    State(state): State<GlobalState>,
+    jwt_user: JwtClaims,
+    Path(room_id): Path<String>,
+) -> impl IntoResponse {
+    let Some(room) = get_active_room(&state, &room_id).await else {
+        return (StatusCode::NOT_FOUND, "Room not found").into_response();
+    };
+    if room.created_by != jwt_user.sub.to_string() {
+        return (StatusCode::FORBIDDEN, "Only the creator can close the room").into_response();
+    }
+    remove_room(&state, &room_id).await;
+    StatusCode::NO_CONTENT.into_response()
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/rooms/{id}/join",
+    description = "Join a sign room (or update consent if already a member)",
+    params(("id" = String, Path, description = "Room id")),
+    request_body = JoinRoomPayload,
+    responses(
+        (status = 200, description = "Joined", body = RoomResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Room not found or expired"),
+    ),
+    tag = "Rooms"
+)]
+pub async fn join_room(
+    State(state): State<GlobalState>,
+    jwt_user: JwtClaims,
+    Path(room_id): Path<String>,
+    Json(payload): Json<JoinRoomPayload>,
+) -> impl IntoResponse {
+    let user = match get_user_by_id(&state, &jwt_user.sub) {
+        Ok(Some(user)) => user,
+        Ok(None) => return (StatusCode::NOT_FOUND, "User not found").into_response(),
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Error fetching user").into_response(),
+    };
+
+    let room = {
+        let mut rooms = state.sign_rooms.write().await;
+        let Some(room) = rooms.get_mut(&room_id).filter(|room| room.expires_at > Utc::now()) else {
+            return (StatusCode::NOT_FOUND, "Room not found").into_response();
+        };
+        match room.members.iter_mut().find(|m| m.user_id == user.id) {
+            Some(member) => member.consent = payload.consent,
+            None => room.members.push(RoomMember {
+                user_id: user.id.clone(),
+                username: user.username.clone(),
+                consent: payload.consent,
+                joined_at: Utc::now(),
+            }),
+        }
+        room.clone()
+    };
+    info!("{} a rejoint la salle {} (consentement: {})", user.username, room_id, payload.consent);
+    publish_room_updated(&state, &room);
+
+    (StatusCode::OK, Json(RoomResponse::from(room))).into_response()
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/rooms/{id}/leave",
+    description = "Leave a sign room",
+    params(("id" = String, Path, description = "Room id")),
+    responses(
+        (status = 204, description = "Left the room"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Room not found, expired, or not a member"),
+    ),
+    tag = "Rooms"
+)]
+pub async fn leave_room(
+    State(state): State<GlobalState>,
+    jwt_user: JwtClaims,
+    Path(room_id): Path<String>,
+) -> impl IntoResponse {
+    let user_id = jwt_user.sub.to_string();
+    let room = {
+        let mut rooms = state.sign_rooms.write().await;
+        let Some(room) = rooms.get_mut(&room_id).filter(|room| room.expires_at > Utc::now()) else {
+            return (StatusCode::NOT_FOUND, "Room not found").into_response();
+        };
+        let before = room.members.len();
+        room.members.retain(|m| m.user_id != user_id);
+        if room.members.len() == before {
+            return (StatusCode::NOT_FOUND, "Not a member of this room").into_response();
+        }
+        room.clone()
+    };
+    publish_room_updated(&state, &room);
+    StatusCode::NO_CONTENT.into_response()
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/rooms/{id}/scan",
+    description = "Post the scanned QR code to the room: every consenting member is signed and the results are broadcast (`room_sign_results`). A new scan for the same event signs only the consenting members not signed yet; when there are none, it returns the latest results.",
+    params(("id" = String, Path, description = "Room id")),
+    request_body = ScanRoomPayload,
+    responses(
+        (status = 200, description = "Members signed (or every consenting member already signed for this event)", body = RoomScan),
+        (status = 400, description = "Invalid sign URL"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not a member of this room"),
+        (status = 404, description = "Room not found or no cookies found for today"),
+        (status = 409, description = "A scan is already being processed for this room"),
+    ),
+    tag = "Rooms"
+)]
+pub async fn scan_room(
+    State(state): State<GlobalState>,
+    jwt_user: JwtClaims,
+    Path(room_id): Path<String>,
+    Json(mut payload): Json<ScanRoomPayload>,
+) -> impl IntoResponse {
+    if let Err(err) = payload.target.expand_short_link(&state.intra).await {
+        warn!("Invalid sign URL '{}' in room {}: {}", payload.target.describe(), room_id, err);
+        return (StatusCode::BAD_REQUEST, format!("Invalid sign URL: {}", err)).into_response();
+    }
+    let sign_url = match payload.target.resolve() {
+        Ok(sign_url) => sign_url,
+        Err(err) => {
+            warn!("Invalid sign URL '{}' in room {}: {}", payload.target.describe(), room_id, err);
+            return (StatusCode::BAD_REQUEST, format!("Invalid sign URL: {}", err)).into_response();
+        }
+    };
+    let user_id = jwt_user.sub.to_string();
+
+    // Réserve la salle pour ce scan : seuls les membres pas encore signés pour l'événement sont signés
+    let (scanned_by, member_ids) = {
+        let mut rooms = state.sign_rooms.write().await;
+        let Some(room) = rooms.get_mut(&room_id).filter(|room| room.expires_at > Utc::now()) else {
+            return (StatusCode::NOT_FOUND, "Room not found").into_response();
+        };
+        match reserve_room_scan(room, &user_id, &sign_url.event) {
+            ScanReservation::NotMember => {
+                return (StatusCode::FORBIDDEN, "Not a member of this room").into_response();
+            }
+            ScanReservation::Done(scan) => return (StatusCode::OK, Json(scan)).into_response(),
+            ScanReservation::Busy => {
+                return (StatusCode::CONFLICT, "A scan is already being processed for this room").into_response();
+            }
+            ScanReservation::Reserved { scanned_by, member_ids } => (scanned_by, member_ids),
+        }
+    };
+
+    // La signature tourne dans sa propre tâche : si le client se déconnecte, elle va quand même
+    // au bout et libère la salle au lieu de la laisser verrouillée jusqu'à son expiration.
+    let task = tokio::spawn(sign_room_members(
+        state.clone(),
+        room_id.clone(),
+        scanned_by,
+        member_ids,
+        sign_url,
+    ));
+    match task.await {
+        Ok(Ok(scan)) => (StatusCode::OK, Json(scan)).into_response(),
+        Ok(Err((status, message))) => (status, message).into_response(),
+        Err(err) => {
+            warn!("Signature de la salle {} interrompue: {}", room_id, err);
+            finish_room_signing(&state, &room_id, None).await;
+            (StatusCode::INTERNAL_SERVER_ERROR, "Room signing failed").into_response()
+        }
+    }
+}
+
+/// Signe les membres consentants d'une salle réservée puis libère la salle, quelle que soit l'issue.
+async fn sign_room_members(
+    state: GlobalState,
+    room_id: String,
+    scanned_by: String,
+    member_ids: Vec<Ulid>,
+    sign_url: SignUrl,
+) -> Result<RoomScan, (StatusCode, String)> {
+    let cookies = match get_cookies(&state) {
+        Ok(Some(cookies)) => cookies,
+        Ok(None) => {
+            finish_room_signing(&state, &room_id, None).await;
+            return Err((StatusCode::NOT_FOUND, "No cookies found for today".to_string()));
+        }
+        Err(err) => {
+            finish_room_signing(&state, &room_id, None).await;
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, format!("Error fetching cookies: {}", err)));
+        }
+    };
+    let users = match get_users_by_ulids(&state, &member_ids) {
+        Ok(users) => users,
+        Err(err) => {
+            finish_room_signing(&state, &room_id, None).await;
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, format!("Error fetching users: {}", err)));
+        }
+    };
+
+    info!("Salle {}: {} signe {} membre(s) pour {}", room_id, scanned_by, users.len(), sign_url.event);
+    let submission = IntraSubmission { sign_url, cookies };
+    let results = submit_for_users(&state, &IntraProvider, users, &submission).await;
+
+    let scan = RoomScan {
+        event: submission.sign_url.event.clone(),
+        scanned_by: scanned_by.clone(),
+        scanned_at: Utc::now(),
+        results: results
+            .iter()
+            .map(|r| RoomMemberResult {
+                user_id: r.user_id.clone(),
+                username: r.username.clone(),
+                response: r.detail.response.clone(),
+            })
+            .collect(),
+    };
+    finish_room_signing(&state, &room_id, Some(scan.clone())).await;
+
+    state.events.publish(ServerEvent::RoomSignResults {
+        room_id: room_id.clone(),
+        event: scan.event.clone(),
+        scanned_by: scanned_by.clone(),
+        results: scan
+            .results
+            .iter()
+            .map(|r| UserResultEvent {
+                user_id: r.user_id.clone(),
+                username: r.username.clone(),
+                success: r.response.is_signed(),
+                message: r.response.to_message(),
+            })
+            .collect(),
+    });
+    // `room_sign_results` tient lieu de bilan SSE : seuls les réservations et le webhook restent à traiter
+    fulfill_signed_reservations(&state, &submission.sign_url.event, &results);
+    let initiator = format!("{} (salle {})", scanned_by, room_id);
+    send_results_webhook(&state, &IntraProvider, &initiator, &submission, &results);
+
+    Ok(scan)
+}
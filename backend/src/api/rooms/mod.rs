@@ -0,0 +1,5 @@
+pub mod endpoints;
+pub mod models;
+mod services;
+
+pub use services::{get_routes, spawn_room_sweeper};
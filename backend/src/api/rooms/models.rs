@@ -0,0 +1,97 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::api::sign::{SignResponse, SignTarget};
+
+/// Salle de signature en mémoire : les membres la rejoignent avant le cours et le premier scan signe tout le monde.
+#[derive(Debug, Clone)]
+pub struct SignRoom {
+    pub id: String,
+    pub name: String,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    /// Fin de la session : la salle est supprimée automatiquement passé cette date.
+    pub expires_at: DateTime<Utc>,
+    pub members: Vec<RoomMember>,
+    /// Événement intra en cours de signature (évite deux signatures simultanées).
+    pub signing_event: Option<String>,
+    pub scans: Vec<RoomScan>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RoomMember {
+    pub user_id: String,
+    pub username: String,
+    /// Le membre accepte d'être signé quand un autre membre scanne le QR code.
+    pub consent: bool,
+    pub joined_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RoomScan {
+    pub event: String,
+    pub scanned_by: String,
+    pub scanned_at: DateTime<Utc>,
+    pub results: Vec<RoomMemberResult>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RoomMemberResult {
+    pub user_id: String,
+    pub username: String,
+    pub response: SignResponse,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateRoomPayload {
+    #[schema(example = "B-INN-000 jeudi matin")]
+    pub name: String,
+    /// Fin de la session (UTC). Par défaut : dans 4 heures.
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct JoinRoomPayload {
+    /// Accepter d'être signé par les autres membres (par défaut : oui).
+    #[serde(default = "default_consent")]
+    pub consent: bool,
+}
+
+fn default_consent() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ScanRoomPayload {
+    #[serde(flatten)]
+    pub target: SignTarget,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RoomResponse {
+    pub id: String,
+    pub name: String,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub members: Vec<RoomMember>,
+    pub signing_event: Option<String>,
+    pub scans: Vec<RoomScan>,
+}
+
+impl From<SignRoom> for RoomResponse {
+    fn from(room: SignRoom) -> Self {
+        Self {
+            id: room.id,
+            name: room.name,
+            created_by: room.created_by,
+            created_at: room.created_at,
+            expires_at: room.expires_at,
+            members: room.members,
+            signing_event: room.signing_event,
+            scans: room.scans,
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use axum::{
+    Router,
+    routing::{delete, get, post},
+};
+use chrono::Utc;
+use tracing::info;
+use ulid::Ulid;
+
+use super::models::{RoomScan, SignRoom};
+use crate::misc::{GlobalState, ServerEvent};
+
+/// Fréquence de suppression des salles expirées.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+pub fn get_routes(state: GlobalState) -> Router {
+    Router::new()
+        .route("/", post(super::endpoints::create_room))
+        .route("/", get(super::endpoints::get_rooms))
+        .route("/{id}", get(super::endpoints::get_room))
+        .route("/{id}", delete(super::endpoints::close_room))
+        .route("/{id}/join", post(super::endpoints::join_room))
+        .route("/{id}/leave", post(super::endpoints::leave_room))
+        .route("/{id}/scan", post(super::endpoints::scan_room))
+        .with_state(state)
+}
+
+/// Salle active (non expirée) par id.
+pub async fn get_active_room(state: &GlobalState, room_id: &str) -> Option<SignRoom> {
+    let rooms = state.sign_rooms.read().await;
+    rooms
+        .get(room_id)
+        .filter(|room| room.expires_at > Utc::now())
+        .cloned()
+}
+
+/// Publie l'état des membres d'une salle.
+pub fn publish_room_updated(state: &GlobalState, room: &SignRoom) {
+    state.events.publish(ServerEvent::RoomUpdated {
+        room_id: room.id.clone(),
+        member_count: room.members.len(),
+        consenting_count: room.members.iter().filter(|m| m.consent).count(),
+    });
+}
+
+/// Issue de la réservation d'une salle pour un scan.
+#[derive(Debug)]
+pub enum ScanReservation {
+    /// Le scanneur n'est pas membre de la salle.
+    NotMember,
+    /// Tous les membres consentants sont déjà signés pour cet événement : on renvoie le dernier scan.
+    Done(RoomScan),
+    /// Un autre scan est en cours de signature.
+    Busy,
+    /// La salle est verrouillée pour ce scan jusqu'à `finish_room_signing`.
+    Reserved { scanned_by: String, member_ids: Vec<Ulid> },
+}
+
+/// Réserve une salle pour signer `event`. Un nouveau scan (ex: nouveau token après un échec) ne signe que les
+/// membres consentants qui ne sont pas encore signés pour cet événement.
+pub fn reserve_room_scan(room: &mut SignRoom, user_id: &str, event: &str) -> ScanReservation {
+    let Some(scanner) = room.members.iter().find(|m| m.user_id == user_id) else {
+        return ScanReservation::NotMember;
+    };
+    let event_scans: Vec<&RoomScan> = room.scans.iter().filter(|s| s.event == event).collect();
+    let signed: HashSet<&str> = event_scans
+        .iter()
+        .flat_map(|s| &s.results)
+        .filter(|r| r.response.is_signed())
+        .map(|r| r.user_id.as_str())
+        .collect();
+    let member_ids: Vec<Ulid> = room
+        .members
+        .iter()
+        .filter(|m| m.consent && !signed.contains(m.user_id.as_str()))
+        .filter_map(|m| Ulid::from_string(&m.user_id).ok())
+        .collect();
+    if member_ids.is_empty()
+        && let Some(scan) = event_scans.last()
+    {
+        return ScanReservation::Done((*scan).clone());
+    }
+    if room.signing_event.is_some() {
+        return ScanReservation::Busy;
+    }
+    let scanned_by = scanner.username.clone();
+    room.signing_event = Some(event.to_string());
+    ScanReservation::Reserved { scanned_by, member_ids }
+}
+
+/// Libère le verrou de signature d'une salle et enregistre le scan s'il a abouti.
+pub async fn finish_room_signing(state: &GlobalState, room_id: &str, scan: Option<RoomScan>) {
+    let mut rooms = state.sign_rooms.write().await;
+    if let Some(room) = rooms.get_mut(room_id) {
+        room.signing_event = None;
+        if let Some(scan) = scan {
+            room.scans.push(scan);
+        }
+    }
+}
+
+/// Supprime une salle et prévient les abonnés.
+pub async fn remove_room(state: &GlobalState, room_id: &str) -> Option<SignRoom> {
+    let removed = state.sign_rooms.write().await.remove(room_id);
+    if removed.is_some() {
+        state.events.publish(ServerEvent::RoomClosed {
+            room_id: room_id.to_string(),
+        });
+    }
+    removed
+}
+
+/// Lance la tâche qui ferme les salles arrivées en fin de session.
+pub fn spawn_room_sweeper(state: GlobalState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            let now = Utc::now();
+            let expired: Vec<String> = state
+                .sign_rooms
+                .read()
+                .await
+                .values()
+                .filter(|room| room.expires_at <= now)
+                .map(|room| room.id.clone())
+                .collect();
+            for room_id in expired {
+                if remove_room(&state, &room_id).await.is_some() {
+                    info!("Salle {} expirée et fermée", room_id);
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::rooms::models::{RoomMember, RoomMemberResult};
+    use crate::api::sign::SignResponse;
+
+    fn member(user_id: &str, consent: bool) -> RoomMember {
+        RoomMember {
+            user_id: user_id.to_string(),
+            username: user_id.to_lowercase(),
+            consent,
+            joined_at: Utc::now(),
+        }
+    }
+
+    fn room(members: Vec<RoomMember>) -> SignRoom {
+        SignRoom {
+            id: "room".to_string(),
+            name: "TD".to_string(),
+            created_by: members[0].user_id.clone(),
+            created_at: Utc::now(),
+            expires_at: Utc::now() + chrono::Duration::hours(4),
+            members,
+            signing_event: None,
+            scans: Vec::new(),
+        }
+    }
+
+    /// Scan terminé pour `event` avec une réponse par membre.
+    fn scan(event: &str, results: &[(&str, SignResponse)]) -> RoomScan {
+        RoomScan {
+            event: event.to_string(),
+            scanned_by: "scanner".to_string(),
+            scanned_at: Utc::now(),
+            results: results
+                .iter()
+                .map(|(user_id, response)| RoomMemberResult {
+                    user_id: user_id.to_string(),
+                    username: user_id.to_lowercase(),
+                    response: response.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    fn reserved_ids(reservation: ScanReservation) -> Vec<Ulid> {
+        match reservation {
+            ScanReservation::Reserved { member_ids, .. } => member_ids,
+            other => panic!("expected a reservation, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn a_locked_room_refuses_other_scans_until_released() {
+        let scanner = Ulid::new().to_string();
+        let other = Ulid::new().to_string();
+        let mut room = room(vec![member(&scanner, true), member(&other, false)]);
+
+        let ScanReservation::Reserved { member_ids, .. } = reserve_room_scan(&mut room, &scanner, "event-1") else {
+            panic!("the first scan should reserve the room");
+        };
+        assert_eq!(member_ids, vec![Ulid::from_string(&scanner).unwrap()]);
+        assert_eq!(room.signing_event.as_deref(), Some("event-1"));
+
+        assert!(matches!(reserve_room_scan(&mut room, &other, "event-1"), ScanReservation::Busy));
+        assert!(matches!(reserve_room_scan(&mut room, &other, "event-2"), ScanReservation::Busy));
+        assert!(matches!(reserve_room_scan(&mut room, "stranger", "event-2"), ScanReservation::NotMember));
+
+        room.signing_event = None;
+        assert!(matches!(
+            reserve_room_scan(&mut room, &other, "event-2"),
+            ScanReservation::Reserved { .. }
+        ));
+    }
+
+    #[test]
+    fn a_failed_scan_can_be_scanned_again() {
+        let (a, b) = (Ulid::new(), Ulid::new());
+        let mut room = room(vec![member(&a.to_string(), true), member(&b.to_string(), true)]);
+        room.scans.push(scan("event-1", &[(&a.to_string(), SignResponse::TokenExpired), (&b.to_string(), SignResponse::TokenExpired)]));
+
+        // Personne n'a été signé : un nouveau scan (nouveau token) signe tout le monde
+        assert_eq!(reserved_ids(reserve_room_scan(&mut room, &a.to_string(), "event-1")), vec![a, b]);
+        room.signing_event = None;
+
+        // Après un succès partiel, seul le membre restant est signé
+        room.scans.push(scan("event-1", &[(&a.to_string(), SignResponse::Success), (&b.to_string(), SignResponse::TokenExpired)]));
+        assert_eq!(reserved_ids(reserve_room_scan(&mut room, &b.to_string(), "event-1")), vec![b]);
+        room.signing_event = None;
+
+        room.scans.push(scan("event-1", &[(&b.to_string(), SignResponse::AlreadySigned)]));
+        let ScanReservation::Done(latest) = reserve_room_scan(&mut room, &a.to_string(), "event-1") else {
+            panic!("every member is signed");
+        };
+        assert_eq!(latest.results.len(), 1);
+    }
+
+    #[test]
+    fn a_member_consenting_after_the_scan_is_signed_by_the_next_one() {
+        let (a, b) = (Ulid::new(), Ulid::new());
+        let mut room = room(vec![member(&a.to_string(), true), member(&b.to_string(), false)]);
+        room.scans.push(scan("event-1", &[(&a.to_string(), SignResponse::Success)]));
+        assert!(matches!(reserve_room_scan(&mut room, &b.to_string(), "event-1"), ScanReservation::Done(_)));
+
+        room.members[1].consent = true;
+        assert_eq!(reserved_ids(reserve_room_scan(&mut room, &b.to_string(), "event-1")), vec![b]);
+    }
+}
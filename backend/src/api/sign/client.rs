@@ -0,0 +1,115 @@
+use crate::api::sign::url_parser::SignUrl;
+
+/// Adresse de l'intra Epitech utilisée si `INTRA_BASE_URL` n'est pas définie.
+pub const DEFAULT_INTRA_BASE_URL: &str = "https://intra.epitech.eu";
+
+/// Client HTTP de l'intra : adresse de base configurable (tests, proxy) et connexions réutilisées entre les signatures.
+#[derive(Clone)]
+pub struct IntraClient {
+    base_url: String,
+    base: reqwest::Url,
+    http: reqwest::Client,
+    /// Sans suivi automatique des redirections, pour les liens raccourcis
+    no_redirect: reqwest::Client,
+}
+
+impl IntraClient {
+    /// Échoue si `base_url` n'est pas une URL http(s) absolue (ex: `INTRA_BASE_URL` sans schéma).
+    pub fn new(base_url: &str) -> Result<Self, String> {
+        let base_url = base_url.trim().trim_end_matches('/');
+        let base = reqwest::Url::parse(base_url).map_err(|e| format!("Invalid intra base URL '{}': {}", base_url, e))?;
+        if !matches!(base.scheme(), "http" | "https") || base.host_str().is_none() {
+            return Err(format!("Invalid intra base URL '{}': expected http(s)://host", base_url));
+        }
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert(
+            reqwest::header::USER_AGENT,
+            reqwest::header::HeaderValue::from_static("Mozilla/5.0 (compatible; MyApp/1.0)"),
+        );
+        headers.insert(
+            reqwest::header::ACCEPT,
+            reqwest::header::HeaderValue::from_static("application/json"),
+        );
+
+        let http = reqwest::Client::builder()
+            .default_headers(headers.clone())
+            .build()
+            .map_err(|e| format!("Failed to build HTTP client: {}", e))?;
+        let no_redirect = reqwest::Client::builder()
+            .default_headers(headers)
+            .redirect(reqwest::redirect::Policy::none())
+            .timeout(std::time::Duration::from_secs(10))
+            .build()
+            .map_err(|e| format!("Failed to build HTTP client: {}", e))?;
+        Ok(Self {
+            base_url: base_url.to_string(),
+            base,
+            http,
+            no_redirect,
+        })
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    pub fn http(&self) -> &reqwest::Client {
+        &self.http
+    }
+
+    /// URL de l'endpoint qui enregistre le token de signature d'un événement.
+    pub fn token_url(&self, sign_url: &SignUrl) -> reqwest::Url {
+        sign_url.token_url(&self.base)
+    }
+
+    /// Suit une seule redirection et renvoie sa destination (lien raccourci vers le lien intra).
+    pub async fn follow_redirect(&self, url: &str) -> Result<String, String> {
+        let response = self.no_redirect.get(url).send().await.map_err(|e| e.to_string())?;
+        if !response.status().is_redirection() {
+            return Err(format!("expected a redirect, got {}", response.status()));
+        }
+        let location = response
+            .headers()
+            .get(reqwest::header::LOCATION)
+            .and_then(|value| value.to_str().ok())
+            .ok_or("redirect without Location header")?;
+        response
+            .url()
+            .join(location)
+            .map(|target| target.to_string())
+            .map_err(|e| format!("invalid redirect target '{}': {}", location, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_base_url_without_scheme() {
+        assert!(IntraClient::new("intra.epitech.eu").is_err());
+        assert!(IntraClient::new("localhost:8080").is_err());
+        assert_eq!(IntraClient::new(" https://intra.epitech.eu/ ").unwrap().base_url(), "https://intra.epitech.eu");
+    }
+
+    #[tokio::test]
+    async fn follows_a_single_redirect() {
+        use axum::{Router, http::{StatusCode, header}, routing::get};
+
+        let app = Router::new()
+            .route("/short", get(|| async { (StatusCode::MOVED_PERMANENTLY, [(header::LOCATION, "/long?token=12345678")]) }))
+            .route("/long", get(|| async { (StatusCode::FOUND, [(header::LOCATION, "/elsewhere")]) }))
+            .route("/plain", get(|| async { "not a redirect" }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let client = IntraClient::new(DEFAULT_INTRA_BASE_URL).unwrap();
+        assert_eq!(
+            client.follow_redirect(&format!("{}/short", base)).await.unwrap(),
+            format!("{}/long?token=12345678", base)
+        );
+        assert!(client.follow_redirect(&format!("{}/plain", base)).await.is_err());
+    }
+}
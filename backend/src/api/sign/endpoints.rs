@@ -1,21 +1,38 @@
-use axum::{Json, extract::State, response::IntoResponse};
+use std::collections::HashSet;
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    response::IntoResponse,
+};
 use http::StatusCode;
 use tracing::{error, info, warn};
-
-use std::collections::HashMap;
+use ulid::Ulid;
 
 use crate::{
     api::{
+        attendance::{report_results, submit_for_users},
         auth::JwtClaims,
         sign::{
-            models::{SignPayload, SignResponse, UserSignResponse},
-            services::{check_cookie_exists, get_cookies, sign_fn},
+            models::{CreateSignJobPayload, IntraUserResult, SignJobResponse, SignPayload, UserSignResponse},
+            provider::{IntraProvider, IntraSubmission},
+            services::{
+                cancel_sign_job as cancel_job, check_cookie_exists, create_sign_job as create_job,
+                get_cookies, get_sign_jobs_for_user,
+            },
         },
+        reservations::fulfill_reservations,
         users::{get_user_by_id, get_users_by_ulids},
     },
     misc::GlobalState,
 };
 
+/// ULIDs sans doublons, dans l'ordre reçu : un utilisateur listé deux fois n'est signé qu'une fois.
+fn unique_ulids(ulids: &[Ulid]) -> Vec<Ulid> {
+    let mut seen = HashSet::new();
+    ulids.iter().copied().filter(|ulid| seen.insert(*ulid)).collect()
+}
+
 #[utoipa::path(
     post,
     path = "/api/sign",
@@ -23,7 +40,7 @@ use crate::{
     request_body = SignPayload,
     responses(
         (status = 200, description = "Cookies signed successfully", body = Vec<UserSignResponse>),
-        (status = 400, description = "No users found for the provided ULIDs"),
+        (status = 400, description = "Invalid sign URL or no users found for the provided ULIDs"),
         (status = 401, description = "Unauthorized - Invalid or missing JWT token"),
         (status = 404, description = "No cookies found for today"),
     ),
@@ -32,8 +49,20 @@ use crate::{
 pub async fn sign(
     State(state): State<GlobalState>,
     jwt_user: JwtClaims,
-    Json(payload): Json<SignPayload>,
+    Json(mut payload): Json<SignPayload>,
 ) -> impl IntoResponse {
+    if let Err(err) = payload.target.expand_short_link(&state.intra).await {
+        warn!("Invalid sign URL '{}': {}", payload.target.describe(), err);
+        return (StatusCode::BAD_REQUEST, format!("Invalid sign URL: {}", err)).into_response();
+    }
+    let sign_url = match payload.target.resolve() {
+        Ok(sign_url) => sign_url,
+        Err(err) => {
+            warn!("Invalid sign URL '{}': {}", payload.target.describe(), err);
+            return (StatusCode::BAD_REQUEST, format!("Invalid sign URL: {}", err)).into_response();
+        }
+    };
+
     let cookies = match get_cookies(&state) {
         Ok(Some(cookies)) => cookies,
         Ok(None) => {
@@ -48,7 +77,8 @@ pub async fn sign(
         }
     };
 
-    let users = match get_users_by_ulids(&state, &payload.ulids) {
+    let ulids = unique_ulids(&payload.ulids);
+    let users = match get_users_by_ulids(&state, &ulids) {
         Ok(users) => users,
         Err(err) => {
             return (
@@ -59,10 +89,10 @@ pub async fn sign(
         }
     };
 
-    if users.len() != payload.ulids.len() {
+    if users.len() != ulids.len() {
         error!(
             "Mismatch in number of users found: expected {}, found {}",
-            payload.ulids.len(),
+            ulids.len(),
             users.len()
         );
         return (
@@ -72,44 +102,17 @@ pub async fn sign(
             .into_response();
     }
 
-    let username_by_id: HashMap<String, String> = users.iter().map(|u| (u.id.clone(), u.username.clone())).collect();
-    let signing_result = sign_fn(cookies, users, &payload.url).await;
-    println!("Signing result: {:?}", signing_result);
-
-    match signing_result {
-        Ok(res) => {
-            let validated: Vec<String> = res
-                .iter()
-                .filter(|r| r.response == SignResponse::Success)
-                .filter_map(|r| username_by_id.get(&r.ulid).cloned())
-                .collect();
-            let failed: Vec<(String, String)> = res
-                .iter()
-                .filter(|r| r.response != SignResponse::Success)
-                .filter_map(|r| username_by_id.get(&r.ulid).map(|u| (u.clone(), sign_response_to_message(&r.response))))
-                .collect();
-            let initiator_username = get_user_by_id(&state, &jwt_user.sub)
-                .ok()
-                .flatten()
-                .map(|u| u.username)
-                .unwrap_or_else(|| jwt_user.sub.to_string());
-
-            if let Some(ref webhook_url) = state.sign_webhook_url {
-                if !validated.is_empty() {
-                    let url = webhook_url.clone();
-                    let validated_clone = validated.clone();
-                    let failed_clone = failed.clone();
-                    let payload_url = payload.url.clone();
-                    let initiator = initiator_username.clone();
-                    tokio::spawn(async move {
-                        send_sign_webhook_bilan(&url, &validated_clone, &failed_clone, &payload_url, &initiator).await;
-                    });
-                }
-            }
-            (StatusCode::OK, Json(res)).into_response()
-        }
-        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR).into_response(),
-    }
+    let submission = IntraSubmission { sign_url, cookies };
+    let results = submit_for_users(&state, &IntraProvider, users, &submission).await;
+
+    let initiator_username = get_user_by_id(&state, &jwt_user.sub)
+        .ok()
+        .flatten()
+        .map(|u| u.username)
+        .unwrap_or_else(|| jwt_user.sub.to_string());
+    handle_sign_results(&state, &submission, &results, &initiator_username);
+    let res: Vec<UserSignResponse> = results.iter().map(UserSignResponse::from).collect();
+    (StatusCode::OK, Json(res)).into_response()
 }
 
 #[utoipa::path(
@@ -125,7 +128,7 @@ pub async fn sign(
 )]
 pub async fn status(State(state): State<GlobalState>) -> impl IntoResponse {
     info!("Checking status...");
-    let today = chrono::Local::now().date_naive();
+    let today = state.today();
     match check_cookie_exists(&state, today) {
         Ok(true) => (StatusCode::OK, "Cookies exist for today".to_string()).into_response(),
         Ok(false) => (
@@ -141,75 +144,150 @@ pub async fn status(State(state): State<GlobalState>) -> impl IntoResponse {
     }
 }
 
-fn sign_response_to_message(r: &SignResponse) -> String {
-    match r {
-        SignResponse::Success => "Succès".to_string(),
-        SignResponse::TokenExpired => "Token expiré".to_string(),
-        SignResponse::TokenNotFound => "Token non trouvé".to_string(),
-        SignResponse::AlreadySigned => "Déjà signé".to_string(),
-        SignResponse::UnknownError => "Erreur inconnue".to_string(),
-        SignResponse::ServiceUnavailable => "Service indisponible".to_string(),
+#[utoipa::path(
+    post,
+    path = "/api/sign/jobs",
+    description = "Schedule a sign for the provided ULIDs at `not_before`. Temporary failures are retried until the optional `deadline`.",
+    request_body = CreateSignJobPayload,
+    responses(
+        (status = 201, description = "Sign job scheduled", body = SignJobResponse),
+        (status = 400, description = "Invalid sign URL, schedule or user list"),
+        (status = 401, description = "Unauthorized"),
+    ),
+    tag = "Sign"
+)]
+pub async fn create_sign_job(
+    State(state): State<GlobalState>,
+    jwt_user: JwtClaims,
+    Json(mut payload): Json<CreateSignJobPayload>,
+) -> impl IntoResponse {
+    // Le lien raccourci est suivi maintenant : le job enregistre le lien intra
+    if let Err(err) = payload.target.expand_short_link(&state.intra).await {
+        return (StatusCode::BAD_REQUEST, format!("Invalid sign URL: {}", err)).into_response();
+    }
+    if let Err(err) = payload.target.resolve() {
+        return (StatusCode::BAD_REQUEST, format!("Invalid sign URL: {}", err)).into_response();
+    }
+    let ulids = unique_ulids(&payload.ulids);
+    if ulids.is_empty() {
+        return (StatusCode::BAD_REQUEST, "ulids must not be empty").into_response();
+    }
+    if let Some(deadline) = payload.deadline
+        && deadline <= payload.not_before
+    {
+        return (StatusCode::BAD_REQUEST, "deadline must be after not_before").into_response();
     }
-}
 
-/// Envoie un webhook bilan après signature multiple.
-/// Discord : payload { "content": "message lisible" } avec lancé par, URL, validés, échecs.
-/// Autres URLs : payload JSON structuré { "event", "initiated_by", "url", "validated", "failed" }.
-async fn send_sign_webhook_bilan(
-    webhook_url: &str,
-    validated: &[String],
-    failed: &[(String, String)],
-    url: &str,
-    initiated_by: &str,
-) {
-    let is_discord = webhook_url.to_lowercase().contains("discord.com");
-    let body: serde_json::Value = if is_discord {
-        let mut parts: Vec<String> = Vec::new();
-        parts.push("**Bilan signature**".to_string());
-        parts.push(format!("**Lancé par :** {}", initiated_by));
-        parts.push(format!("**URL :** {}", url));
-        if !validated.is_empty() {
-            parts.push(format!("✅ **Validés :** {}.", validated.join(", ")));
+    match get_users_by_ulids(&state, &ulids) {
+        Ok(users) if users.len() == ulids.len() => {}
+        Ok(_) => {
+            return (StatusCode::BAD_REQUEST, "No users found for the provided ULIDs").into_response();
         }
-        if !failed.is_empty() {
-            let failed_list: Vec<String> = failed
-                .iter()
-                .map(|(u, m)| format!("{} ({})", u, m))
-                .collect();
-            parts.push(format!("❌ **Échecs :** {}.", failed_list.join(" ; ")));
+        Err(err) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Error fetching users: {}", err),
+            )
+                .into_response();
         }
-        let content = parts.join("\n");
-        let content = if content.len() > 2000 {
-            format!("{}…", &content[..1997])
-        } else {
-            content
-        };
-        serde_json::json!({ "content": content })
-    } else {
-        serde_json::json!({
-            "event": "sign_multi",
-            "initiated_by": initiated_by,
-            "url": url,
-            "validated": validated,
-            "failed": failed.iter().map(|(u, m)| serde_json::json!({ "username": u, "message": m })).collect::<Vec<_>>(),
-        })
-    };
-    let client = match reqwest::Client::builder().build() {
-        Ok(c) => c,
-        Err(e) => {
-            error!("Webhook Sign: impossible de créer le client HTTP: {}", e);
-            return;
+    }
+
+    let user_ids: Vec<String> = ulids.iter().map(|u| u.to_string()).collect();
+    match create_job(
+        &state,
+        &jwt_user.sub.to_string(),
+        &user_ids,
+        &payload.target,
+        payload.not_before.naive_utc(),
+        payload.deadline.map(|d| d.naive_utc()),
+    ) {
+        Ok(job) => {
+            info!("Sign job {} programmé pour {} ({} utilisateur(s))", job.id, job.not_before, user_ids.len());
+            (StatusCode::CREATED, Json(SignJobResponse::from(job))).into_response()
         }
-    };
-    match client.post(webhook_url).json(&body).send().await {
-        Ok(res) if !res.status().is_success() => {
-            warn!("Webhook Sign: statut {} pour {}", res.status(), webhook_url);
+        Err(err) => {
+            error!("Error creating sign job: {}", err);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Error creating sign job").into_response()
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/sign/jobs",
+    description = "List the scheduled signs created by or targeting the current user",
+    responses(
+        (status = 200, description = "Sign jobs", body = Vec<SignJobResponse>),
+        (status = 401, description = "Unauthorized"),
+    ),
+    tag = "Sign"
+)]
+pub async fn get_sign_jobs(State(state): State<GlobalState>, jwt_user: JwtClaims) -> impl IntoResponse {
+    match get_sign_jobs_for_user(&state, &jwt_user.sub.to_string()) {
+        Ok(jobs) => {
+            let jobs: Vec<SignJobResponse> = jobs.into_iter().map(SignJobResponse::from).collect();
+            (StatusCode::OK, Json(jobs)).into_response()
         }
-        Err(e) => {
-            warn!("Webhook Sign: erreur envoi vers {}: {}", webhook_url, e);
+        Err(err) => {
+            error!("Error fetching sign jobs: {}", err);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Error fetching sign jobs").into_response()
+        }
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/sign/jobs/{id}",
+    description = "Cancel a pending scheduled sign (creator), or remove yourself from its targeted users",
+    params(("id" = String, Path, description = "Sign job id")),
+    responses(
+        (status = 204, description = "Sign job cancelled"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "No pending sign job with this id"),
+    ),
+    tag = "Sign"
+)]
+pub async fn cancel_sign_job(
+    State(state): State<GlobalState>,
+    jwt_user: JwtClaims,
+    Path(job_id): Path<String>,
+) -> impl IntoResponse {
+    match cancel_job(&state, &job_id, &jwt_user.sub.to_string()) {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => (StatusCode::NOT_FOUND, "No pending sign job with this id").into_response(),
+        Err(err) => {
+            error!("Error cancelling sign job {}: {}", job_id, err);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Error cancelling sign job").into_response()
         }
-        _ => {
-            info!("Webhook Sign: bilan envoyé (validés: {}, échecs: {})", validated.len(), failed.len());
+    }
+}
+
+/// Suites d'une signature (immédiate ou programmée) : réservations honorées, bilan SSE et webhook.
+pub(crate) fn handle_sign_results(
+    state: &GlobalState,
+    submission: &IntraSubmission,
+    results: &[IntraUserResult],
+    initiator: &str,
+) {
+    fulfill_signed_reservations(state, &submission.sign_url.event, results);
+    report_results(state, &IntraProvider, initiator, submission, results);
+}
+
+/// Honore les réservations des utilisateurs signés pour `event`.
+pub(crate) fn fulfill_signed_reservations(state: &GlobalState, event: &str, results: &[IntraUserResult]) {
+    let signed_ids: Vec<String> = results
+        .iter()
+        .filter(|r| r.detail.response.is_signed())
+        .map(|r| r.user_id.clone())
+        .collect();
+    match fulfill_reservations(state, &signed_ids, state.today(), event) {
+        Ok(fulfilled) if !fulfilled.is_empty() => {
+            info!("{} réservation(s) honorée(s) pour {}", fulfilled.len(), event);
+            for reservation in &fulfilled {
+                state.events.publish(reservation.to_event());
+            }
         }
+        Ok(_) => {}
+        Err(e) => error!("Error fulfilling reservations: {}", e),
     }
 }
@@ -1,6 +1,13 @@
+pub mod client;
 pub mod endpoints;
 mod models;
-mod services;
+mod provider;
+mod scheduler;
+pub mod services;
+pub mod url_parser;
 
-pub use models::CookieItem;
-pub use services::get_routes;
+pub use client::IntraClient;
+pub use models::{CookieItem, SignResponse, SignTarget};
+pub use provider::{IntraProvider, IntraSubmission};
+pub use scheduler::spawn_sign_scheduler;
+pub use services::{check_cookie_exists, get_cookies, get_routes};
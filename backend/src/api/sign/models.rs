@@ -1,11 +1,15 @@
-use chrono::NaiveDate;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
 use diesel::{Selectable, pg::Pg, prelude::Queryable};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use ulid::Ulid;
+use tracing::warn;
 use utoipa::ToSchema;
 
-use crate::schema::cookies;
+use crate::api::attendance::UserAttendanceResult;
+use crate::api::sign::client::IntraClient;
+use crate::api::sign::url_parser::{SignUrl, SignUrlError, resolve_sign_url, short_link};
+use crate::schema::{cookies, sign_jobs};
 
 #[allow(dead_code)]
 #[derive(Debug, Queryable, Selectable)]
@@ -31,9 +35,10 @@ pub struct CookieItem {
     pub same_site: Option<String>,
 }
 
-impl CookieItem {
-    pub fn to_string(&self) -> String {
-        format!(
+impl std::fmt::Display for CookieItem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
             "{}={}; Domain={}; Path={}; Expires={}; HttpOnly={}; Secure={}; SameSite={:?}",
             self.name,
             self.value,
@@ -45,7 +50,9 @@ impl CookieItem {
             self.same_site
         )
     }
+}
 
+impl CookieItem {
     pub fn to_header_value(&self) -> String {
         format!("{}={}", self.name, self.value)
     }
@@ -55,25 +62,254 @@ impl CookieItem {
 pub struct SignPayload {
     #[schema(value_type = Vec<String>, example = "[\"01F8MECHZX3TBDSZ7X4F5G9Z6H\", \"01F8MECHZX3TBDSZ7X4F5G9Z6I\"]")]
     pub ulids: Vec<Ulid>,
-    #[schema(value_type = String, example = "https://intra.epitech.eu/module/XXXX/X-XXX-000/XXX-0-0/acti-000000/event-000000/registered?token=00000000")]
-    pub url: String,
+    #[serde(flatten)]
+    pub target: SignTarget,
 }
 
-#[derive(Debug, Serialize, ToSchema)]
+/// Lien à signer : le lien scanné (`url`), ou l'événement sélectionné (`event_url`) et le token (`token`).
+#[derive(Debug, Clone, Default, Deserialize, ToSchema)]
+pub struct SignTarget {
+    /// Lien scanné (QR code, lien mobile, lien encodé...). Les liens raccourcis connus (bit.ly...) sont suivis une fois.
+    #[serde(default)]
+    #[schema(example = "https://intra.epitech.eu/module/XXXX/X-XXX-000/XXX-0-0/acti-000000/event-000000/registered?token=00000000")]
+    pub url: Option<String>,
+    /// Événement sélectionné, avec `token` quand le lien scanné n'est pas disponible
+    #[serde(default)]
+    #[schema(example = "https://intra.epitech.eu/module/XXXX/X-XXX-000/XXX-0-0/acti-000000/event-000000")]
+    pub event_url: Option<String>,
+    /// Token de signature seul, avec `event_url` (prioritaire sur le token du lien)
+    #[serde(default)]
+    #[schema(example = "00000000")]
+    pub token: Option<String>,
+}
+
+impl SignTarget {
+    /// Remplace un lien raccourci connu (`SHORT_LINK_HOSTS`) par sa destination, en suivant une seule redirection.
+    /// Les autres liens ne sont pas touchés.
+    pub async fn expand_short_link(&mut self, intra: &IntraClient) -> Result<(), SignUrlError> {
+        let Some(url) = self.url.as_deref().and_then(short_link) else {
+            return Ok(());
+        };
+        let expanded = intra.follow_redirect(url.as_str()).await.map_err(SignUrlError::ShortLink)?;
+        self.url = Some(expanded);
+        Ok(())
+    }
+
+    pub fn resolve(&self) -> Result<SignUrl, SignUrlError> {
+        resolve_sign_url(self.url.as_deref(), self.event_url.as_deref(), self.token.as_deref())
+    }
+
+    /// Description pour les logs.
+    pub fn describe(&self) -> String {
+        [("url", &self.url), ("event_url", &self.event_url), ("token", &self.token)]
+            .iter()
+            .filter_map(|(name, value)| value.as_ref().map(|v| format!("{}={}", name, v)))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
 pub struct UserSignResponse {
     #[schema(value_type = String, example = "01F8MECHZX3TBDSZ7X4F5G9Z6H")]
     pub ulid: String,
     pub response: SignResponse,
 }
 
-#[derive(Debug, Serialize, ToSchema, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema, PartialEq, Default)]
 #[serde(rename_all = "camelCase")]
 pub enum SignResponse {
     Success,
     TokenExpired,
     TokenNotFound,
     AlreadySigned,
+    #[default]
     UnknownError,
     ServiceUnavailable,
     // BadToken,
 }
+
+/// Détail intra du résultat d'un utilisateur (voir `UserAttendanceResult`).
+#[derive(Debug, Serialize, Clone, Default, ToSchema)]
+pub struct IntraSignDetail {
+    pub response: SignResponse,
+}
+
+/// Résultat de la signature intra d'un utilisateur.
+pub type IntraUserResult = UserAttendanceResult<IntraSignDetail>;
+
+impl From<&IntraUserResult> for UserSignResponse {
+    fn from(result: &IntraUserResult) -> Self {
+        UserSignResponse {
+            ulid: result.user_id.clone(),
+            response: result.detail.response.clone(),
+        }
+    }
+}
+
+/// Entrée du planning intra (`/planning/load?format=json`) ; seuls les champs utiles sont lus.
+#[derive(Debug, Deserialize)]
+pub struct IntraPlanningEntry {
+    pub scolaryear: Option<String>,
+    pub codemodule: Option<String>,
+    pub codeinstance: Option<String>,
+    pub codeacti: Option<String>,
+    pub codeevent: Option<String>,
+    pub acti_title: Option<String>,
+    /// Début, heure locale de l'école (`2026-10-19 09:00:00`)
+    pub start: Option<String>,
+    pub end: Option<String>,
+    /// `"registered"` ou `"present"` si l'utilisateur est inscrit, `false` sinon
+    #[serde(default)]
+    pub event_registered: Value,
+}
+
+impl SignResponse {
+    /// Échec potentiellement temporaire (hors fenêtre de l'événement, intra indisponible) : on peut réessayer.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            SignResponse::TokenExpired | SignResponse::ServiceUnavailable | SignResponse::UnknownError
+        )
+    }
+
+    /// La présence est enregistrée sur l'intra (nouvelle signature ou déjà signé).
+    pub fn is_signed(&self) -> bool {
+        matches!(self, SignResponse::Success | SignResponse::AlreadySigned)
+    }
+
+    pub fn to_message(&self) -> String {
+        match self {
+            SignResponse::Success => "Succès".to_string(),
+            SignResponse::TokenExpired => "Token expiré".to_string(),
+            SignResponse::TokenNotFound => "Token non trouvé".to_string(),
+            SignResponse::AlreadySigned => "Déjà signé".to_string(),
+            SignResponse::UnknownError => "Erreur inconnue".to_string(),
+            SignResponse::ServiceUnavailable => "Service indisponible".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Queryable, Selectable)]
+#[diesel(table_name = sign_jobs)]
+#[diesel(check_for_backend(Pg))]
+pub struct SignJob {
+    pub id: String,
+    pub created_by: String,
+    pub user_ids: Value,
+    pub url: Option<String>,
+    pub event_url: Option<String>,
+    pub not_before: NaiveDateTime,
+    pub deadline: Option<NaiveDateTime>,
+    pub status: String,
+    pub attempts: i32,
+    pub results: Option<Value>,
+    pub error: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub executed_at: Option<NaiveDateTime>,
+    pub token: Option<String>,
+}
+
+impl SignJob {
+    pub fn target(&self) -> SignTarget {
+        SignTarget {
+            url: self.url.clone(),
+            event_url: self.event_url.clone(),
+            token: self.token.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, ToSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum SignJobStatus {
+    Pending,
+    Running,
+    Done,
+    Failed,
+    Expired,
+    Cancelled,
+}
+
+impl SignJobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SignJobStatus::Pending => "pending",
+            SignJobStatus::Running => "running",
+            SignJobStatus::Done => "done",
+            SignJobStatus::Failed => "failed",
+            SignJobStatus::Expired => "expired",
+            SignJobStatus::Cancelled => "cancelled",
+        }
+    }
+
+    /// Statut enregistré. Une valeur inconnue (ligne corrompue) est affichée en échec, jamais comme `pending`.
+    pub fn from_db(value: &str) -> Self {
+        match value {
+            "pending" => SignJobStatus::Pending,
+            "running" => SignJobStatus::Running,
+            "done" => SignJobStatus::Done,
+            "failed" => SignJobStatus::Failed,
+            "expired" => SignJobStatus::Expired,
+            "cancelled" => SignJobStatus::Cancelled,
+            other => {
+                warn!("Statut de sign job inconnu '{}', considéré en échec", other);
+                SignJobStatus::Failed
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateSignJobPayload {
+    #[schema(value_type = Vec<String>, example = "[\"01F8MECHZX3TBDSZ7X4F5G9Z6H\"]")]
+    pub ulids: Vec<Ulid>,
+    #[serde(flatten)]
+    pub target: SignTarget,
+    /// Heure à partir de laquelle la signature est envoyée (RFC 3339, ex: 2026-01-30T09:05:00+01:00).
+    #[schema(value_type = String, example = "2026-01-30T09:05:00+01:00")]
+    pub not_before: DateTime<Utc>,
+    /// Heure limite optionnelle : les échecs temporaires sont réessayés jusqu'à cette heure.
+    #[serde(default)]
+    #[schema(value_type = Option<String>, example = "2026-01-30T09:30:00+01:00")]
+    pub deadline: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SignJobResponse {
+    pub id: String,
+    pub created_by: String,
+    pub user_ids: Vec<String>,
+    pub url: Option<String>,
+    pub event_url: Option<String>,
+    pub token: Option<String>,
+    pub not_before: NaiveDateTime,
+    pub deadline: Option<NaiveDateTime>,
+    pub status: SignJobStatus,
+    pub attempts: i32,
+    pub results: Option<Vec<UserSignResponse>>,
+    pub error: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub executed_at: Option<NaiveDateTime>,
+}
+
+impl From<SignJob> for SignJobResponse {
+    fn from(job: SignJob) -> Self {
+        Self {
+            id: job.id,
+            created_by: job.created_by,
+            user_ids: serde_json::from_value(job.user_ids).unwrap_or_default(),
+            url: job.url,
+            event_url: job.event_url,
+            token: job.token,
+            not_before: job.not_before,
+            deadline: job.deadline,
+            status: SignJobStatus::from_db(&job.status),
+            attempts: job.attempts,
+            results: job.results.and_then(|r| serde_json::from_value(r).ok()),
+            error: job.error,
+            created_at: job.created_at,
+            executed_at: job.executed_at,
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use std::time::Duration;
+
+use chrono::NaiveDate;
+
+use crate::api::attendance::{AttendanceEvent, AttendanceProvider, Readiness, UserAttendanceResult, WebhookBilan};
+use crate::api::sign::{
+    models::{CookieItem, IntraSignDetail, IntraUserResult, SignResponse},
+    services::{check_cookie_exists, fetch_intra_events, sign_user},
+    url_parser::SignUrl,
+};
+use crate::api::users::User;
+use crate::misc::{GlobalState, ServerEvent, UserResultEvent};
+
+/// Délai maximal d'une signature intra pour un utilisateur.
+const INTRA_USER_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Signature de présence sur l'intra Epitech (lien de signature + cookies du jour + JWT de l'utilisateur).
+pub struct IntraProvider;
+
+/// Signature intra à soumettre pour chaque utilisateur.
+pub struct IntraSubmission {
+    pub sign_url: SignUrl,
+    /// Cookies intra du jour
+    pub cookies: Vec<CookieItem>,
+}
+
+impl AttendanceProvider for IntraProvider {
+    type Submission = IntraSubmission;
+    type Detail = IntraSignDetail;
+
+    fn name(&self) -> &'static str {
+        "intra"
+    }
+
+    /// Les utilisateurs sont signés un par un, comme avant l'introduction du trait.
+    fn limits(&self, _state: &GlobalState) -> (usize, Duration) {
+        (1, INTRA_USER_TIMEOUT)
+    }
+
+    fn readiness(&self, state: &GlobalState, user: &User) -> Readiness {
+        if user.get_jwt_as_cookie().is_none() {
+            return Readiness::not_ready("Token intra manquant");
+        }
+        if user.jwt_expires_at.is_some_and(|expires_at| expires_at <= chrono::Utc::now().naive_utc()) {
+            return Readiness::not_ready("Token intra expiré");
+        }
+        match check_cookie_exists(state, state.today()) {
+            Ok(true) => Readiness::ready(),
+            Ok(false) => Readiness::not_ready("Aucun cookie intra pour aujourd'hui"),
+            Err(e) => Readiness::not_ready(e),
+        }
+    }
+
+    async fn submit_for_user(&self, state: &GlobalState, user: &User, submission: &IntraSubmission) -> IntraUserResult {
+        let response = sign_user(&state.intra, &submission.cookies, user, &submission.sign_url).await;
+        UserAttendanceResult {
+            user_id: user.id.clone(),
+            username: user.username.clone(),
+            success: response.is_signed(),
+            message: response.to_message(),
+            detail: IntraSignDetail { response },
+        }
+    }
+
+    async fn fetch_events(&self, state: &GlobalState, user: &User, date: NaiveDate) -> Result<Vec<AttendanceEvent>, String> {
+        fetch_intra_events(&state.intra, user, date, state.school_timezone).await
+    }
+
+    fn results_event(
+        &self,
+        initiated_by: &str,
+        submission: &IntraSubmission,
+        _results: &[IntraUserResult],
+        user_results: Vec<UserResultEvent>,
+    ) -> ServerEvent {
+        ServerEvent::SignResults {
+            initiated_by: initiated_by.to_string(),
+            event: submission.sign_url.event.clone(),
+            results: user_results,
+        }
+    }
+
+    fn webhook_bilan(
+        &self,
+        state: &GlobalState,
+        initiated_by: &str,
+        submission: &IntraSubmission,
+        results: &[IntraUserResult],
+    ) -> Option<(String, WebhookBilan)> {
+        let webhook_url = state.sign_webhook_url.clone()?;
+        let (validated, failed): (Vec<&IntraUserResult>, Vec<&IntraUserResult>) =
+            results.iter().partition(|r| r.detail.response == SignResponse::Success);
+        if validated.is_empty() {
+            return None;
+        }
+
+        let event_url = format!("{}{}", state.intra.base_url(), submission.sign_url.event_path());
+        let mut extra = serde_json::Map::new();
+        extra.insert("url".to_string(), serde_json::json!(event_url));
+        Some((
+            webhook_url,
+            WebhookBilan {
+                title: "Bilan signature".to_string(),
+                event: "sign_multi".to_string(),
+                initiated_by: initiated_by.to_string(),
+                context: vec![("URL".to_string(), event_url)],
+                validated: validated.iter().map(|r| r.username.clone()).collect(),
+                failed: failed.iter().map(|r| (r.username.clone(), r.message.clone())).collect(),
+                extra,
+            },
+        ))
+    }
+}
@@ -0,0 +1,309 @@
+use std::time::Duration;
+
+use chrono::{NaiveDateTime, Utc};
+use tracing::{error, info, warn};
+use ulid::Ulid;
+
+use crate::{
+    api::{
+        attendance::{UserAttendanceResult, submit_for_users},
+        sign::{
+            endpoints::handle_sign_results,
+            models::{IntraSignDetail, IntraUserResult, SignJob, SignJobStatus, UserSignResponse},
+            provider::{IntraProvider, IntraSubmission},
+            services::{claim_due_sign_jobs, get_cookies, requeue_interrupted_sign_jobs, update_sign_job},
+        },
+        users::{get_user_by_id, get_users_by_ulids},
+    },
+    misc::GlobalState,
+};
+
+/// Fréquence à laquelle on cherche les jobs arrivés à échéance.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// Délai avant de réessayer les utilisateurs en échec temporaire (si une deadline est fixée).
+const RETRY_DELAY_SECS: i64 = 30;
+
+/// Lance la boucle qui exécute les signatures programmées (`sign_jobs`) à leur heure.
+pub fn spawn_sign_scheduler(state: GlobalState) {
+    tokio::spawn(async move {
+        match requeue_interrupted_sign_jobs(&state) {
+            Ok(0) => {}
+            Ok(n) => info!("Sign scheduler: {} job(s) interrompu(s) remis en attente", n),
+            Err(e) => error!("Sign scheduler: impossible de remettre les jobs en attente: {}", e),
+        }
+
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            let jobs = match claim_due_sign_jobs(&state, Utc::now().naive_utc()) {
+                Ok(jobs) => jobs,
+                Err(e) => {
+                    error!("Sign scheduler: {}", e);
+                    continue;
+                }
+            };
+            for job in jobs {
+                let state = state.clone();
+                tokio::spawn(async move { run_sign_job(&state, job).await });
+            }
+        }
+    });
+}
+
+async fn run_sign_job(state: &GlobalState, job: SignJob) {
+    let now = Utc::now().naive_utc();
+    let previous: Vec<UserSignResponse> = job
+        .results
+        .clone()
+        .and_then(|r| serde_json::from_value(r).ok())
+        .unwrap_or_default();
+
+    if let Some(deadline) = job.deadline
+        && deadline < now
+    {
+        warn!("Sign job {}: deadline dépassée avant exécution", job.id);
+        finish(state, &job, SignJobStatus::Expired, &previous, &[], Some("Deadline passed before execution"), None);
+        return;
+    }
+
+    let sign_url = match job.target().resolve() {
+        Ok(sign_url) => sign_url,
+        Err(e) => {
+            finish(state, &job, SignJobStatus::Failed, &previous, &[], Some(&format!("Invalid sign URL: {}", e)), None);
+            return;
+        }
+    };
+
+    let retry_at = retry_at(job.deadline, now);
+
+    let cookies = match get_cookies(state) {
+        Ok(Some(cookies)) => cookies,
+        Ok(None) if retry_at.is_some() => {
+            finish(state, &job, SignJobStatus::Pending, &previous, &[], Some("No cookies found for today"), retry_at);
+            return;
+        }
+        Ok(None) => {
+            finish(state, &job, SignJobStatus::Failed, &previous, &[], Some("No cookies found for today"), None);
+            return;
+        }
+        Err(e) => {
+            finish(state, &job, SignJobStatus::Failed, &previous, &[], Some(&e), None);
+            return;
+        }
+    };
+
+    let pending_ids = pending_user_ids(&job.user_ids, &previous);
+    let users = match get_users_by_ulids(state, &pending_ids) {
+        Ok(users) => users,
+        Err(e) => {
+            finish(state, &job, SignJobStatus::Failed, &previous, &[], Some(&format!("Error fetching users: {}", e)), None);
+            return;
+        }
+    };
+
+    info!("Sign job {}: signature de {} utilisateur(s) (tentative {})", job.id, users.len(), job.attempts + 1);
+    let submission = IntraSubmission { sign_url, cookies };
+    let results = submit_for_users(state, &IntraProvider, users, &submission).await;
+
+    let merged = merge_results(previous, results.iter().map(UserSignResponse::from));
+
+    let (status, next_not_before) = after_attempt(&merged, retry_at);
+    if let Some(next) = next_not_before {
+        info!("Sign job {}: échecs temporaires, nouvel essai à {}", job.id, next);
+    }
+    finish(state, &job, status, &merged, &results, None, next_not_before);
+}
+
+/// Prochain essai possible : dans `RETRY_DELAY_SECS`, seulement s'il tombe avant la deadline (sans deadline, pas
+/// de nouvel essai).
+fn retry_at(deadline: Option<NaiveDateTime>, now: NaiveDateTime) -> Option<NaiveDateTime> {
+    let retry_at = now + chrono::Duration::seconds(RETRY_DELAY_SECS);
+    deadline.filter(|deadline| retry_at < *deadline).map(|_| retry_at)
+}
+
+/// Utilisateurs à (re)signer : ceux sans résultat ou dont le dernier résultat est un échec temporaire.
+/// Un résultat définitif (succès, déjà signé, token introuvable) n'est jamais renvoyé.
+fn pending_user_ids(user_ids: &serde_json::Value, previous: &[UserSignResponse]) -> Vec<Ulid> {
+    let all_ids: Vec<String> = serde_json::from_value(user_ids.clone()).unwrap_or_default();
+    all_ids
+        .iter()
+        .filter(|uid| !previous.iter().any(|r| &r.ulid == *uid && !r.response.is_retryable()))
+        .filter_map(|uid| Ulid::from_string(uid).ok())
+        .collect()
+}
+
+/// Résultats cumulés : le nouveau résultat d'un utilisateur remplace le précédent.
+fn merge_results(
+    mut merged: Vec<UserSignResponse>,
+    results: impl IntoIterator<Item = UserSignResponse>,
+) -> Vec<UserSignResponse> {
+    for result in results {
+        match merged.iter_mut().find(|r| r.ulid == result.ulid) {
+            Some(existing) => *existing = result,
+            None => merged.push(result),
+        }
+    }
+    merged
+}
+
+/// État du job après une tentative : remis en attente jusqu'à `retry_at` s'il reste des échecs temporaires et
+/// qu'un essai est encore possible, sinon terminé.
+fn after_attempt(
+    merged: &[UserSignResponse],
+    retry_at: Option<NaiveDateTime>,
+) -> (SignJobStatus, Option<NaiveDateTime>) {
+    match retry_at {
+        Some(retry_at) if merged.iter().any(|r| r.response.is_retryable()) => (SignJobStatus::Pending, Some(retry_at)),
+        _ => (SignJobStatus::Done, None),
+    }
+}
+
+/// Enregistre l'état du job. Une fois le job terminé, ses suites (réservations, bilan SSE et webhook) partent une
+/// seule fois avec les résultats cumulés ; `latest` (dernière tentative) fournit les messages détaillés.
+fn finish(
+    state: &GlobalState,
+    job: &SignJob,
+    status: SignJobStatus,
+    results: &[UserSignResponse],
+    latest: &[IntraUserResult],
+    error_message: Option<&str>,
+    next_not_before: Option<chrono::NaiveDateTime>,
+) {
+    let stored = (!results.is_empty()).then_some(results);
+    if let Err(e) = update_sign_job(state, &job.id, status, stored, error_message, next_not_before) {
+        error!("Sign job {}: impossible d'enregistrer le résultat: {}", job.id, e);
+    }
+    if status != SignJobStatus::Pending && !results.is_empty() {
+        report_final_results(state, job, results, latest);
+    }
+}
+
+fn report_final_results(state: &GlobalState, job: &SignJob, results: &[UserSignResponse], latest: &[IntraUserResult]) {
+    let sign_url = match job.target().resolve() {
+        Ok(sign_url) => sign_url,
+        Err(e) => {
+            warn!("Sign job {}: bilan impossible, lien invalide: {}", job.id, e);
+            return;
+        }
+    };
+    // Le bilan ne soumet rien : les cookies ne servent pas
+    let submission = IntraSubmission { sign_url, cookies: Vec::new() };
+
+    let earlier_ids: Vec<Ulid> = results
+        .iter()
+        .filter(|r| !latest.iter().any(|l| l.user_id == r.ulid))
+        .filter_map(|r| Ulid::from_string(&r.ulid).ok())
+        .collect();
+    let usernames: Vec<(String, String)> = match get_users_by_ulids(state, &earlier_ids) {
+        Ok(users) => users.into_iter().map(|u| (u.id, u.username)).collect(),
+        Err(e) => {
+            warn!("Sign job {}: noms d'utilisateurs indisponibles pour le bilan: {}", job.id, e);
+            Vec::new()
+        }
+    };
+    let final_results = final_results(results, latest, &usernames);
+
+    let initiator = Ulid::from_string(&job.created_by)
+        .ok()
+        .and_then(|ulid| get_user_by_id(state, &ulid).ok().flatten())
+        .map(|u| format!("{} (programmé)", u.username))
+        .unwrap_or_else(|| job.created_by.clone());
+    handle_sign_results(state, &submission, &final_results, &initiator);
+}
+
+/// Résultats cumulés sous la forme du bilan : ceux de la dernière tentative tels quels, les plus anciens
+/// reconstruits depuis leur réponse enregistrée.
+fn final_results(
+    results: &[UserSignResponse],
+    latest: &[IntraUserResult],
+    usernames: &[(String, String)],
+) -> Vec<IntraUserResult> {
+    results
+        .iter()
+        .map(|result| match latest.iter().find(|l| l.user_id == result.ulid) {
+            Some(latest) => latest.clone(),
+            None => UserAttendanceResult {
+                user_id: result.ulid.clone(),
+                username: usernames
+                    .iter()
+                    .find(|(id, _)| id == &result.ulid)
+                    .map_or_else(|| result.ulid.clone(), |(_, name)| name.clone()),
+                success: result.response.is_signed(),
+                message: result.response.to_message(),
+                detail: IntraSignDetail { response: result.response.clone() },
+            },
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::sign::models::SignResponse;
+
+    fn at(minute: u32) -> NaiveDateTime {
+        chrono::NaiveDate::from_ymd_opt(2026, 10, 19).unwrap().and_hms_opt(9, minute, 0).unwrap()
+    }
+
+    fn result(ulid: &Ulid, response: SignResponse) -> UserSignResponse {
+        UserSignResponse {
+            ulid: ulid.to_string(),
+            response,
+        }
+    }
+
+    #[test]
+    fn retries_only_before_the_deadline() {
+        assert_eq!(retry_at(None, at(0)), None);
+        assert_eq!(retry_at(Some(at(10)), at(0)), Some(at(0) + chrono::Duration::seconds(RETRY_DELAY_SECS)));
+        // Le prochain essai tomberait après la deadline
+        assert_eq!(retry_at(Some(at(10)), at(10) - chrono::Duration::seconds(RETRY_DELAY_SECS)), None);
+    }
+
+    #[test]
+    fn temporary_failures_are_requeued_and_terminal_results_finish_the_job() {
+        let (a, b) = (Ulid::new(), Ulid::new());
+        let next = Some(at(1));
+        let temporary = vec![result(&a, SignResponse::Success), result(&b, SignResponse::TokenExpired)];
+        assert_eq!(after_attempt(&temporary, next), (SignJobStatus::Pending, next));
+        assert_eq!(after_attempt(&temporary, None), (SignJobStatus::Done, None));
+
+        let terminal = vec![result(&a, SignResponse::Success), result(&b, SignResponse::TokenNotFound)];
+        assert_eq!(after_attempt(&terminal, next), (SignJobStatus::Done, None));
+    }
+
+    #[test]
+    fn only_users_without_a_final_result_are_signed_again() {
+        let (a, b, c) = (Ulid::new(), Ulid::new(), Ulid::new());
+        let user_ids = serde_json::json!([a.to_string(), b.to_string(), c.to_string()]);
+        let previous = vec![
+            result(&a, SignResponse::Success),
+            result(&b, SignResponse::ServiceUnavailable),
+        ];
+        assert_eq!(pending_user_ids(&user_ids, &previous), vec![b, c]);
+
+        // Le nouvel essai remplace l'échec temporaire ; le succès précédent est conservé
+        let merged = merge_results(
+            previous,
+            [result(&b, SignResponse::AlreadySigned), result(&c, SignResponse::TokenExpired)],
+        );
+        assert_eq!(merged.len(), 3);
+        assert_eq!(merged[0].response, SignResponse::Success);
+        assert_eq!(merged[1].response, SignResponse::AlreadySigned);
+        assert_eq!(merged[2].response, SignResponse::TokenExpired);
+        assert_eq!(pending_user_ids(&user_ids, &merged), vec![c]);
+    }
+
+    #[test]
+    fn final_results_keep_earlier_successes_and_latest_messages() {
+        let (a, b) = (Ulid::new(), Ulid::new());
+        let merged = vec![result(&a, SignResponse::Success), result(&b, SignResponse::TokenExpired)];
+        let latest = vec![IntraUserResult::failure(&b.to_string(), "bob", "Délai dépassé")];
+
+        let results = final_results(&merged, &latest, &[(a.to_string(), "alice".to_string())]);
+        assert_eq!(results.len(), 2);
+        assert_eq!((results[0].username.as_str(), results[0].success), ("alice", true));
+        assert_eq!(results[0].detail.response, SignResponse::Success);
+        assert_eq!((results[1].username.as_str(), results[1].message.as_str()), ("bob", "Délai dépassé"));
+    }
+}
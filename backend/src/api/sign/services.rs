@@ -1,11 +1,20 @@
 use crate::{
     api::{
-        sign::models::{Cookie, CookieItem, SignResponse, UserSignResponse},
+        attendance::AttendanceEvent,
+        sign::{
+            client::IntraClient,
+            models::{
+                Cookie, CookieItem, IntraPlanningEntry, SignJob, SignJobStatus, SignResponse, SignTarget, UserSignResponse,
+            },
+            url_parser::SignUrl,
+        },
         users::User,
     },
     misc::GlobalState,
 };
 use axum::routing::Router;
+use chrono::{NaiveDate, NaiveDateTime, TimeZone};
+use chrono_tz::Tz;
 use http::{StatusCode, header::COOKIE};
 use tracing::error;
 
@@ -13,6 +22,9 @@ pub fn get_routes(state: GlobalState) -> Router {
     Router::new()
         .route("/", axum::routing::post(super::endpoints::sign))
         .route("/status", axum::routing::get(super::endpoints::status))
+        .route("/jobs", axum::routing::post(super::endpoints::create_sign_job))
+        .route("/jobs", axum::routing::get(super::endpoints::get_sign_jobs))
+        .route("/jobs/{id}", axum::routing::delete(super::endpoints::cancel_sign_job))
         .with_state(state)
 }
 
@@ -32,14 +44,15 @@ pub fn check_cookie_exists(
         .get_result::<bool>(&mut conn)
         .map_err(|_| "Database error when checking cookie existence".to_string())?;
 
-    Ok(exists)
+    // En mode démo, l'intra simulé n'a pas besoin des cookies du jour
+    Ok(exists || state.demo_mode)
 }
 
 pub fn get_cookies(state: &GlobalState) -> Result<Option<Vec<CookieItem>>, String> {
     use crate::schema::cookies::dsl::*;
     use diesel::prelude::*;
 
-    let current_date = chrono::Utc::now().date_naive();
+    let current_date = state.today();
 
     let mut conn = match state.get_db_conn() {
         Ok(conn) => conn,
@@ -54,6 +67,9 @@ pub fn get_cookies(state: &GlobalState) -> Result<Option<Vec<CookieItem>>, Strin
 
     let cookie = match cookie_record {
         Ok(Some(cookie)) => cookie,
+        Ok(None) if state.demo_mode => {
+            return Ok(Some(Vec::new()));
+        }
         Ok(None) => {
             return Ok(None);
         }
@@ -65,153 +81,379 @@ pub fn get_cookies(state: &GlobalState) -> Result<Option<Vec<CookieItem>>, Strin
     match cookie.cookie_data.as_str() {
         Some(json_string) => match serde_json::from_str::<Vec<CookieItem>>(json_string) {
             Ok(cookie_items) => Ok(Some(cookie_items)),
-            Err(_) => {
-                return Err("Failed to parse cookie JSON string".into());
-            }
+            Err(_) => Err("Failed to parse cookie JSON string".into()),
         },
         None => Err("Cookie data is not a valid JSON string".into()),
     }
 }
 
-pub async fn sign_fn(
-    cookies: Vec<CookieItem>,
-    users: Vec<User>,
-    url: &str,
-) -> Result<Vec<UserSignResponse>, String> {
-    let mut res = Vec::new();
-    let (url, token) = get_parsed_url(url)?;
+/// Signe un utilisateur sur l'intra avec les cookies du jour et son JWT.
+pub async fn sign_user(intra: &IntraClient, cookies: &[CookieItem], user: &User, sign_url: &SignUrl) -> SignResponse {
     let payload = serde_json::json!({
-        "token": token,
+        "token": sign_url.token,
         "rate": 0,
         "comment": ""
     });
 
-    let client = match get_reqwest_client() {
-        Ok(client) => client,
+    let jwt_cookie = match user.get_jwt_as_cookie() {
+        Some(cookie) => cookie,
+        None => return SignResponse::TokenNotFound,
+    };
+    let cookie_str = cookies
+        .iter()
+        .chain(std::iter::once(&jwt_cookie))
+        .map(|c| c.to_header_value())
+        .collect::<Vec<_>>()
+        .join("; ");
+
+    let response = intra
+        .http()
+        .post(intra.token_url(sign_url))
+        .header(COOKIE, cookie_str)
+        .json(&payload)
+        .send()
+        .await;
+
+    let status = match response {
+        Ok(resp) => resp.status(),
         Err(e) => {
-            return Err(format!("Failed to create HTTP client: {}", e));
+            error!("HTTP request failed: {}", e);
+            return SignResponse::UnknownError;
         }
     };
-    for user in users {
-        let jwt_cookie = match user.get_jwt_as_cookie() {
-            Some(cookie) => cookie,
-            None => {
-                res.push(UserSignResponse {
-                    ulid: user.id,
-                    response: SignResponse::TokenNotFound,
-                });
-                continue;
-            }
-        };
-        let mut user_cookies = cookies.clone();
-        user_cookies.push(jwt_cookie);
-        let cookie_str = user_cookies
-            .iter()
-            .map(|c| c.to_header_value())
-            .collect::<Vec<_>>()
-            .join("; ");
-
-        let response = client
-            .post(url.clone())
-            .header(COOKIE, cookie_str)
-            .json(&payload)
-            .send()
-            .await;
-            
-        println!("{:?}", &response);
-        
-        let status = match response {
-            Ok(resp) => resp.status(),
-            Err(e) => {
-                error!("HTTP request failed: {}", e);
-                res.push(UserSignResponse {
-                    ulid: user.id,
-                    response: SignResponse::UnknownError,
-                });
-                continue;
-            }
-        };
 
-        match status {
-            StatusCode::OK => res.push(UserSignResponse {
-                ulid: user.id,
-                response: SignResponse::Success,
-            }),
-            StatusCode::UNAUTHORIZED => res.push(UserSignResponse {
-                ulid: user.id,
-                response: SignResponse::TokenExpired,
-            }),
-            StatusCode::FORBIDDEN => res.push(UserSignResponse {
-                ulid: user.id,
-                response: SignResponse::TokenExpired,
-            }),
-            StatusCode::INTERNAL_SERVER_ERROR => {
-                res.push(UserSignResponse {
-                    ulid: user.id,
-                    response: SignResponse::AlreadySigned,
-                });
-            }
-            StatusCode::SERVICE_UNAVAILABLE => {
-                res.push(UserSignResponse {
-                    ulid: user.id,
-                    response: SignResponse::ServiceUnavailable,
-                });
-            }
-            _ => {
-                error!("Unexpected status code: {}", status);
-
-                res.push(UserSignResponse {
-                    ulid: user.id,
-                    response: SignResponse::UnknownError,
-                });
-            }
+    match status {
+        StatusCode::OK => SignResponse::Success,
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => SignResponse::TokenExpired,
+        StatusCode::INTERNAL_SERVER_ERROR => SignResponse::AlreadySigned,
+        StatusCode::SERVICE_UNAVAILABLE => SignResponse::ServiceUnavailable,
+        _ => {
+            error!("Unexpected status code: {}", status);
+            SignResponse::UnknownError
         }
     }
+}
+
+/// Cours de la journée auxquels l'utilisateur est inscrit (planning intra).
+pub async fn fetch_intra_events(
+    intra: &IntraClient,
+    user: &User,
+    day: NaiveDate,
+    tz: Tz,
+) -> Result<Vec<AttendanceEvent>, String> {
+    let jwt_cookie = user.get_jwt_as_cookie().ok_or("Token intra manquant")?;
+    let day = day.format("%Y-%m-%d").to_string();
+    let response = intra
+        .http()
+        .get(format!("{}/planning/load", intra.base_url()))
+        .query(&[("format", "json"), ("start", day.as_str()), ("end", day.as_str())])
+        .header(COOKIE, jwt_cookie.to_header_value())
+        .send()
+        .await
+        .map_err(|e| format!("Intra injoignable: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("Planning intra: statut {}", response.status()));
+    }
+    let entries: Vec<IntraPlanningEntry> = response
+        .json()
+        .await
+        .map_err(|e| format!("Planning intra illisible: {}", e))?;
+    Ok(entries.iter().filter_map(|entry| intra_event(entry, tz)).collect())
+}
 
-    Ok(res)
+/// Convertit une entrée du planning intra ; ignore les événements auxquels l'utilisateur n'est pas inscrit.
+fn intra_event(entry: &IntraPlanningEntry, tz: Tz) -> Option<AttendanceEvent> {
+    if !matches!(entry.event_registered.as_str(), Some("registered" | "present")) {
+        return None;
+    }
+    let to_rfc3339 = |value: &Option<String>| {
+        let naive = NaiveDateTime::parse_from_str(value.as_deref()?, "%Y-%m-%d %H:%M:%S").ok()?;
+        Some(tz.from_local_datetime(&naive).earliest()?.to_rfc3339())
+    };
+    Some(AttendanceEvent {
+        id: format!(
+            "/module/{}/{}/{}/{}/{}",
+            entry.scolaryear.as_deref()?,
+            entry.codemodule.as_deref()?,
+            entry.codeinstance.as_deref()?,
+            entry.codeacti.as_deref()?,
+            entry.codeevent.as_deref()?
+        ),
+        title: entry.acti_title.clone().unwrap_or_default(),
+        start: to_rfc3339(&entry.start)?,
+        end: to_rfc3339(&entry.end)?,
+    })
 }
 
-pub fn get_parsed_url(url: &str) -> Result<(reqwest::Url, String), String> {
-    let url = reqwest::Url::parse(url).map_err(|e| format!("Failed to parse URL: {}", e))?;
+pub fn create_sign_job(
+    state: &GlobalState,
+    created_by_param: &str,
+    user_ids_param: &[String],
+    target: &SignTarget,
+    not_before_param: NaiveDateTime,
+    deadline_param: Option<NaiveDateTime>,
+) -> Result<SignJob, String> {
+    use crate::schema::sign_jobs::dsl::*;
+    use diesel::prelude::*;
 
-    let token = url
-        .query_pairs()
-        .find(|(k, _)| k == "token")
-        .map(|(_, v)| v.to_string())
-        .ok_or_else(|| "Token not found in URL".to_string())?;
+    let mut conn = match state.get_db_conn() {
+        Ok(conn) => conn,
+        Err(_) => return Err("Failed to get database connection".into()),
+    };
 
-    let mut path_segments: Vec<_> = url
-        .path_segments()
-        .map(|c| c.collect::<Vec<_>>())
-        .unwrap_or_default();
+    diesel::insert_into(sign_jobs)
+        .values((
+            id.eq(ulid::Ulid::new().to_string()),
+            created_by.eq(created_by_param),
+            user_ids.eq(serde_json::json!(user_ids_param)),
+            url.eq(&target.url),
+            event_url.eq(&target.event_url),
+            token.eq(&target.token),
+            not_before.eq(not_before_param),
+            deadline.eq(deadline_param),
+            status.eq(SignJobStatus::Pending.as_str()),
+            created_at.eq(chrono::Utc::now().naive_utc()),
+        ))
+        .returning(SignJob::as_returning())
+        .get_result(&mut conn)
+        .map_err(|e| format!("Database error when creating sign job: {}", e))
+}
 
-    if let Some(last) = path_segments.last() {
-        if *last == "registered" {
-            path_segments.pop();
+/// Jobs créés par l'utilisateur ou qui le concernent, du plus récent au plus ancien.
+pub fn get_sign_jobs_for_user(state: &GlobalState, user_id_param: &str) -> Result<Vec<SignJob>, String> {
+    use crate::schema::sign_jobs::dsl::*;
+    use diesel::prelude::*;
+
+    let mut conn = match state.get_db_conn() {
+        Ok(conn) => conn,
+        Err(_) => return Err("Failed to get database connection".into()),
+    };
+
+    sign_jobs
+        .filter(created_by.eq(user_id_param).or(user_ids.has_key(user_id_param)))
+        .order(not_before.desc())
+        .limit(50)
+        .select(SignJob::as_select())
+        .load(&mut conn)
+        .map_err(|e| format!("Database error when fetching sign jobs: {}", e))
+}
+
+/// Annule un job encore en attente. Seul son créateur annule le job ; un utilisateur ciblé s'en retire
+/// seulement (le job est annulé s'il ne cible plus personne). Retourne `false` si le job n'existe pas,
+/// n'est plus en attente ou ne concerne pas l'utilisateur.
+pub fn cancel_sign_job(state: &GlobalState, job_id: &str, user_id_param: &str) -> Result<bool, String> {
+    use crate::schema::sign_jobs::dsl::*;
+    use diesel::prelude::*;
+
+    let mut conn = match state.get_db_conn() {
+        Ok(conn) => conn,
+        Err(_) => return Err("Failed to get database connection".into()),
+    };
+
+    conn.transaction(|conn| {
+        let pending_job = sign_jobs.filter(id.eq(job_id)).filter(status.eq(SignJobStatus::Pending.as_str()));
+
+        let cancelled = diesel::update(pending_job.filter(created_by.eq(user_id_param)))
+            .set(status.eq(SignJobStatus::Cancelled.as_str()))
+            .execute(conn)?;
+        if cancelled > 0 {
+            return Ok(true);
         }
-    }
-    path_segments.push("token");
 
-    let mut new_url = url.clone();
-    new_url.set_path(&path_segments.join("/"));
-    new_url.set_query(Some("format=json"));
+        let left = diesel::update(pending_job.filter(user_ids.has_key(user_id_param)))
+            .set(user_ids.eq(user_ids.remove(user_id_param)))
+            .execute(conn)?;
+        if left > 0 {
+            diesel::update(pending_job.filter(user_ids.eq(serde_json::json!([]))))
+                .set(status.eq(SignJobStatus::Cancelled.as_str()))
+                .execute(conn)?;
+        }
+        Ok(left > 0)
+    })
+    .map_err(|e: diesel::result::Error| format!("Database error when cancelling sign job: {}", e))
+}
+
+/// Passe en `running` les jobs arrivés à échéance et les retourne (un job n'est réclamé qu'une fois).
+pub fn claim_due_sign_jobs(state: &GlobalState, now: NaiveDateTime) -> Result<Vec<SignJob>, String> {
+    use crate::schema::sign_jobs::dsl::*;
+    use diesel::prelude::*;
 
-    Ok((new_url, token))
+    let mut conn = match state.get_db_conn() {
+        Ok(conn) => conn,
+        Err(_) => return Err("Failed to get database connection".into()),
+    };
+
+    diesel::update(
+        sign_jobs
+            .filter(status.eq(SignJobStatus::Pending.as_str()))
+            .filter(not_before.le(now)),
+    )
+    .set(status.eq(SignJobStatus::Running.as_str()))
+    .returning(SignJob::as_returning())
+    .get_results(&mut conn)
+    .map_err(|e| format!("Database error when claiming sign jobs: {}", e))
+}
+
+/// Remet en attente les jobs restés `running` (serveur arrêté pendant leur exécution).
+pub fn requeue_interrupted_sign_jobs(state: &GlobalState) -> Result<usize, String> {
+    use crate::schema::sign_jobs::dsl::*;
+    use diesel::prelude::*;
+
+    let mut conn = match state.get_db_conn() {
+        Ok(conn) => conn,
+        Err(_) => return Err("Failed to get database connection".into()),
+    };
+
+    diesel::update(sign_jobs.filter(status.eq(SignJobStatus::Running.as_str())))
+        .set(status.eq(SignJobStatus::Pending.as_str()))
+        .execute(&mut conn)
+        .map_err(|e| format!("Database error when requeuing sign jobs: {}", e))
+}
+
+pub fn update_sign_job(
+    state: &GlobalState,
+    job_id: &str,
+    new_status: SignJobStatus,
+    new_results: Option<&[UserSignResponse]>,
+    new_error: Option<&str>,
+    next_not_before: Option<NaiveDateTime>,
+) -> Result<(), String> {
+    use crate::schema::sign_jobs::dsl::*;
+    use diesel::prelude::*;
+
+    let mut conn = match state.get_db_conn() {
+        Ok(conn) => conn,
+        Err(_) => return Err("Failed to get database connection".into()),
+    };
+
+    let now = chrono::Utc::now().naive_utc();
+    diesel::update(sign_jobs.filter(id.eq(job_id)))
+        .set((
+            status.eq(new_status.as_str()),
+            attempts.eq(attempts + 1),
+            results.eq(new_results.map(|r| serde_json::json!(r))),
+            error.eq(new_error),
+            executed_at.eq(now),
+        ))
+        .execute(&mut conn)
+        .map_err(|e| format!("Database error when updating sign job: {}", e))?;
+
+    if let Some(next) = next_not_before {
+        diesel::update(sign_jobs.filter(id.eq(job_id)))
+            .set(not_before.eq(next))
+            .execute(&mut conn)
+            .map_err(|e| format!("Database error when rescheduling sign job: {}", e))?;
+    }
+
+    Ok(())
 }
 
-pub fn get_reqwest_client() -> Result<reqwest::Client, String> {
-    let mut headers = reqwest::header::HeaderMap::new();
-    headers.insert(
-        reqwest::header::USER_AGENT,
-        reqwest::header::HeaderValue::from_static("Mozilla/5.0 (compatible; MyApp/1.0)"),
-    );
-    headers.insert(
-        reqwest::header::ACCEPT,
-        reqwest::header::HeaderValue::from_static("application/json"),
-    );
-
-    reqwest::Client::builder()
-        .default_headers(headers)
-        .build()
-        .map_err(|e| format!("Failed to build HTTP client: {}", e))
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::edsquare::{EdsquareClient, client::DEFAULT_EDSQUARE_BASE_URL};
+    use crate::api::sign::url_parser::parse_sign_url;
+    use axum::{http::HeaderMap, routing::post};
+
+    /// Faux endpoint intra `.../token?format=json` : accepte le JWT "valid", refuse les autres.
+    async fn start_mock_intra() -> IntraClient {
+        let app = Router::new().route(
+            "/module/{year}/{module}/{instance}/{acti}/{event}/token",
+            post(|headers: HeaderMap| async move {
+                let cookies = headers.get(COOKIE).and_then(|v| v.to_str().ok()).unwrap_or_default();
+                if cookies.split("; ").any(|c| c == "user=valid") {
+                    StatusCode::OK
+                } else {
+                    StatusCode::FORBIDDEN
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        IntraClient::new(&format!("http://{}", addr)).unwrap()
+    }
+
+    fn user(jwt: Option<&str>) -> User {
+        let mut user = User::new("jdoe".to_string(), "password".to_string());
+        user.jwt_intra_epitech = jwt.map(str::to_string);
+        user
+    }
+
+    #[tokio::test]
+    async fn signs_each_user_against_intra_base_url() {
+        let intra = start_mock_intra().await;
+        let sign_url = parse_sign_url(
+            "https://intra.epitech.eu/module/2024/B-DEV-500/PAR-5-1/acti-654321/event-123456/registered?token=12345678",
+        )
+        .unwrap();
+
+        let mut responses = Vec::new();
+        for user in [user(Some("valid")), user(Some("stale")), user(None)] {
+            responses.push(sign_user(&intra, &[], &user, &sign_url).await);
+        }
+        assert_eq!(
+            responses,
+            vec![SignResponse::Success, SignResponse::TokenExpired, SignResponse::TokenNotFound]
+        );
+    }
+
+    #[tokio::test]
+    async fn only_the_creator_cancels_a_sign_job() {
+        let Some(db_url) = crate::misc::test_database_url() else {
+            return;
+        };
+        let state = GlobalState::for_tests(&db_url, EdsquareClient::new(DEFAULT_EDSQUARE_BASE_URL).unwrap());
+        let [creator, alice, bob] = ["creator", "alice", "bob"].map(|name| {
+            let user = User::new(format!("{}-{}", name, ulid::Ulid::new()), "password".to_string());
+            let user_id = user.id.clone();
+            crate::api::users::create_user(&state, user).unwrap();
+            user_id
+        });
+        let target = SignTarget { url: Some("https://intra.epitech.eu/module/2024/B-DEV-500/PAR-5-1/acti-654321/event-123456/registered?token=12345678".to_string()), ..Default::default() };
+        let targets = [alice.clone(), bob.clone()];
+        let now = chrono::Utc::now().naive_utc() + chrono::Duration::hours(1);
+        let job_status = |job_id: &str| {
+            let job = get_sign_jobs_for_user(&state, &creator).unwrap().into_iter().find(|job| job.id == job_id).unwrap();
+            (job.status, serde_json::from_value::<Vec<String>>(job.user_ids).unwrap())
+        };
+
+        // Un utilisateur ciblé se retire sans annuler le job des autres
+        let job = create_sign_job(&state, &creator, &targets, &target, now, None).unwrap();
+        assert!(cancel_sign_job(&state, &job.id, &alice).unwrap());
+        assert_eq!(job_status(&job.id), ("pending".to_string(), vec![bob.clone()]));
+        assert!(!cancel_sign_job(&state, &job.id, &alice).unwrap());
+        // Dernier utilisateur ciblé retiré : plus personne à signer
+        assert!(cancel_sign_job(&state, &job.id, &bob).unwrap());
+        assert_eq!(job_status(&job.id), ("cancelled".to_string(), vec![]));
+
+        // Le créateur annule le job entier
+        let job = create_sign_job(&state, &creator, &targets, &target, now, None).unwrap();
+        assert!(cancel_sign_job(&state, &job.id, &creator).unwrap());
+        assert_eq!(job_status(&job.id).0, "cancelled");
+
+        for user in [&creator, &alice, &bob] {
+            crate::api::users::delete_user_account(&state, user).unwrap();
+        }
+    }
+
+    #[test]
+    fn keeps_only_registered_intra_events() {
+        let entry = |registered: serde_json::Value| IntraPlanningEntry {
+            scolaryear: Some("2026".to_string()),
+            codemodule: Some("B-DEV-500".to_string()),
+            codeinstance: Some("PAR-5-1".to_string()),
+            codeacti: Some("acti-654321".to_string()),
+            codeevent: Some("event-123456".to_string()),
+            acti_title: Some("Kick-off".to_string()),
+            start: Some("2026-10-19 09:00:00".to_string()),
+            end: Some("2026-10-19 12:00:00".to_string()),
+            event_registered: registered,
+        };
+
+        let event = intra_event(&entry(serde_json::json!("registered")), chrono_tz::Europe::Paris).unwrap();
+        assert_eq!(event.id, "/module/2026/B-DEV-500/PAR-5-1/acti-654321/event-123456");
+        assert_eq!(event.start, "2026-10-19T09:00:00+02:00");
+        assert!(intra_event(&entry(serde_json::json!(false)), chrono_tz::Europe::Paris).is_none());
+    }
 }
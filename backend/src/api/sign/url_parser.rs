@@ -0,0 +1,381 @@
+use std::fmt;
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+/// Raccourcisseurs dont les liens sont suivis (une redirection) pour retrouver le lien intra.
+/// Les autres hôtes ne sont jamais contactés.
+pub const SHORT_LINK_HOSTS: [&str; 8] =
+    ["bit.ly", "tinyurl.com", "t.co", "is.gd", "cutt.ly", "urlz.fr", "qrco.de", "rebrand.ly"];
+
+static EVENT_PATH_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)module/(?P<year>\d{4})/(?P<module>[^/?#&\s]+)/(?P<instance>[^/?#&\s]+)/(?P<acti>acti-\d+)/(?P<event>event-\d+)",
+    )
+    .expect("valid event path regex")
+});
+static QUERY_PARAM_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"[?&#](\w+)=([^?&#\s]*)").expect("valid query parameter regex"));
+static TOKEN_PATH_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)/event-\d+(?:/(?:token|registered))?/([A-Za-z0-9]+)/?(?:[?#]|$)").expect("valid token path regex")
+});
+
+/// Lien de signature intra décomposé : année, module, instance, activité, événement et token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignUrl {
+    pub scolar_year: String,
+    pub module: String,
+    pub instance: String,
+    pub activity: String,
+    pub event: String,
+    pub token: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignUrlError {
+    Empty,
+    MissingEvent,
+    MissingToken,
+    InvalidToken(String),
+    ShortLink(String),
+}
+
+impl fmt::Display for SignUrlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignUrlError::Empty => write!(f, "Missing sign URL (send `url`, or `event_url` and `token`)"),
+            SignUrlError::MissingEvent => write!(
+                f,
+                "No intra event found in URL (expected module/<year>/<module>/<instance>/acti-XXX/event-XXX)"
+            ),
+            SignUrlError::MissingToken => write!(f, "Token not found in URL"),
+            SignUrlError::InvalidToken(token) => write!(f, "Invalid token: {}", token),
+            SignUrlError::ShortLink(reason) => write!(f, "Could not follow shortened link: {}", reason),
+        }
+    }
+}
+
+impl SignUrl {
+    /// Chemin de l'événement sur l'intra, sans le token (ex: /module/2024/B-DEV-500/PAR-5-1/acti-1/event-2).
+    pub fn event_path(&self) -> String {
+        format!(
+            "/module/{}/{}/{}/{}/{}",
+            self.scolar_year, self.module, self.instance, self.activity, self.event
+        )
+    }
+
+    /// URL de l'endpoint intra qui enregistre le token (`.../token?format=json`), sous le chemin de `base_url`.
+    pub fn token_url(&self, base_url: &reqwest::Url) -> reqwest::Url {
+        let mut url = base_url.clone();
+        url.set_path(&format!("{}{}/token", base_url.path().trim_end_matches('/'), self.event_path()));
+        url.set_query(Some("format=json"));
+        url
+    }
+}
+
+/// Événement intra sans token (utilisé pour les liens sans token ou l'événement sélectionné).
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct EventRef {
+    scolar_year: String,
+    module: String,
+    instance: String,
+    activity: String,
+    event: String,
+}
+
+impl EventRef {
+    fn with_token(self, token: String) -> SignUrl {
+        SignUrl {
+            scolar_year: self.scolar_year,
+            module: self.module,
+            instance: self.instance,
+            activity: self.activity,
+            event: self.event,
+            token,
+        }
+    }
+}
+
+/// Lien à signer tel qu'envoyé par le client : soit le lien scanné (`url`), soit l'événement sélectionné
+/// (`event_url`, à défaut un `url` sans token) et le token (`token`) séparément. Un `token` explicite l'emporte
+/// sur celui du lien.
+pub fn resolve_sign_url(
+    url: Option<&str>,
+    event_url: Option<&str>,
+    token: Option<&str>,
+) -> Result<SignUrl, SignUrlError> {
+    let present = |value: Option<&str>| value.map(normalize_input).filter(|v| !v.is_empty());
+    let Some(token) = present(token) else {
+        return match present(url) {
+            Some(url) => parse_sign_url(&url),
+            None if present(event_url).is_some() => Err(SignUrlError::MissingToken),
+            None => Err(SignUrlError::Empty),
+        };
+    };
+    if !is_bare_token(&token) {
+        return Err(SignUrlError::InvalidToken(token));
+    }
+    let event = present(event_url)
+        .or_else(|| present(url))
+        .and_then(|e| parse_event_ref(&e))
+        .ok_or(SignUrlError::MissingEvent)?;
+    Ok(event.with_token(token))
+}
+
+/// Parse un lien de signature intra, quelle que soit sa forme :
+/// - `.../event-XXX/registered?token=12345678` (QR code classique)
+/// - `.../event-XXX/token?token=12345678` ou `.../event-XXX/token/12345678`
+/// - liens mobiles (`/#!/module/...`) ou avec paramètres `scolaryear`, `codemodule`, `codeinstance`, `codeacti`, `codeevent`
+/// - liens encodés (`https%3A%2F%2F...`), y compris dans le paramètre d'une redirection, ou sans schéma
+///   (`intra.epitech.eu/module/...`)
+///
+/// Les liens raccourcis (bit.ly...) ne contiennent pas l'événement : `SignTarget::resolve_with` les suit d'abord.
+/// Un token seul passe par `resolve_sign_url` avec l'événement sélectionné.
+pub fn parse_sign_url(input: &str) -> Result<SignUrl, SignUrlError> {
+    let input = normalize_input(input);
+    if input.is_empty() {
+        return Err(SignUrlError::Empty);
+    }
+
+    let event = parse_event_ref(&input).ok_or(SignUrlError::MissingEvent)?;
+    let token = extract_token(&input).ok_or(SignUrlError::MissingToken)?;
+    if !is_valid_token(&token) {
+        return Err(SignUrlError::InvalidToken(token));
+    }
+
+    Ok(event.with_token(token))
+}
+
+/// Supprime les espaces/guillemets et décode les liens encodés (jusqu'à 3 niveaux d'encodage).
+fn normalize_input(input: &str) -> String {
+    let mut value = input
+        .trim()
+        .trim_matches(|c| c == '"' || c == '\'' || c == '<' || c == '>')
+        .trim()
+        .to_string();
+    for _ in 0..3 {
+        if !value.contains('%') {
+            break;
+        }
+        match urlencoding::decode(&value) {
+            Ok(decoded) if decoded != value => value = decoded.into_owned(),
+            _ => break,
+        }
+    }
+    value.replace("&amp;", "&")
+}
+
+fn is_bare_token(value: &str) -> bool {
+    is_valid_token(value) && !value.contains(['/', '?', '=', '.'])
+}
+
+fn is_valid_token(value: &str) -> bool {
+    !value.is_empty() && value.len() <= 32 && value.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+/// Adresse à suivre si le lien vient d'un raccourcisseur connu (`SHORT_LINK_HOSTS`), avec ou sans schéma.
+pub fn short_link(input: &str) -> Option<reqwest::Url> {
+    let input = normalize_input(input);
+    let with_scheme = if input.contains("://") { input } else { format!("https://{}", input) };
+    let url = reqwest::Url::parse(&with_scheme).ok()?;
+    let host = url.host_str()?.trim_start_matches("www.").to_lowercase();
+    (matches!(url.scheme(), "http" | "https") && SHORT_LINK_HOSTS.contains(&host.as_str())).then_some(url)
+}
+
+/// Première valeur non vide du paramètre `name` (casse ignorée) du lien.
+fn query_param(input: &str, name: &str) -> Option<String> {
+    QUERY_PARAM_RE
+        .captures_iter(input)
+        .find(|caps| caps[1].eq_ignore_ascii_case(name) && !caps[2].is_empty())
+        .map(|caps| caps[2].to_string())
+}
+
+fn parse_event_ref(input: &str) -> Option<EventRef> {
+    if let Some(caps) = EVENT_PATH_RE.captures(input) {
+        return Some(EventRef {
+            scolar_year: caps["year"].to_string(),
+            module: caps["module"].to_string(),
+            instance: caps["instance"].to_string(),
+            activity: caps["acti"].to_lowercase(),
+            event: caps["event"].to_lowercase(),
+        });
+    }
+
+    // Liens de l'app mobile / de l'API intra : paramètres séparés
+    let param = |name: &str| query_param(input, name);
+    Some(EventRef {
+        scolar_year: param("scolaryear")?,
+        module: param("codemodule")?,
+        instance: param("codeinstance")?,
+        activity: param("codeacti")?.to_lowercase(),
+        event: param("codeevent")?.to_lowercase(),
+    })
+}
+
+fn extract_token(input: &str) -> Option<String> {
+    if let Some(token) = query_param(input, "token") {
+        return Some(token);
+    }
+
+    // Token dans le chemin : .../event-XXX/12345678, .../event-XXX/token/12345678, .../registered/12345678
+    TOKEN_PATH_RE
+        .captures(input)
+        .map(|caps| caps[1].to_string())
+        .filter(|t| !t.eq_ignore_ascii_case("token") && !t.eq_ignore_ascii_case("registered"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EVENT: &str = "https://intra.epitech.eu/module/2024/B-DEV-500/PAR-5-1/acti-654321/event-123456";
+
+    fn expected(token: &str) -> SignUrl {
+        SignUrl {
+            scolar_year: "2024".to_string(),
+            module: "B-DEV-500".to_string(),
+            instance: "PAR-5-1".to_string(),
+            activity: "acti-654321".to_string(),
+            event: "event-123456".to_string(),
+            token: token.to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_registered_qr_code_url() {
+        let url = format!("{}/registered?token=12345678", EVENT);
+        assert_eq!(parse_sign_url(&url), Ok(expected("12345678")));
+    }
+
+    #[test]
+    fn parses_url_already_ending_in_token() {
+        let url = format!("{}/token?token=12345678&format=json", EVENT);
+        assert_eq!(parse_sign_url(&url), Ok(expected("12345678")));
+    }
+
+    #[test]
+    fn parses_token_as_path_segment() {
+        for url in [
+            format!("{}/token/12345678", EVENT),
+            format!("{}/registered/12345678", EVENT),
+            format!("{}/12345678/", EVENT),
+        ] {
+            assert_eq!(parse_sign_url(&url), Ok(expected("12345678")), "{}", url);
+        }
+    }
+
+    #[test]
+    fn parses_mobile_hash_bang_link() {
+        let url = "https://intra.epitech.eu/#!/module/2024/B-DEV-500/PAR-5-1/acti-654321/event-123456/registered?token=12345678";
+        assert_eq!(parse_sign_url(url), Ok(expected("12345678")));
+    }
+
+    #[test]
+    fn parses_mobile_query_parameters_link() {
+        let url = "https://intra.epitech.eu/mobile/sign?scolaryear=2024&codemodule=B-DEV-500&codeinstance=PAR-5-1&codeacti=acti-654321&codeevent=event-123456&token=12345678";
+        assert_eq!(parse_sign_url(url), Ok(expected("12345678")));
+    }
+
+    #[test]
+    fn parses_url_encoded_link() {
+        let url = "https%3A%2F%2Fintra.epitech.eu%2Fmodule%2F2024%2FB-DEV-500%2FPAR-5-1%2Facti-654321%2Fevent-123456%2Fregistered%3Ftoken%3D12345678";
+        assert_eq!(parse_sign_url(url), Ok(expected("12345678")));
+    }
+
+    #[test]
+    fn parses_double_encoded_link_wrapped_in_redirect() {
+        let url = "https://l.example.com/?u=https%253A%252F%252Fintra.epitech.eu%252Fmodule%252F2024%252FB-DEV-500%252FPAR-5-1%252Facti-654321%252Fevent-123456%252Fregistered%253Ftoken%253D12345678";
+        assert_eq!(parse_sign_url(url), Ok(expected("12345678")));
+    }
+
+    #[test]
+    fn parses_link_without_scheme() {
+        let url = "  intra.epitech.eu/module/2024/B-DEV-500/PAR-5-1/acti-654321/event-123456/registered?token=12345678 ";
+        assert_eq!(parse_sign_url(url), Ok(expected("12345678")));
+        let url = "module/2024/B-DEV-500/PAR-5-1/acti-654321/event-123456?token=12345678";
+        assert_eq!(parse_sign_url(url), Ok(expected("12345678")));
+    }
+
+    #[test]
+    fn parses_html_escaped_query() {
+        let url = format!("{}/registered?format=json&amp;token=12345678", EVENT);
+        assert_eq!(parse_sign_url(&url), Ok(expected("12345678")));
+    }
+
+    #[test]
+    fn resolves_token_with_selected_event() {
+        assert_eq!(resolve_sign_url(None, Some(EVENT), Some("12345678")), Ok(expected("12345678")));
+        let selected = format!("{}/registered", EVENT);
+        assert_eq!(resolve_sign_url(None, Some(&selected), Some(" 12345678 ")), Ok(expected("12345678")));
+        // Lien sans token (ex: .../event-XXX/token) et token à part
+        let url = format!("{}/token", EVENT);
+        assert_eq!(resolve_sign_url(Some(&url), None, Some("12345678")), Ok(expected("12345678")));
+        // Sans token explicite, le lien scanné suffit
+        let url = format!("{}/registered?token=12345678", EVENT);
+        assert_eq!(resolve_sign_url(Some(&url), None, None), Ok(expected("12345678")));
+    }
+
+    #[test]
+    fn rejects_token_without_event_and_bare_token_as_url() {
+        assert_eq!(resolve_sign_url(None, None, Some("12345678")), Err(SignUrlError::MissingEvent));
+        assert_eq!(
+            resolve_sign_url(None, Some("https://intra.epitech.eu/"), Some("12345678")),
+            Err(SignUrlError::MissingEvent)
+        );
+        assert_eq!(resolve_sign_url(None, Some(EVENT), None), Err(SignUrlError::MissingToken));
+        assert_eq!(
+            resolve_sign_url(None, Some(EVENT), Some("1234/5678")),
+            Err(SignUrlError::InvalidToken("1234/5678".to_string()))
+        );
+        // `url` doit être un lien : un token seul n'est plus interprété avec `event_url`
+        assert_eq!(resolve_sign_url(Some("12345678"), Some(EVENT), None), Err(SignUrlError::MissingEvent));
+        assert_eq!(resolve_sign_url(None, None, None), Err(SignUrlError::Empty));
+    }
+
+    #[test]
+    fn recognizes_shortened_links() {
+        assert_eq!(parse_sign_url("https://bit.ly/3xYzAbC"), Err(SignUrlError::MissingEvent));
+        assert_eq!(short_link("https://bit.ly/3xYzAbC").unwrap().as_str(), "https://bit.ly/3xYzAbC");
+        assert_eq!(short_link("www.tinyurl.com/abc").unwrap().as_str(), "https://www.tinyurl.com/abc");
+        assert_eq!(short_link("https%3A%2F%2Fbit.ly%2Fabc").unwrap().as_str(), "https://bit.ly/abc");
+        assert!(short_link("https://bit.ly.evil.example/abc").is_none());
+        assert!(short_link("ftp://bit.ly/abc").is_none());
+        assert!(short_link(&format!("{}/registered?token=12345678", EVENT)).is_none());
+    }
+
+    #[test]
+    fn rejects_url_without_token() {
+        assert_eq!(parse_sign_url(&format!("{}/registered", EVENT)), Err(SignUrlError::MissingToken));
+        assert_eq!(parse_sign_url(&format!("{}/token", EVENT)), Err(SignUrlError::MissingToken));
+    }
+
+    #[test]
+    fn rejects_invalid_token() {
+        let url = format!("{}/registered?token=abc-def!", EVENT);
+        assert!(matches!(parse_sign_url(&url), Err(SignUrlError::InvalidToken(_))));
+    }
+
+    #[test]
+    fn rejects_empty_and_unrelated_input() {
+        assert_eq!(parse_sign_url("   "), Err(SignUrlError::Empty));
+        assert_eq!(
+            parse_sign_url("https://intra.epitech.eu/user/?token=12345678"),
+            Err(SignUrlError::MissingEvent)
+        );
+    }
+
+    #[test]
+    fn builds_token_endpoint_url() {
+        let base = reqwest::Url::parse("https://intra.epitech.eu").unwrap();
+        assert_eq!(
+            expected("12345678").token_url(&base).as_str(),
+            "https://intra.epitech.eu/module/2024/B-DEV-500/PAR-5-1/acti-654321/event-123456/token?format=json"
+        );
+
+        let proxied = reqwest::Url::parse("http://127.0.0.1:8080/intra/").unwrap();
+        assert_eq!(
+            expected("12345678").token_url(&proxied).as_str(),
+            "http://127.0.0.1:8080/intra/module/2024/B-DEV-500/PAR-5-1/acti-654321/event-123456/token?format=json"
+        );
+    }
+}
@@ -1,4 +1,8 @@
+use crate::api::attendance;
 use crate::api::auth;
+use crate::api::events;
+use crate::api::reservations;
+use crate::api::rooms;
 use crate::api::sign;
 use crate::api::users;
 use utoipa::OpenApi;
@@ -15,15 +19,39 @@ use utoipa::OpenApi;
         users::endpoints::update_user,
         users::endpoints::save_signature,
         users::endpoints::get_signatures,
+        users::endpoints::update_signature,
+        users::endpoints::render_signature_png,
+        users::endpoints::get_signature_image,
+        users::endpoints::generate_signatures,
         users::endpoints::delete_signature,
         users::endpoints::delete_account,
         sign::endpoints::sign,
-        sign::endpoints::status
+        sign::endpoints::status,
+        sign::endpoints::create_sign_job,
+        sign::endpoints::get_sign_jobs,
+        sign::endpoints::cancel_sign_job,
+        reservations::endpoints::create_reservation,
+        reservations::endpoints::get_today_reservations,
+        reservations::endpoints::cancel_reservation,
+        rooms::endpoints::create_room,
+        rooms::endpoints::get_rooms,
+        rooms::endpoints::get_room,
+        rooms::endpoints::close_room,
+        rooms::endpoints::join_room,
+        rooms::endpoints::leave_room,
+        rooms::endpoints::scan_room,
+        events::endpoints::stream,
+        attendance::endpoints::get_readiness,
+        attendance::endpoints::get_events
     ),
     tags(
         (name = "Auth", description = "Authentication related endpoints"),
         (name = "Users", description = "User management endpoints"),
-        (name = "Sign", description = "Epitech signing endpoints")
+        (name = "Sign", description = "Epitech signing endpoints"),
+        (name = "Reservations", description = "Priority signing reservations"),
+        (name = "Rooms", description = "Live sign rooms: one QR scan signs every consenting member"),
+        (name = "Events", description = "Real-time updates (Server-Sent Events)"),
+        (name = "Attendance", description = "Readiness and events across attendance platforms (intra, EDSquare)")
     )
 )]
 pub struct Swagger;
@@ -1,17 +1,21 @@
-use axum::{Json, extract::State, response::IntoResponse};
+use axum::{Json, extract::{Query, State}, response::IntoResponse};
 use base64::{Engine as _, engine::general_purpose};
 use chrono::DateTime;
-use http::StatusCode;
+use http::{HeaderMap, StatusCode, header};
 use serde_json::Value;
-use tracing::{info, error};
+use tracing::{info, error, warn};
 
 use crate::{
     api::{
         auth::{JwtClaims, hash_password},
+        reservations::get_reserved_user_ids,
         users::{
             User, get_user_by_id, get_user_by_username,
-            models::{JwtPayload, PublicUserResponse, UpdateUserPayload, SaveSignaturePayload, UserSignature},
-            services::{get_all_users, update_user_jwt, add_user_signature, get_user_signatures, delete_user_signature, delete_user_account},
+            models::{JwtPayload, PublicUserResponse, UpdateUserPayload, SaveSignaturePayload, UpdateSignaturePayload, UserSignatureResponse, GenerateSignaturesPayload, GeneratedSignaturesResponse},
+            services::{get_all_users, update_user_jwt, add_user_signature, get_user_signature, get_signature_image as load_signature_image, get_user_signatures, update_user_signature, delete_user_signature, delete_user_account},
+            signature_image::{CANONICAL_HEIGHT, CANONICAL_WIDTH, MAX_RENDER_DIMENSION, MAX_SIGNATURE_VARIATION, png_bytes},
+            signature_font::{DEFAULT_CANDIDATES, DEFAULT_SLANT_DEG, DEFAULT_TEXT_SIZE, MAX_CANDIDATES, MAX_NAME_CHARS, MAX_SLANT_DEG, TEXT_SIZE_RANGE, TypedSignatureStyle, synthesize_signatures},
+            signature_vector::{prepare_signature, render_signature},
         },
     },
     misc::GlobalState,
@@ -34,10 +38,10 @@ pub async fn get_me(State(state): State<GlobalState>, jwt: JwtClaims) -> impl In
         Ok(None) => return (StatusCode::NOT_FOUND, "User not found").into_response(),
         Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Error fetching user").into_response(),
     };
-    if let Ok(sigs) = get_user_signatures(&state, &user.id) {
-        if let Some(first) = sigs.first() {
-            user.signature_manuscrite = Some(first.signature_data.clone());
-        }
+    if let Ok(sigs) = get_user_signatures(&state, &user.id)
+        && let Some(first) = sigs.first()
+    {
+        user.signature_manuscrite = Some(first.image_url());
     }
     (StatusCode::OK, Json(user)).into_response()
 }
@@ -109,16 +113,14 @@ pub async fn update_jwt(
 
     match update_user_jwt(&state, jwt_user.sub, &jwt_payload.jwt, exp_naive) {
         Ok(_) => (StatusCode::OK).into_response(),
-        Err(_) => {
-            return (StatusCode::INTERNAL_SERVER_ERROR).into_response();
-        }
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR).into_response(),
     }
 }
 
 #[utoipa::path(
     get,
     path = "/api/users",
-    description = "Get all users",
+    description = "Get all users, users with a pending reservation for today first",
     responses(
         (status = 200, description = "Users retrieved successfully", body = Vec<PublicUserResponse>),
         (status = 401 , description = "Unauthorized"),
@@ -137,10 +139,21 @@ pub async fn get_users(State(state): State<GlobalState>) -> impl IntoResponse {
         return (StatusCode::NOT_FOUND, "No users found").into_response();
     }
 
-    let public_users: Vec<PublicUserResponse> = users
+    let reserved_ids = match get_reserved_user_ids(&state, state.today()) {
+        Ok(reserved_ids) => reserved_ids,
+        Err(e) => {
+            error!("Error fetching today's reservations: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Error fetching reservations").into_response();
+        }
+    };
+    let mut public_users: Vec<PublicUserResponse> = users
         .into_iter()
-        .map(|user| PublicUserResponse::from(user))
+        .map(|user| {
+            let reserved = reserved_ids.contains(&user.id);
+            PublicUserResponse { reserved, ..PublicUserResponse::from(user) }
+        })
         .collect();
+    public_users.sort_by_key(|u| !u.reserved);
     (StatusCode::OK, Json(public_users)).into_response()
 }
 
@@ -196,6 +209,31 @@ pub async fn update_user(
         }
     }
 
+    if let Some(variation) = payload.signature_variation {
+        if !(0..=MAX_SIGNATURE_VARIATION).contains(&variation) {
+            return (StatusCode::BAD_REQUEST, "signature_variation must be between 0 and 100").into_response();
+        }
+        user.signature_variation = variation;
+    }
+
+    if let Some(rotation) = payload.signature_rotation {
+        user.signature_rotation = rotation.as_str().to_string();
+    }
+
+    if let Some(pinned_id) = payload.pinned_signature_id {
+        if pinned_id.is_empty() {
+            user.pinned_signature_id = None;
+        } else {
+            match get_user_signatures(&state, &user.id) {
+                Ok(sigs) if sigs.iter().any(|s| s.id == pinned_id) => user.pinned_signature_id = Some(pinned_id),
+                Ok(_) => return (StatusCode::BAD_REQUEST, "Unknown pinned_signature_id").into_response(),
+                Err(_) => {
+                    return (StatusCode::INTERNAL_SERVER_ERROR, "Error fetching signatures").into_response();
+                }
+            }
+        }
+    }
+
     match super::services::update_user(&state, &user) {
         Ok(_) => (StatusCode::OK, Json(user)).into_response(),
         Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Error updating user").into_response(),
@@ -205,13 +243,15 @@ pub async fn update_user(
 #[utoipa::path(
     post,
     path = "/api/users/me/signature",
-    description = "Save the handwritten signature for the current user",
+    description = "Save a handwritten signature for the current user, as a PNG or as vector data (strokes or SVG path) rendered server-side",
     request_body = SaveSignaturePayload,
     responses(
-        (status = 200, description = "Signature saved successfully"),
-        (status = 400, description = "Invalid signature format"),
+        (status = 201, description = "Signature saved successfully", body = UserSignatureResponse),
+        (status = 400, description = "Invalid PNG or vector data, or blank signature"),
         (status = 401, description = "Unauthorized"),
         (status = 404, description = "User not found"),
+        (status = 409, description = "The same signature image is already saved"),
+        (status = 413, description = "PNG over 1 MiB or 4000x4000 pixels, or vector data over 20000 points"),
     ),
     tag = "Users"
 )]
@@ -220,9 +260,22 @@ pub async fn save_signature(
     jwt_user: JwtClaims,
     Json(payload): Json<SaveSignaturePayload>,
 ) -> impl IntoResponse {
-    if !payload.signature.starts_with("data:image/png;base64,") {
-        return (StatusCode::BAD_REQUEST, "Invalid signature format. Expected PNG base64 data URL").into_response();
-    }
+    // Décodage, recadrage et compression PNG : hors des threads async
+    let prepared = tokio::task::spawn_blocking(move || {
+        prepare_signature(&payload).and_then(|(signature, path)| Ok((png_bytes(&signature)?, path)))
+    })
+    .await;
+    let (png, path) = match prepared {
+        Ok(Ok(prepared)) => prepared,
+        Ok(Err(e)) => {
+            warn!("Signature refusée pour {}: {}", jwt_user.sub, e);
+            return e.into_response();
+        }
+        Err(e) => {
+            error!("Préparation de la signature interrompue pour {}: {}", jwt_user.sub, e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Error saving signature").into_response();
+        }
+    };
 
     let user = match get_user_by_id(&state, &jwt_user.sub) {
         Ok(Some(user)) => user,
@@ -236,11 +289,23 @@ pub async fn save_signature(
         },
     };
 
-    info!("Adding signature for user {}: {} characters", user.username, payload.signature.len());
-    match add_user_signature(&state, &user.id, &payload.signature) {
+    info!(
+        "Adding {} signature for user {}: {} bytes normalized",
+        if path.is_some() { "vector" } else { "PNG" },
+        user.username,
+        png.len()
+    );
+    match add_user_signature(&state, &user.id, &png, path.as_deref()) {
         Ok(sig) => {
             info!("Signature added successfully for user {} (id: {})", user.username, sig.id);
-            (StatusCode::CREATED, Json(sig)).into_response()
+            (StatusCode::CREATED, Json(UserSignatureResponse::from(sig))).into_response()
+        },
+        Err(diesel::result::Error::DatabaseError(
+            diesel::result::DatabaseErrorKind::UniqueViolation,
+            _,
+        )) => {
+            warn!("Signature déjà enregistrée pour {}", user.username);
+            (StatusCode::CONFLICT, "Signature already saved").into_response()
         },
         Err(e) => {
             error!("Error adding signature for user {}: {:?}", user.username, e);
@@ -249,12 +314,87 @@ pub async fn save_signature(
     }
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/users/me/signatures/generate",
+    description = "Generate signature candidates writing a name (the username by default) with the signature font (SIGNATURE_FONT_PATH; the bundled fallback is an italic serif, not a handwriting font). Nothing is saved: pick candidates and save them with POST /api/users/me/signature",
+    request_body = GenerateSignaturesPayload,
+    responses(
+        (status = 200, description = "Signature candidates", body = GeneratedSignaturesResponse),
+        (status = 400, description = "Invalid name, slant, size or count, or a character missing from the font"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "User not found"),
+    ),
+    tag = "Users"
+)]
+pub async fn generate_signatures(
+    State(state): State<GlobalState>,
+    jwt_user: JwtClaims,
+    Json(payload): Json<GenerateSignaturesPayload>,
+) -> impl IntoResponse {
+    let user = match get_user_by_id(&state, &jwt_user.sub) {
+        Ok(Some(user)) => user,
+        Ok(None) => return (StatusCode::NOT_FOUND, "User not found").into_response(),
+        Err(e) => {
+            error!("Error fetching user for signature generation: {:?}", e);
+            return (StatusCode::NOT_FOUND, "User not found").into_response();
+        },
+    };
+
+    let name = payload.name.as_deref().unwrap_or(&user.username).trim();
+    if name.is_empty() || name.chars().count() > MAX_NAME_CHARS {
+        return (StatusCode::BAD_REQUEST, format!("name must be 1 to {} characters", MAX_NAME_CHARS)).into_response();
+    }
+    let style = TypedSignatureStyle {
+        slant_deg: payload.slant.unwrap_or(DEFAULT_SLANT_DEG),
+        size: payload.size.unwrap_or(DEFAULT_TEXT_SIZE),
+    };
+    if !(-MAX_SLANT_DEG..=MAX_SLANT_DEG).contains(&style.slant_deg) {
+        return (StatusCode::BAD_REQUEST, format!("slant must be between -{0} and {0}", MAX_SLANT_DEG)).into_response();
+    }
+    if !TEXT_SIZE_RANGE.contains(&style.size) {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("size must be between {} and {}", TEXT_SIZE_RANGE.start(), TEXT_SIZE_RANGE.end()),
+        )
+            .into_response();
+    }
+    let count = payload.count.unwrap_or(DEFAULT_CANDIDATES);
+    if !(1..=MAX_CANDIDATES).contains(&count) {
+        return (StatusCode::BAD_REQUEST, format!("count must be between 1 and {}", MAX_CANDIDATES)).into_response();
+    }
+
+    let font = state.signature_font.clone();
+    let text = name.to_string();
+    let generated = tokio::task::spawn_blocking(move || {
+        synthesize_signatures(&font, &text, style, count, &mut rand::thread_rng())
+    })
+    .await;
+    let generated = match generated {
+        Ok(generated) => generated,
+        Err(e) => {
+            error!("Génération de signature interrompue pour {}: {}", user.username, e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Error generating signatures").into_response();
+        },
+    };
+    match generated {
+        Ok(candidates) => {
+            info!("{} signature(s) générée(s) pour {}", candidates.len(), user.username);
+            (StatusCode::OK, Json(GeneratedSignaturesResponse { candidates })).into_response()
+        },
+        Err(e) => {
+            warn!("Génération de signature refusée pour {}: {}", user.username, e);
+            e.into_response()
+        },
+    }
+}
+
 #[utoipa::path(
     get,
     path = "/api/users/me/signatures",
-    description = "List all handwritten signatures for the current user",
+    description = "List all handwritten signatures for the current user: metadata and image URLs, not the images themselves",
     responses(
-        (status = 200, description = "List of signatures", body = Vec<UserSignature>),
+        (status = 200, description = "List of signatures", body = Vec<UserSignatureResponse>),
         (status = 401, description = "Unauthorized"),
     ),
     tag = "Users"
@@ -265,7 +405,10 @@ pub async fn get_signatures(
 ) -> impl IntoResponse {
     let user_id = jwt_user.sub.to_string();
     match get_user_signatures(&state, &user_id) {
-        Ok(sigs) => (StatusCode::OK, Json(sigs)).into_response(),
+        Ok(sigs) => {
+            let sigs: Vec<UserSignatureResponse> = sigs.into_iter().map(UserSignatureResponse::from).collect();
+            (StatusCode::OK, Json(sigs)).into_response()
+        },
         Err(e) => {
             error!("Error fetching signatures: {:?}", e);
             (StatusCode::INTERNAL_SERVER_ERROR, "Error fetching signatures").into_response()
@@ -273,6 +416,36 @@ pub async fn get_signatures(
     }
 }
 
+#[utoipa::path(
+    patch,
+    path = "/api/users/me/signatures/{id}",
+    description = "Rename a handwritten signature or enable/disable it (a disabled signature is kept but never sent)",
+    params(("id" = String, Path, description = "Signature id")),
+    request_body = UpdateSignaturePayload,
+    responses(
+        (status = 200, description = "Signature updated", body = UserSignatureResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Signature not found"),
+    ),
+    tag = "Users"
+)]
+pub async fn update_signature(
+    State(state): State<GlobalState>,
+    jwt_user: JwtClaims,
+    axum::extract::Path(signature_id): axum::extract::Path<String>,
+    Json(payload): Json<UpdateSignaturePayload>,
+) -> impl IntoResponse {
+    let user_id = jwt_user.sub.to_string();
+    match update_user_signature(&state, &signature_id, &user_id, &payload) {
+        Ok(Some(sig)) => (StatusCode::OK, Json(UserSignatureResponse::from(sig))).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Signature not found").into_response(),
+        Err(e) => {
+            error!("Error updating signature: {:?}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Error updating signature").into_response()
+        },
+    }
+}
+
 #[utoipa::path(
     delete,
     path = "/api/users/me/signatures/{id}",
@@ -326,3 +499,131 @@ pub async fn delete_account(
         },
     }
 }
+
+/// Les images d'une signature ne changent jamais (une nouvelle image crée une nouvelle signature).
+const SIGNATURE_CACHE_CONTROL: &str = "private, max-age=31536000, immutable";
+
+/// `If-None-Match` désigne-t-il `etag` (liste d'ETags, préfixe faible `W/` ou `*`) ?
+fn etag_matches(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|candidate| candidate.trim())
+        .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag)
+}
+
+/// Réponse image d'une signature : 304 si le client a déjà cette version, sinon le PNG (produit hors des threads async).
+async fn cached_png(
+    headers: &HeaderMap,
+    etag: String,
+    png: impl FnOnce() -> Result<Vec<u8>, String> + Send + 'static,
+) -> axum::response::Response {
+    let cache_headers = [(header::ETAG, etag.clone()), (header::CACHE_CONTROL, SIGNATURE_CACHE_CONTROL.to_string())];
+    if etag_matches(headers, &etag) {
+        return (StatusCode::NOT_MODIFIED, cache_headers).into_response();
+    }
+    let png = tokio::task::spawn_blocking(png).await.unwrap_or_else(|e| Err(e.to_string()));
+    match png {
+        Ok(png) => (cache_headers, [(header::CONTENT_TYPE, "image/png")], png).into_response(),
+        Err(e) => {
+            error!("Image de signature {} impossible: {}", etag, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Error rendering signature").into_response()
+        },
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/users/me/signatures/{id}/image",
+    description = "Get the stored PNG of a handwritten signature. The ETag is the image's SHA-256; send it back in If-None-Match to get a 304",
+    params(("id" = String, Path, description = "Signature id")),
+    responses(
+        (status = 200, description = "PNG image", content_type = "image/png"),
+        (status = 304, description = "Not modified"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Signature not found"),
+    ),
+    tag = "Users"
+)]
+pub async fn get_signature_image(
+    State(state): State<GlobalState>,
+    jwt_user: JwtClaims,
+    axum::extract::Path(signature_id): axum::extract::Path<String>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let user_id = jwt_user.sub.to_string();
+    match load_signature_image(&state, &signature_id, &user_id) {
+        Ok(Some((png, hash))) => cached_png(&headers, format!("\"{}\"", hash), || Ok(png)).await,
+        Ok(None) => (StatusCode::NOT_FOUND, "Signature not found").into_response(),
+        Err(e) => {
+            error!("Error fetching signature image: {:?}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Error fetching signature").into_response()
+        },
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct RenderSignatureQuery {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/users/me/signatures/{id}/png",
+    description = "Render a handwritten signature as a PNG at the requested size (vector signatures are re-rendered, PNG ones resized; aspect ratio is kept)",
+    params(
+        ("id" = String, Path, description = "Signature id"),
+        ("width" = Option<u32>, Query, description = "Width in pixels (default 800, max 2000)"),
+        ("height" = Option<u32>, Query, description = "Height in pixels (default 300, max 2000)"),
+    ),
+    responses(
+        (status = 200, description = "PNG image", content_type = "image/png"),
+        (status = 304, description = "Not modified (ETag sent in If-None-Match)"),
+        (status = 400, description = "Invalid dimensions"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Signature not found"),
+    ),
+    tag = "Users"
+)]
+pub async fn render_signature_png(
+    State(state): State<GlobalState>,
+    jwt_user: JwtClaims,
+    axum::extract::Path(signature_id): axum::extract::Path<String>,
+    Query(query): Query<RenderSignatureQuery>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let width = query.width.unwrap_or(CANONICAL_WIDTH);
+    let height = query.height.unwrap_or(CANONICAL_HEIGHT);
+    if !(1..=MAX_RENDER_DIMENSION).contains(&width) || !(1..=MAX_RENDER_DIMENSION).contains(&height) {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("width and height must be between 1 and {}", MAX_RENDER_DIMENSION),
+        )
+            .into_response();
+    }
+
+    let user_id = jwt_user.sub.to_string();
+    let signature = match get_user_signature(&state, &signature_id, &user_id) {
+        Ok(Some(sig)) => sig,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Signature not found").into_response(),
+        Err(e) => {
+            error!("Error fetching signature: {:?}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Error fetching signature").into_response();
+        },
+    };
+    // Le PNG stocké n'est chargé que si le client n'a pas déjà ce rendu
+    let etag = format!("\"{}-{}x{}\"", signature.content_hash, width, height);
+    cached_png(&headers, etag, move || {
+        let png = load_signature_image(&state, &signature.id, &user_id)
+            .map_err(|e| e.to_string())?
+            .ok_or("signature supprimée")?
+            .0;
+        render_signature(&signature, &png, width, height)
+            .and_then(|rendered| png_bytes(&rendered))
+            .map_err(|e| format!("rendu {}x{}: {}", width, height, e))
+    })
+    .await
+}
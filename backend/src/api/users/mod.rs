@@ -1,9 +1,14 @@
 pub mod endpoints;
 mod models;
+mod rotation;
 mod services;
+mod signature_font;
+mod signature_image;
+mod signature_vector;
 
 pub use models::User;
+pub use signature_font::load_signature_font;
 pub use services::{
     create_user, get_routes, get_user_by_id, get_user_by_username, get_users_by_ulids, user_exists, get_all_users,
-    get_user_signatures, get_random_signature_for_user, delete_user_account,
+    get_signature_for_submission, has_enabled_signature, delete_user_account, record_signature_use,
 };
@@ -7,6 +7,7 @@ use crate::api::auth::hash_password;
 
 use crate::api::auth::RegisterPayload;
 use crate::api::sign::CookieItem;
+use crate::api::users::signature_image::{THUMBNAIL_HEIGHT, THUMBNAIL_WIDTH};
 
 #[derive(Serialize, Deserialize, Queryable, Insertable, Selectable, ToSchema)]
 #[diesel(table_name = crate::schema::users)]
@@ -25,8 +26,17 @@ pub struct User {
     )]
     pub jwt_intra_epitech: Option<String>,
     pub jwt_expires_at: Option<chrono::NaiveDateTime>,
-    #[schema(example = "data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAA...")]
+    /// Renseigné par `GET /api/users/me` : URL de l'image de la signature la plus récente
+    #[schema(example = "/api/users/me/signatures/01F8MECHZX3TBDSZ7XK4F5G9ZQ/image")]
     pub signature_manuscrite: Option<String>,
+    /// Intensité (0-100) des variations aléatoires appliquées à la signature avant chaque envoi ; 0 = désactivé
+    #[schema(example = 30)]
+    pub signature_variation: i16,
+    /// Stratégie de choix de la signature envoyée (voir `SignatureRotation`)
+    #[schema(example = "round_robin")]
+    pub signature_rotation: String,
+    /// Signature favorite, envoyée avec la stratégie `pinned`
+    pub pinned_signature_id: Option<String>,
 }
 
 impl User {
@@ -38,6 +48,9 @@ impl User {
             jwt_intra_epitech: None,
             jwt_expires_at: None,
             signature_manuscrite: None,
+            signature_variation: 0,
+            signature_rotation: SignatureRotation::Random.as_str().to_string(),
+            pinned_signature_id: None,
         }
     }
 
@@ -87,6 +100,8 @@ pub struct PublicUserResponse {
     pub username: String,
     #[schema(example = "2023-10-01T12:00:00")]
     pub jwt_expires_at: Option<chrono::NaiveDateTime>,
+    /// L'utilisateur a une réservation en attente aujourd'hui (à signer en priorité)
+    pub reserved: bool,
 }
 
 impl From<User> for PublicUserResponse {
@@ -95,6 +110,7 @@ impl From<User> for PublicUserResponse {
             id: user.id,
             username: user.username,
             jwt_expires_at: user.jwt_expires_at,
+            reserved: false,
         }
     }
 }
@@ -103,21 +119,154 @@ pub struct UpdateUserPayload {
     pub username: Option<String>,
     pub old_password: Option<String>,
     pub new_password: Option<String>,
+    /// Intensité des variations de signature (0-100, 0 = désactivé)
+    pub signature_variation: Option<i16>,
+    pub signature_rotation: Option<SignatureRotation>,
+    /// Signature favorite (chaîne vide pour la retirer)
+    pub pinned_signature_id: Option<String>,
+}
+
+/// Choix de la signature envoyée parmi les signatures actives de l'utilisateur.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, ToSchema, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SignatureRotation {
+    /// Chacune à tour de rôle, dans l'ordre de création
+    RoundRobin,
+    /// Celle qui n'a pas servi depuis le plus longtemps
+    LeastRecentlyUsed,
+    /// Au hasard
+    Random,
+    /// Toujours la signature favorite (à défaut, la moins récemment utilisée)
+    Pinned,
 }
 
+impl SignatureRotation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SignatureRotation::RoundRobin => "round_robin",
+            SignatureRotation::LeastRecentlyUsed => "least_recently_used",
+            SignatureRotation::Random => "random",
+            SignatureRotation::Pinned => "pinned",
+        }
+    }
+
+    pub fn from_db(value: &str) -> Self {
+        match value {
+            "round_robin" => SignatureRotation::RoundRobin,
+            "least_recently_used" => SignatureRotation::LeastRecentlyUsed,
+            "pinned" => SignatureRotation::Pinned,
+            _ => SignatureRotation::Random,
+        }
+    }
+}
+
+/// Signature envoyée : PNG et/ou tracé vectoriel. Quand un tracé est fourni, c'est lui qui est conservé
+/// et les PNG en sont rendus ; le PNG n'est alors pas nécessaire.
 #[derive(Deserialize, ToSchema, Debug)]
 pub struct SaveSignaturePayload {
     #[schema(example = "data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAA...")]
-    pub signature: String,
+    pub signature: Option<String>,
+    /// Traits dessinés, chacun une liste de points [x, y] dans le repère du canevas
+    #[schema(example = json!([[[10.0, 20.0], [14.5, 22.0], [20.0, 30.0]]]))]
+    pub strokes: Option<Vec<Vec<[f32; 2]>>>,
+    /// Données de chemin SVG (attribut `d`), alternative à `strokes`
+    #[schema(example = "M10 20 C 40 0, 60 40, 90 20")]
+    pub svg_path: Option<String>,
 }
 
-#[derive(Debug, Queryable, Selectable, Serialize, ToSchema)]
+#[derive(Debug, Clone, Queryable, Selectable, Serialize, ToSchema)]
 #[diesel(table_name = crate::schema::user_signatures)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
 #[serde(rename_all = "camelCase")]
 pub struct UserSignature {
     pub id: String,
     pub user_id: String,
-    pub signature_data: String,
     pub created_at: chrono::NaiveDateTime,
+    #[schema(example = "Signature du matin")]
+    pub label: Option<String>,
+    /// Une signature désactivée est conservée mais n'est plus envoyée
+    pub enabled: bool,
+    pub use_count: i32,
+    pub last_used_at: Option<chrono::NaiveDateTime>,
+    /// Tracé vectoriel (chemin SVG dans le canevas 800x300) ; `None` pour une signature envoyée en PNG
+    #[serde(skip_serializing)]
+    pub signature_path: Option<String>,
+    /// SHA-256 du PNG stocké : une même image n'est enregistrée qu'une fois par utilisateur, sert aussi d'ETag
+    pub content_hash: String,
+}
+
+/// Signature préparée pour une plateforme : son utilisation n'est comptée qu'une fois l'envoi accepté
+/// (`record_signature_use`). Tant qu'elle existe, les autres envois de l'utilisateur attendent : deux envois
+/// simultanés ne choisissent pas la même signature « la moins utilisée ».
+#[derive(Debug)]
+pub struct SubmissionSignature {
+    pub signature_id: String,
+    /// Image à envoyer (data URL PNG)
+    pub data_url: String,
+    pub(crate) _use_guard: tokio::sync::OwnedMutexGuard<()>,
+}
+
+impl UserSignature {
+    pub fn image_url(&self) -> String {
+        format!("/api/users/me/signatures/{}/image", self.id)
+    }
+
+    pub fn thumbnail_url(&self) -> String {
+        format!(
+            "/api/users/me/signatures/{}/png?width={}&height={}",
+            self.id, THUMBNAIL_WIDTH, THUMBNAIL_HEIGHT
+        )
+    }
+}
+
+/// Signature telle que listée : métadonnées et URLs des images, sans les images elles-mêmes.
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UserSignatureResponse {
+    #[serde(flatten)]
+    pub signature: UserSignature,
+    /// Signature enregistrée comme tracé vectoriel
+    pub vector: bool,
+    #[schema(example = "/api/users/me/signatures/01F8MECHZX3TBDSZ7XK4F5G9ZQ/image")]
+    pub image_url: String,
+    #[schema(example = "/api/users/me/signatures/01F8MECHZX3TBDSZ7XK4F5G9ZQ/png?width=240&height=90")]
+    pub thumbnail_url: String,
+}
+
+impl From<UserSignature> for UserSignatureResponse {
+    fn from(signature: UserSignature) -> Self {
+        Self {
+            vector: signature.signature_path.is_some(),
+            image_url: signature.image_url(),
+            thumbnail_url: signature.thumbnail_url(),
+            signature,
+        }
+    }
+}
+
+/// Génération de signatures à partir d'un nom écrit avec la police des signatures.
+#[derive(Deserialize, ToSchema, Debug)]
+pub struct GenerateSignaturesPayload {
+    /// Nom à écrire (par défaut le nom d'utilisateur)
+    #[schema(example = "Jeanne Dupont")]
+    pub name: Option<String>,
+    /// Inclinaison vers la droite en degrés, de -30 à 30 (12 par défaut)
+    pub slant: Option<f32>,
+    /// Hauteur du texte en pixels dans le canevas 800x300, de 40 à 220 (110 par défaut)
+    pub size: Option<f32>,
+    /// Nombre de propositions, de 1 à 8 (4 par défaut)
+    pub count: Option<usize>,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+pub struct GeneratedSignaturesResponse {
+    /// Propositions (data URLs PNG 800x300) à enregistrer avec `POST /api/users/me/signature`
+    pub candidates: Vec<String>,
+}
+
+#[derive(Deserialize, ToSchema, Debug)]
+pub struct UpdateSignaturePayload {
+    /// Libellé (chaîne vide pour le retirer)
+    pub label: Option<String>,
+    pub enabled: Option<bool>,
 }
\ No newline at end of file
@@ -0,0 +1,128 @@
+use rand::{Rng, seq::SliceRandom};
+
+use crate::api::users::models::{SignatureRotation, UserSignature};
+
+/// Signature à envoyer parmi celles de l'utilisateur, selon sa stratégie. Les signatures désactivées sont ignorées.
+pub fn choose_signature<'a>(
+    signatures: &'a [UserSignature],
+    rotation: SignatureRotation,
+    pinned_id: Option<&str>,
+    rng: &mut impl Rng,
+) -> Option<&'a UserSignature> {
+    let mut enabled: Vec<&UserSignature> = signatures.iter().filter(|s| s.enabled).collect();
+    // Ordre de création, l'id départage les signatures créées au même instant
+    enabled.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.id.cmp(&b.id)));
+
+    match rotation {
+        SignatureRotation::Random => enabled.choose(rng).copied(),
+        SignatureRotation::RoundRobin => {
+            // La suivante après la dernière utilisée ; la première si aucune n'a servi
+            let last_used = enabled
+                .iter()
+                .enumerate()
+                .filter_map(|(i, s)| s.last_used_at.map(|at| (at, i)))
+                .max();
+            let next = last_used.map_or(0, |(_, i)| (i + 1) % enabled.len());
+            enabled.get(next).copied()
+        }
+        SignatureRotation::LeastRecentlyUsed => least_recently_used(&enabled),
+        SignatureRotation::Pinned => pinned_id
+            .and_then(|pinned| enabled.iter().find(|s| s.id == pinned).copied())
+            .or_else(|| least_recently_used(&enabled)),
+    }
+}
+
+/// Jamais utilisée d'abord, puis la plus anciennement utilisée ; à égalité, la plus ancienne.
+fn least_recently_used<'a>(enabled: &[&'a UserSignature]) -> Option<&'a UserSignature> {
+    enabled.iter().min_by_key(|s| s.last_used_at).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveDate, NaiveDateTime};
+    use rand::{SeedableRng, rngs::StdRng};
+
+    fn at(day: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 10, day).unwrap().and_hms_opt(9, 0, 0).unwrap()
+    }
+
+    fn signature(id: &str, created: u32, last_used: Option<u32>, enabled: bool) -> UserSignature {
+        UserSignature {
+            id: id.to_string(),
+            user_id: "user".to_string(),
+            created_at: at(created),
+            label: None,
+            enabled,
+            use_count: last_used.map_or(0, |_| 1),
+            last_used_at: last_used.map(at),
+            signature_path: None,
+            content_hash: String::new(),
+        }
+    }
+
+    fn chosen(signatures: &[UserSignature], rotation: SignatureRotation, pinned: Option<&str>) -> Option<String> {
+        let mut rng = StdRng::seed_from_u64(7);
+        choose_signature(signatures, rotation, pinned, &mut rng).map(|s| s.id.clone())
+    }
+
+    /// Simule `n` envois : chaque signature choisie est marquée utilisée le jour suivant.
+    fn sequence(signatures: &mut [UserSignature], rotation: SignatureRotation, n: u32) -> Vec<String> {
+        let mut rng = StdRng::seed_from_u64(7);
+        (0..n)
+            .map(|day| {
+                let id = choose_signature(signatures, rotation, None, &mut rng).unwrap().id.clone();
+                let used = signatures.iter_mut().find(|s| s.id == id).unwrap();
+                used.last_used_at = Some(at(10 + day));
+                used.use_count += 1;
+                id
+            })
+            .collect()
+    }
+
+    #[test]
+    fn round_robin_cycles_in_creation_order() {
+        let mut signatures = vec![
+            signature("b", 2, None, true),
+            signature("a", 1, None, true),
+            signature("c", 3, None, true),
+        ];
+        assert_eq!(
+            sequence(&mut signatures, SignatureRotation::RoundRobin, 5),
+            vec!["a", "b", "c", "a", "b"]
+        );
+    }
+
+    #[test]
+    fn least_recently_used_prefers_unused_then_oldest() {
+        let signatures = vec![
+            signature("a", 1, Some(5), true),
+            signature("b", 2, Some(3), true),
+            signature("c", 3, None, true),
+        ];
+        assert_eq!(chosen(&signatures, SignatureRotation::LeastRecentlyUsed, None).as_deref(), Some("c"));
+        assert_eq!(chosen(&signatures[..2], SignatureRotation::LeastRecentlyUsed, None).as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn disabled_signatures_are_never_chosen() {
+        let signatures = vec![signature("a", 1, None, false), signature("b", 2, Some(4), true)];
+        for rotation in [
+            SignatureRotation::RoundRobin,
+            SignatureRotation::LeastRecentlyUsed,
+            SignatureRotation::Random,
+            SignatureRotation::Pinned,
+        ] {
+            assert_eq!(chosen(&signatures, rotation, Some("a")).as_deref(), Some("b"));
+        }
+        assert_eq!(chosen(&signatures[..1], SignatureRotation::Random, None), None);
+    }
+
+    #[test]
+    fn pinned_falls_back_to_least_recently_used() {
+        let signatures = vec![signature("a", 1, Some(5), true), signature("b", 2, Some(3), true)];
+        assert_eq!(chosen(&signatures, SignatureRotation::Pinned, Some("a")).as_deref(), Some("a"));
+        assert_eq!(chosen(&signatures, SignatureRotation::Pinned, Some("deleted")).as_deref(), Some("b"));
+        assert_eq!(chosen(&signatures, SignatureRotation::Pinned, None).as_deref(), Some("b"));
+    }
+}
@@ -4,10 +4,13 @@ use axum::{
 };
 use chrono::{NaiveDateTime, Utc};
 use diesel::prelude::*;
-use rand::seq::SliceRandom;
+use tracing::warn;
 use ulid::Ulid;
 
-use super::models::{User, UserSignature};
+use super::models::{SignatureRotation, SubmissionSignature, UpdateSignaturePayload, User, UserSignature};
+use super::rotation::choose_signature;
+use super::signature_image::{content_hash as png_content_hash, png_data_url, vary_signature};
+use super::signature_vector::render_signature;
 use crate::{api::auth::RegisterPayload, misc::GlobalState};
 
 pub fn get_routes(state: GlobalState) -> Router {
@@ -19,7 +22,13 @@ pub fn get_routes(state: GlobalState) -> Router {
         .route("/me/update-jwt", post(super::endpoints::update_jwt))
         .route("/me/signature", post(super::endpoints::save_signature))
         .route("/me/signatures", get(super::endpoints::get_signatures))
-        .route("/me/signatures/{id}", delete(super::endpoints::delete_signature))
+        .route("/me/signatures/generate", post(super::endpoints::generate_signatures))
+        .route("/me/signatures/{id}/image", get(super::endpoints::get_signature_image))
+        .route("/me/signatures/{id}/png", get(super::endpoints::render_signature_png))
+        .route(
+            "/me/signatures/{id}",
+            delete(super::endpoints::delete_signature).patch(super::endpoints::update_signature),
+        )
         .with_state(state)
 }
 
@@ -131,7 +140,7 @@ pub fn update_user_jwt(
 
 pub fn get_users_by_ulids(
     state: &GlobalState,
-    user_ids: &Vec<Ulid>,
+    user_ids: &[Ulid],
 ) -> Result<Vec<User>, diesel::result::Error> {
     use crate::schema::users::dsl::*;
     use diesel::prelude::*;
@@ -180,36 +189,37 @@ pub fn update_user(state: &GlobalState, user: &User) -> Result<(), diesel::resul
             jwt_intra_epitech.eq(&user.jwt_intra_epitech),
             jwt_expires_at.eq(&user.jwt_expires_at),
             signature_manuscrite.eq(&user.signature_manuscrite),
+            signature_variation.eq(user.signature_variation),
+            signature_rotation.eq(&user.signature_rotation),
+            pinned_signature_id.eq(&user.pinned_signature_id),
         ))
         .execute(&mut conn)?;
 
     Ok(())
 }
 
+/// Enregistre le PNG d'une signature. Une image déjà enregistrée pour l'utilisateur viole l'index unique
+/// `(user_id, content_hash)`.
 pub fn add_user_signature(
     state: &GlobalState,
     user_id_param: &str,
-    signature_data_param: &str,
+    png: &[u8],
+    signature_path_param: Option<&str>,
 ) -> Result<UserSignature, diesel::result::Error> {
     use crate::schema::user_signatures::dsl::*;
 
     let mut conn = state.get_db_conn().map_err(|_| diesel::result::Error::NotFound)?;
-    let sig_id = Ulid::new().to_string();
-    let now = Utc::now().naive_utc();
     diesel::insert_into(user_signatures)
         .values((
-            id.eq(&sig_id),
+            id.eq(Ulid::new().to_string()),
             user_id.eq(user_id_param),
-            signature_data.eq(signature_data_param),
-            created_at.eq(now),
+            image.eq(png),
+            content_hash.eq(png_content_hash(png)),
+            signature_path.eq(signature_path_param),
+            created_at.eq(Utc::now().naive_utc()),
         ))
-        .execute(&mut conn)?;
-    Ok(UserSignature {
-        id: sig_id,
-        user_id: user_id_param.to_string(),
-        signature_data: signature_data_param.to_string(),
-        created_at: now,
-    })
+        .returning(UserSignature::as_returning())
+        .get_result(&mut conn)
 }
 
 pub fn get_user_signatures(
@@ -226,17 +236,141 @@ pub fn get_user_signatures(
         .load(&mut conn)
 }
 
-pub fn get_random_signature_for_user(
+pub fn get_user_signature(
     state: &GlobalState,
+    signature_id: &str,
     user_id_param: &str,
-) -> Result<Option<String>, diesel::result::Error> {
-    let sigs = get_user_signatures(state, user_id_param)?;
-    if sigs.is_empty() {
-        return Ok(None);
+) -> Result<Option<UserSignature>, diesel::result::Error> {
+    use crate::schema::user_signatures::dsl::*;
+
+    let mut conn = state.get_db_conn().map_err(|_| diesel::result::Error::NotFound)?;
+    user_signatures
+        .filter(id.eq(signature_id))
+        .filter(user_id.eq(user_id_param))
+        .select(UserSignature::as_select())
+        .first(&mut conn)
+        .optional()
+}
+
+/// PNG stocké d'une signature de l'utilisateur et son empreinte. `None` si elle n'existe pas.
+pub fn get_signature_image(
+    state: &GlobalState,
+    signature_id: &str,
+    user_id_param: &str,
+) -> Result<Option<(Vec<u8>, String)>, diesel::result::Error> {
+    use crate::schema::user_signatures::dsl::*;
+
+    let mut conn = state.get_db_conn().map_err(|_| diesel::result::Error::NotFound)?;
+    user_signatures
+        .filter(id.eq(signature_id))
+        .filter(user_id.eq(user_id_param))
+        .select((image, content_hash))
+        .first(&mut conn)
+        .optional()
+}
+
+/// L'utilisateur a-t-il au moins une signature active (envoyable à EDSquare) ?
+pub fn has_enabled_signature(state: &GlobalState, user_id_param: &str) -> Result<bool, diesel::result::Error> {
+    use crate::schema::user_signatures::dsl::*;
+
+    let mut conn = state.get_db_conn().map_err(|_| diesel::result::Error::NotFound)?;
+    diesel::select(diesel::dsl::exists(
+        user_signatures.filter(user_id.eq(user_id_param)).filter(enabled.eq(true)),
+    ))
+    .get_result(&mut conn)
+}
+
+/// Modifie le libellé et/ou l'état d'une signature de l'utilisateur. `None` si elle n'existe pas.
+pub fn update_user_signature(
+    state: &GlobalState,
+    signature_id: &str,
+    user_id_param: &str,
+    payload: &UpdateSignaturePayload,
+) -> Result<Option<UserSignature>, diesel::result::Error> {
+    use crate::schema::user_signatures::dsl::*;
+
+    let mut conn = state.get_db_conn().map_err(|_| diesel::result::Error::NotFound)?;
+    let target = user_signatures.filter(id.eq(signature_id)).filter(user_id.eq(user_id_param));
+    if let Some(new_label) = &payload.label {
+        let new_label = Some(new_label.trim()).filter(|l| !l.is_empty());
+        diesel::update(target).set(label.eq(new_label)).execute(&mut conn)?;
     }
-    let mut rng = rand::thread_rng();
-    let chosen = sigs.choose(&mut rng).map(|s| s.signature_data.clone());
-    Ok(chosen)
+    if let Some(new_enabled) = payload.enabled {
+        diesel::update(target).set(enabled.eq(new_enabled)).execute(&mut conn)?;
+    }
+    target.select(UserSignature::as_select()).first(&mut conn).optional()
+}
+
+/// Choisit la signature à envoyer selon la stratégie de l'utilisateur, sans compter son utilisation.
+pub fn choose_signature_for_user(state: &GlobalState, user: &User) -> Result<Option<UserSignature>, diesel::result::Error> {
+    let sigs = get_user_signatures(state, &user.id)?;
+    let rotation = SignatureRotation::from_db(&user.signature_rotation);
+    Ok(choose_signature(&sigs, rotation, user.pinned_signature_id.as_deref(), &mut rand::thread_rng()).cloned())
+}
+
+/// Compte une utilisation de la signature, une fois l'envoi accepté par la plateforme (rotation « la moins utilisée »,
+/// statistiques).
+pub fn record_signature_use(state: &GlobalState, signature_id: &str) -> Result<(), diesel::result::Error> {
+    use crate::schema::user_signatures::dsl::*;
+
+    let mut conn = state.get_db_conn().map_err(|_| diesel::result::Error::NotFound)?;
+    diesel::update(user_signatures.filter(id.eq(signature_id)))
+        .set((use_count.eq(use_count + 1), last_used_at.eq(Utc::now().naive_utc())))
+        .execute(&mut conn)?;
+    Ok(())
+}
+
+/// Signature à envoyer à une plateforme : choisie selon `signature_rotation`, rendue aux dimensions attendues par
+/// la plateforme puis variée selon `signature_variation`. Si le rendu ou la variation échoue, la signature
+/// stockée est envoyée telle quelle. Le traitement d'image tourne hors des threads async.
+/// Attend la fin de l'envoi précédent du même utilisateur (voir `SubmissionSignature`).
+pub async fn get_signature_for_submission(
+    state: &GlobalState,
+    user: &User,
+    width: u32,
+    height: u32,
+) -> Result<Option<SubmissionSignature>, diesel::result::Error> {
+    // Relâché avec la `SubmissionSignature`, une fois l'utilisation comptée (ou l'envoi abandonné)
+    let use_guard = state.signature_lock(&user.id).lock_owned().await;
+    let Some(chosen) = choose_signature_for_user(state, user)? else {
+        return Ok(None);
+    };
+    let Some((png, _)) = get_signature_image(state, &chosen.id, &user.id)? else {
+        return Ok(None);
+    };
+    let signature_id = chosen.id.clone();
+    let stored = png.clone();
+    let variation = user.signature_variation;
+    let user_label = format!("{} ({})", user.username, user.id);
+    let signature = tokio::task::spawn_blocking(move || {
+        let signature = match render_signature(&chosen, &png, width, height) {
+            Ok(rendered) => rendered,
+            Err(e) => {
+                warn!("Rendu {}x{} de la signature {} impossible: {}", width, height, chosen.id, e);
+                png_data_url(&png)
+            }
+        };
+        if variation <= 0 {
+            return signature;
+        }
+        match vary_signature(&signature, variation, &mut rand::thread_rng()) {
+            Ok(varied) => varied,
+            Err(e) => {
+                warn!("Variation de signature impossible pour {}: {}", user_label, e);
+                signature
+            }
+        }
+    })
+    .await;
+    let data_url = signature.unwrap_or_else(|e| {
+        warn!("Préparation de la signature interrompue pour {} ({}): {}", user.username, user.id, e);
+        png_data_url(&stored)
+    });
+    Ok(Some(SubmissionSignature {
+        signature_id,
+        data_url,
+        _use_guard: use_guard,
+    }))
 }
 
 pub fn delete_user_signature(
@@ -276,6 +410,179 @@ pub fn delete_user_account(
     {
         use crate::schema::users::dsl::*;
         let deleted = diesel::delete(users.filter(id.eq(user_id_param))).execute(&mut conn)?;
-        return Ok(deleted > 0);
+        Ok(deleted > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::{Engine, engine::general_purpose::STANDARD};
+    use diesel::connection::SimpleConnection;
+    use diesel::sql_types::{Nullable, Text};
+
+    use super::*;
+    use crate::misc::test_database_url;
+
+    const SIGNATURE_BLOBS_UP: &str = include_str!("../../../migrations/2026-10-18-170000_signature_blobs/up.sql");
+    const SIGNATURE_BLOBS_DOWN: &str = include_str!("../../../migrations/2026-10-18-170000_signature_blobs/down.sql");
+
+    const PNG_DATA_URL: &str = "data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNkYPhfDwAChwGA60e6kgAAAABJRU5ErkJggg==";
+
+    /// Tables telles qu'avant la migration, dans un schéma jetable : une signature PNG, son doublon épinglé,
+    /// une data URL JPEG, du base64 invalide (épinglé) et un « PNG » qui n'en est pas un.
+    fn pre_blob_fixtures() -> String {
+        format!(
+            r#"
+            CREATE SCHEMA signature_blobs_test;
+            SET LOCAL search_path TO signature_blobs_test;
+            CREATE TABLE users (id TEXT PRIMARY KEY);
+            CREATE TABLE user_signatures (
+                id TEXT PRIMARY KEY,
+                user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                signature_data TEXT NOT NULL,
+                created_at TIMESTAMP NOT NULL DEFAULT now(),
+                label TEXT,
+                enabled BOOLEAN NOT NULL DEFAULT TRUE,
+                use_count INTEGER NOT NULL DEFAULT 0,
+                last_used_at TIMESTAMP,
+                signature_path TEXT
+            );
+            ALTER TABLE users ADD COLUMN pinned_signature_id TEXT REFERENCES user_signatures(id) ON DELETE SET NULL;
+
+            INSERT INTO users (id) VALUES ('u1'), ('u2');
+            INSERT INTO user_signatures (id, user_id, signature_data, created_at) VALUES
+                ('a', 'u1', '{png}', '2026-10-01 09:00'),
+                ('b', 'u1', '{png}', '2026-10-02 09:00'),
+                ('c', 'u1', 'data:image/jpeg;base64,/9j/4AAQSkZJRgABAQAAAQABAAD/2wBDAA==', '2026-10-03 09:00'),
+                ('d', 'u2', 'data:image/png;base64,@@pas du base64@@', '2026-10-04 09:00'),
+                ('e', 'u2', 'data:image/png;base64,aGVsbG8=', '2026-10-05 09:00');
+            UPDATE users SET pinned_signature_id = 'b' WHERE id = 'u1';
+            UPDATE users SET pinned_signature_id = 'd' WHERE id = 'u2';
+            "#,
+            png = PNG_DATA_URL
+        )
+    }
+
+    #[derive(QueryableByName, Debug, PartialEq)]
+    struct Row {
+        #[diesel(sql_type = Text)]
+        id: String,
+        #[diesel(sql_type = Nullable<Text>)]
+        value: Option<String>,
+    }
+
+    fn rows(conn: &mut PgConnection, query: &str) -> Vec<(String, Option<String>)> {
+        diesel::sql_query(query)
+            .load::<Row>(conn)
+            .unwrap()
+            .into_iter()
+            .map(|row| (row.id, row.value))
+            .collect()
+    }
+
+    fn row(id: &str, value: Option<&str>) -> (String, Option<String>) {
+        (id.to_string(), value.map(str::to_string))
+    }
+
+    #[test]
+    fn signature_blobs_migration_sets_aside_unreadable_rows_and_duplicates() {
+        let Some(db_url) = test_database_url() else {
+            return;
+        };
+        let mut conn = PgConnection::establish(&db_url).unwrap();
+        conn.begin_test_transaction().unwrap();
+        conn.batch_execute(&pre_blob_fixtures()).unwrap();
+
+        conn.batch_execute(SIGNATURE_BLOBS_UP).unwrap();
+        let png = STANDARD.decode(PNG_DATA_URL.trim_start_matches("data:image/png;base64,")).unwrap();
+        assert_eq!(
+            rows(&mut conn, "SELECT id, content_hash AS value FROM user_signatures"),
+            vec![row("a", Some(&png_content_hash(&png)))]
+        );
+        assert_eq!(
+            rows(
+                &mut conn,
+                "SELECT id, reason || ':' || coalesce(kept_id, '-') || ':' || was_pinned AS value
+                 FROM user_signatures_blob_backup ORDER BY id"
+            ),
+            vec![
+                row("b", Some("duplicate:a:true")),
+                row("c", Some("unreadable:-:false")),
+                row("d", Some("unreadable:-:true")),
+                row("e", Some("unreadable:-:false")),
+            ]
+        );
+        assert_eq!(
+            rows(&mut conn, "SELECT id, pinned_signature_id AS value FROM users ORDER BY id"),
+            vec![row("u1", Some("a")), row("u2", None)]
+        );
+
+        conn.batch_execute(SIGNATURE_BLOBS_DOWN).unwrap();
+        assert_eq!(
+            rows(&mut conn, "SELECT id, signature_data AS value FROM user_signatures WHERE id IN ('a', 'b', 'd') ORDER BY id"),
+            vec![
+                row("a", Some(PNG_DATA_URL)),
+                row("b", Some(PNG_DATA_URL)),
+                row("d", Some("data:image/png;base64,@@pas du base64@@")),
+            ]
+        );
+        assert_eq!(rows(&mut conn, "SELECT id, NULL AS value FROM user_signatures").len(), 5);
+        assert_eq!(
+            rows(&mut conn, "SELECT id, pinned_signature_id AS value FROM users ORDER BY id"),
+            vec![row("u1", Some("b")), row("u2", Some("d"))]
+        );
+        assert!(
+            rows(
+                &mut conn,
+                "SELECT tablename AS id, NULL AS value FROM pg_tables
+                 WHERE schemaname = 'signature_blobs_test' AND tablename = 'user_signatures_blob_backup'"
+            )
+            .is_empty()
+        );
+    }
+
+    fn solid_png(rgba: [u8; 4]) -> Vec<u8> {
+        let mut png = std::io::Cursor::new(Vec::new());
+        image::RgbaImage::from_pixel(4, 2, image::Rgba(rgba))
+            .write_to(&mut png, image::ImageFormat::Png)
+            .unwrap();
+        png.into_inner()
+    }
+
+    #[tokio::test]
+    async fn concurrent_submissions_of_a_user_do_not_send_the_same_signature() {
+        let Some(db_url) = test_database_url() else {
+            return;
+        };
+        let state = GlobalState::for_tests(
+            &db_url,
+            crate::api::edsquare::EdsquareClient::new("http://127.0.0.1:9").unwrap(),
+        );
+        let mut user = User::new(format!("rotation-{}", Ulid::new()), "password".to_string());
+        user.signature_rotation = SignatureRotation::LeastRecentlyUsed.as_str().to_string();
+        let user_id = user.id.clone();
+        create_user(&state, user).unwrap();
+        let user = get_user_by_id(&state, &Ulid::from_string(&user_id).unwrap()).unwrap().unwrap();
+        for color in [[0, 0, 0, 255], [0, 0, 255, 255]] {
+            add_user_signature(&state, &user_id, &solid_png(color), None).unwrap();
+        }
+
+        let first = get_signature_for_submission(&state, &user, 40, 20).await.unwrap().unwrap();
+        // Le second envoi attend que le premier soit compté
+        let waiting = tokio::time::timeout(
+            std::time::Duration::from_millis(100),
+            get_signature_for_submission(&state, &user, 40, 20),
+        )
+        .await;
+        assert!(waiting.is_err());
+
+        record_signature_use(&state, &first.signature_id).unwrap();
+        let first_id = first.signature_id.clone();
+        drop(first);
+        let second = get_signature_for_submission(&state, &user, 40, 20).await.unwrap().unwrap();
+        assert_ne!(second.signature_id, first_id);
+
+        drop(second);
+        delete_user_account(&state, &user_id).unwrap();
     }
 }
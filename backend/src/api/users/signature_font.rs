@@ -0,0 +1,246 @@
+use std::ops::RangeInclusive;
+
+use ab_glyph::{Font, FontArc, GlyphId, OutlineCurve, Point};
+use rand::{Rng, RngCore};
+use tiny_skia::{FillRule, Paint, PathBuilder, Pixmap, Transform};
+
+use crate::api::users::signature_image::{CANONICAL_HEIGHT, CANONICAL_MARGIN, CANONICAL_WIDTH, SignatureImageError};
+use crate::api::users::signature_vector::encode_pixmap;
+
+/// Police embarquée (DejaVu Serif Condensed Italic, licence dans `assets/fonts`). Ce n'est pas une écriture manuscrite :
+/// elle garde la génération utilisable en développement et dans les tests, `SIGNATURE_FONT_PATH` est attendu en production.
+/// À remplacer par une police manuscrite sous licence SIL OFL (ex: Great Vibes, avec son `OFL.txt` dans `assets/fonts`) ;
+/// `SIGNATURE_FONT_PATH` restera alors une surcharge facultative.
+const BUNDLED_FONT: &[u8] = include_bytes!("../../../assets/fonts/DejaVuSerifCondensed-Italic.ttf");
+
+/// Inclinaison par défaut du texte (degrés, vers la droite) et inclinaison maximale acceptée.
+pub const DEFAULT_SLANT_DEG: f32 = 12.0;
+pub const MAX_SLANT_DEG: f32 = 30.0;
+/// Hauteur du texte (px dans le canevas canonique) par défaut et bornes acceptées.
+pub const DEFAULT_TEXT_SIZE: f32 = 110.0;
+pub const TEXT_SIZE_RANGE: RangeInclusive<f32> = 40.0..=220.0;
+/// Longueur maximale du nom écrit.
+pub const MAX_NAME_CHARS: usize = 40;
+/// Nombre de propositions par défaut et maximal.
+pub const DEFAULT_CANDIDATES: usize = 4;
+pub const MAX_CANDIDATES: usize = 8;
+
+/// Écarts des propositions suivantes par rapport au style demandé.
+const CANDIDATE_SLANT_JITTER_DEG: f32 = 6.0;
+const CANDIDATE_SIZE_JITTER: f32 = 0.1;
+/// Irrégularités de chaque lettre : rotation, ligne de base et espacement (en fraction de la hauteur du texte).
+const GLYPH_ROTATION_JITTER_DEG: f32 = 3.0;
+const GLYPH_BASELINE_JITTER: f32 = 0.04;
+const GLYPH_SPACING_JITTER: f32 = 0.03;
+
+/// Style d'une signature générée depuis un nom.
+#[derive(Debug, Clone, Copy)]
+pub struct TypedSignatureStyle {
+    pub slant_deg: f32,
+    pub size: f32,
+}
+
+/// Police des signatures générées : le fichier `path` s'il est donné, sinon la police embarquée.
+pub fn load_signature_font(path: Option<&str>) -> Result<FontArc, String> {
+    match path {
+        Some(path) => {
+            let data = std::fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+            FontArc::try_from_vec(data).map_err(|e| format!("{}: {}", path, e))
+        }
+        None => FontArc::try_from_slice(BUNDLED_FONT).map_err(|e| e.to_string()),
+    }
+}
+
+/// `count` propositions de signature (data URLs PNG au format canonique) écrivant `name`. La première suit
+/// exactement le style demandé ; les suivantes varient un peu l'inclinaison, la taille et chaque lettre.
+pub fn synthesize_signatures(
+    font: &FontArc,
+    name: &str,
+    style: TypedSignatureStyle,
+    count: usize,
+    rng: &mut impl Rng,
+) -> Result<Vec<String>, SignatureImageError> {
+    if let Some(missing) = name.chars().find(|c| !c.is_whitespace() && font.glyph_id(*c) == GlyphId(0)) {
+        return Err(SignatureImageError::InvalidFormat(format!(
+            "character '{}' is not available in the signature font",
+            missing
+        )));
+    }
+
+    let mut candidates = Vec::with_capacity(count);
+    for i in 0..count {
+        if i == 0 {
+            candidates.push(render_name(font, name, style, None)?);
+            continue;
+        }
+        let varied = TypedSignatureStyle {
+            slant_deg: (style.slant_deg + rng.gen_range(-1.0..=1.0) * CANDIDATE_SLANT_JITTER_DEG)
+                .clamp(-MAX_SLANT_DEG, MAX_SLANT_DEG),
+            size: style.size * (1.0 + rng.gen_range(-1.0..=1.0) * CANDIDATE_SIZE_JITTER),
+        };
+        candidates.push(render_name(font, name, varied, Some(&mut *rng))?);
+    }
+    Ok(candidates)
+}
+
+/// Écrit le nom sur le canevas canonique, centré ; réduit s'il ne tient pas dans la zone utile.
+/// Avec `rng`, chaque lettre est légèrement tournée, décalée et espacée.
+fn render_name(
+    font: &FontArc,
+    name: &str,
+    style: TypedSignatureStyle,
+    mut rng: Option<&mut dyn RngCore>,
+) -> Result<String, SignatureImageError> {
+    let scale = style.size / font.height_unscaled();
+    let shear = style.slant_deg.to_radians().tan();
+    let mut builder = PathBuilder::new();
+    let mut pen_x = 0.0;
+    let mut previous: Option<GlyphId> = None;
+    for c in name.chars() {
+        let id = font.glyph_id(c);
+        if let Some(previous) = previous {
+            pen_x += font.kern_unscaled(previous, id) * scale;
+        }
+        let (angle, baseline, spacing) = match rng.as_deref_mut() {
+            Some(rng) => (
+                rng.gen_range(-1.0..=1.0) * GLYPH_ROTATION_JITTER_DEG.to_radians(),
+                rng.gen_range(-1.0..=1.0) * GLYPH_BASELINE_JITTER * style.size,
+                rng.gen_range(-1.0..=1.0) * GLYPH_SPACING_JITTER * style.size,
+            ),
+            None => (0.0, 0.0, 0.0),
+        };
+        if let Some(outline) = font.outline(id) {
+            let (sin, cos) = angle.sin_cos();
+            // Unités de la police (y vers le haut) → pixels (y vers le bas), inclinaison puis rotation de la lettre
+            let place = |p: Point| {
+                let (x, y) = (p.x * scale, -p.y * scale);
+                let x = x - y * shear;
+                (pen_x + x * cos - y * sin, baseline + x * sin + y * cos)
+            };
+            add_outline(&mut builder, &outline.curves, place);
+        }
+        pen_x += font.h_advance_unscaled(id) * scale + spacing;
+        previous = Some(id);
+    }
+
+    let path = builder.finish().ok_or(SignatureImageError::Blank)?;
+    let bounds = path.compute_tight_bounds().ok_or(SignatureImageError::Blank)?;
+    let max_width = (CANONICAL_WIDTH - 2 * CANONICAL_MARGIN) as f32;
+    let max_height = (CANONICAL_HEIGHT - 2 * CANONICAL_MARGIN) as f32;
+    let fit = (max_width / bounds.width()).min(max_height / bounds.height()).min(1.0);
+    let dx = CANONICAL_WIDTH as f32 / 2.0 - (bounds.left() + bounds.width() / 2.0) * fit;
+    let dy = CANONICAL_HEIGHT as f32 / 2.0 - (bounds.top() + bounds.height() / 2.0) * fit;
+
+    let mut pixmap = Pixmap::new(CANONICAL_WIDTH, CANONICAL_HEIGHT)
+        .ok_or_else(|| SignatureImageError::InvalidFormat("rendering failed".to_string()))?;
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(0, 0, 0, 255);
+    pixmap.fill_path(&path, &paint, FillRule::Winding, Transform::from_row(fit, 0.0, 0.0, fit, dx, dy), None);
+    encode_pixmap(&pixmap)
+}
+
+/// Ajoute les contours d'une lettre ; une courbe qui ne part pas de la fin de la précédente ouvre un nouveau contour.
+fn add_outline(builder: &mut PathBuilder, curves: &[OutlineCurve], place: impl Fn(Point) -> (f32, f32)) {
+    let mut last: Option<Point> = None;
+    for curve in curves {
+        let (OutlineCurve::Line(start, _) | OutlineCurve::Quad(start, ..) | OutlineCurve::Cubic(start, ..)) = curve;
+        if last != Some(*start) {
+            if last.is_some() {
+                builder.close();
+            }
+            let (x, y) = place(*start);
+            builder.move_to(x, y);
+        }
+        let end = match curve {
+            OutlineCurve::Line(_, p) => {
+                let (x, y) = place(*p);
+                builder.line_to(x, y);
+                *p
+            }
+            OutlineCurve::Quad(_, c, p) => {
+                let ((cx, cy), (x, y)) = (place(*c), place(*p));
+                builder.quad_to(cx, cy, x, y);
+                *p
+            }
+            OutlineCurve::Cubic(_, c1, c2, p) => {
+                let ((c1x, c1y), (c2x, c2y), (x, y)) = (place(*c1), place(*c2), place(*p));
+                builder.cubic_to(c1x, c1y, c2x, c2y, x, y);
+                *p
+            }
+        };
+        last = Some(end);
+    }
+    if last.is_some() {
+        builder.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::users::signature_image::png_bytes;
+    use rand::{SeedableRng, rngs::StdRng};
+
+    fn style() -> TypedSignatureStyle {
+        TypedSignatureStyle {
+            slant_deg: DEFAULT_SLANT_DEG,
+            size: DEFAULT_TEXT_SIZE,
+        }
+    }
+
+    /// Largeur et hauteur de l'encre d'une proposition.
+    fn ink_extent(data_url: &str) -> (u32, u32) {
+        let image = image::load_from_memory(&png_bytes(data_url).unwrap()).unwrap().to_rgba8();
+        assert_eq!(image.dimensions(), (CANONICAL_WIDTH, CANONICAL_HEIGHT));
+        let inked: Vec<(u32, u32)> = image
+            .enumerate_pixels()
+            .filter(|(_, _, p)| p[3] > 128)
+            .map(|(x, y, _)| (x, y))
+            .collect();
+        let width = inked.iter().map(|p| p.0).max().unwrap() - inked.iter().map(|p| p.0).min().unwrap();
+        let height = inked.iter().map(|p| p.1).max().unwrap() - inked.iter().map(|p| p.1).min().unwrap();
+        (width, height)
+    }
+
+    #[test]
+    fn generates_distinct_candidates_from_the_bundled_font() {
+        let font = load_signature_font(None).unwrap();
+        let mut rng = StdRng::seed_from_u64(3);
+        let candidates = synthesize_signatures(&font, "Jeanne Dupont", style(), 3, &mut rng).unwrap();
+        assert_eq!(candidates.len(), 3);
+        assert_ne!(candidates[0], candidates[1]);
+        assert_ne!(candidates[1], candidates[2]);
+        for candidate in &candidates {
+            let (width, height) = ink_extent(candidate);
+            assert!(width > 200 && height > 40, "{}x{}", width, height);
+        }
+        // Sans variation, le même style donne la même image
+        let again = synthesize_signatures(&font, "Jeanne Dupont", style(), 1, &mut rng).unwrap();
+        assert_eq!(again[0], candidates[0]);
+    }
+
+    #[test]
+    fn long_names_are_shrunk_to_fit() {
+        let font = load_signature_font(None).unwrap();
+        let mut rng = StdRng::seed_from_u64(3);
+        let name = "Maximilienne de La Tour-Montgommery";
+        let large = TypedSignatureStyle {
+            size: *TEXT_SIZE_RANGE.end(),
+            ..style()
+        };
+        let (width, height) = ink_extent(&synthesize_signatures(&font, name, large, 1, &mut rng).unwrap()[0]);
+        assert!(width <= CANONICAL_WIDTH - 2 * CANONICAL_MARGIN, "width {}", width);
+        assert!(height < 100, "height {}", height);
+    }
+
+    #[test]
+    fn rejects_missing_glyphs_and_bad_font_files() {
+        let font = load_signature_font(None).unwrap();
+        let mut rng = StdRng::seed_from_u64(3);
+        assert!(matches!(
+            synthesize_signatures(&font, "Jean \u{1F600}", style(), 1, &mut rng),
+            Err(SignatureImageError::InvalidFormat(_))
+        ));
+        assert!(load_signature_font(Some("/nonexistent/font.ttf")).is_err());
+    }
+}
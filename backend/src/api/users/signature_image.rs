@@ -0,0 +1,388 @@
+use std::{fmt, io::Cursor};
+
+use axum::response::{IntoResponse, Response};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use http::StatusCode;
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use image::{
+    ExtendedColorType, ImageEncoder, ImageFormat, ImageReader, Limits, Rgba, RgbaImage,
+    codecs::png::{CompressionType, FilterType as PngFilterType, PngEncoder},
+    imageops::{self, FilterType},
+};
+
+const DATA_URL_PREFIX: &str = "data:image/png;base64,";
+
+/// Taille maximale du PNG reçu (octets décodés).
+pub const MAX_SIGNATURE_BYTES: usize = 1024 * 1024;
+/// Largeur / hauteur maximale du PNG reçu.
+pub const MAX_SIGNATURE_DIMENSION: u32 = 4000;
+
+/// Format canonique stocké : la taille du canevas du frontend, encre noire sur fond transparent.
+pub const CANONICAL_WIDTH: u32 = 800;
+pub const CANONICAL_HEIGHT: u32 = 300;
+/// Marge laissée autour de la signature recadrée.
+pub(super) const CANONICAL_MARGIN: u32 = 20;
+/// Agrandissement maximal d'une petite signature.
+pub(super) const MAX_UPSCALE: f32 = 3.0;
+
+/// Couverture d'encre (0-255) à partir de laquelle un pixel compte comme dessiné.
+const INK_THRESHOLD: u8 = 48;
+/// En dessous, le dessin est considéré comme vide (canevas vierge, simple point...).
+const MIN_INK_PIXELS: usize = 150;
+/// Taille minimale (px) du plus grand côté de la zone dessinée.
+pub(super) const MIN_INK_EXTENT: u32 = 20;
+
+/// Taille maximale d'un PNG rendu à la demande.
+pub const MAX_RENDER_DIMENSION: u32 = 2000;
+/// Taille des vignettes des listes de signatures.
+pub const THUMBNAIL_WIDTH: u32 = 240;
+pub const THUMBNAIL_HEIGHT: u32 = 90;
+
+/// Intensité maximale des variations (`users.signature_variation`).
+pub const MAX_SIGNATURE_VARIATION: i16 = 100;
+/// Amplitudes des variations à intensité maximale.
+const VARIATION_MAX_ROTATION_DEG: f32 = 4.0;
+const VARIATION_MAX_SCALE: f32 = 0.08;
+const VARIATION_MAX_OFFSET: f32 = 0.03;
+/// Déplacement maximal (px) du tracé par l'ondulation, et longueurs d'onde possibles.
+const VARIATION_MAX_JITTER: f32 = 2.0;
+const VARIATION_WAVELENGTHS: std::ops::Range<f32> = 40.0..90.0;
+
+/// Refus d'une signature envoyée par l'utilisateur.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SignatureImageError {
+    /// Pas une data URL PNG en base64
+    InvalidFormat(String),
+    /// Fichier ou dimensions au-delà des limites
+    TooLarge(String),
+    /// Canevas vide ou presque
+    Blank,
+}
+
+impl fmt::Display for SignatureImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignatureImageError::InvalidFormat(msg) => write!(f, "Invalid signature format: {}", msg),
+            SignatureImageError::TooLarge(msg) => write!(f, "Signature too large: {}", msg),
+            SignatureImageError::Blank => write!(f, "Signature is blank or nearly blank"),
+        }
+    }
+}
+
+impl std::error::Error for SignatureImageError {}
+
+impl IntoResponse for SignatureImageError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            SignatureImageError::TooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            SignatureImageError::InvalidFormat(_) | SignatureImageError::Blank => StatusCode::BAD_REQUEST,
+        };
+        (status, self.to_string()).into_response()
+    }
+}
+
+/// Valide la signature (data URL PNG) et la normalise : recadrée sur l'encre, mise à l'échelle et centrée
+/// sur un canevas `CANONICAL_WIDTH` x `CANONICAL_HEIGHT`, encre noire sur fond transparent.
+/// Retourne la nouvelle data URL PNG, celle qui est stockée puis envoyée à EDSquare.
+pub fn normalize_signature(data_url: &str) -> Result<String, SignatureImageError> {
+    let encoded = data_url
+        .strip_prefix(DATA_URL_PREFIX)
+        .ok_or_else(|| SignatureImageError::InvalidFormat("expected PNG base64 data URL".to_string()))?;
+    // 4 caractères base64 pour 3 octets : inutile de décoder au-delà
+    if encoded.len() > MAX_SIGNATURE_BYTES.div_ceil(3) * 4 {
+        return Err(SignatureImageError::TooLarge(format!("max {} bytes", MAX_SIGNATURE_BYTES)));
+    }
+    let bytes = STANDARD
+        .decode(encoded.trim())
+        .map_err(|e| SignatureImageError::InvalidFormat(format!("invalid base64 ({})", e)))?;
+    if bytes.len() > MAX_SIGNATURE_BYTES {
+        return Err(SignatureImageError::TooLarge(format!("max {} bytes", MAX_SIGNATURE_BYTES)));
+    }
+
+    let image = decode_png(&bytes)?;
+    let coverage = ink_coverage(&image);
+    let (x, y, width, height) = ink_bounds(&coverage).ok_or(SignatureImageError::Blank)?;
+    let cropped = imageops::crop_imm(&coverage, x, y, width, height).to_image();
+    encode_png(&fit_on_canvas(&cropped))
+}
+
+fn decode_png(bytes: &[u8]) -> Result<RgbaImage, SignatureImageError> {
+    let mut reader = ImageReader::with_format(Cursor::new(bytes), ImageFormat::Png);
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_SIGNATURE_DIMENSION);
+    limits.max_image_height = Some(MAX_SIGNATURE_DIMENSION);
+    reader.limits(limits);
+    match reader.decode() {
+        Ok(image) => Ok(image.to_rgba8()),
+        Err(image::ImageError::Limits(_)) => Err(SignatureImageError::TooLarge(format!(
+            "max {}x{} pixels",
+            MAX_SIGNATURE_DIMENSION, MAX_SIGNATURE_DIMENSION
+        ))),
+        Err(e) => Err(SignatureImageError::InvalidFormat(format!("unreadable PNG ({})", e))),
+    }
+}
+
+/// Couverture d'encre de chaque pixel (encre noire, alpha = couverture) : un pixel foncé et opaque compte,
+/// un fond transparent ou blanc non. Les signatures dessinées en couleur ou sur fond blanc sont ainsi ramenées
+/// au même format.
+fn ink_coverage(image: &RgbaImage) -> RgbaImage {
+    RgbaImage::from_fn(image.width(), image.height(), |x, y| {
+        let Rgba([r, g, b, a]) = *image.get_pixel(x, y);
+        let luminance = (299 * r as u32 + 587 * g as u32 + 114 * b as u32) / 1000;
+        let coverage = a as u32 * (255 - luminance) / 255;
+        Rgba([0, 0, 0, coverage as u8])
+    })
+}
+
+/// Rectangle (x, y, largeur, hauteur) qui contient l'encre ; `None` si le dessin est vide ou presque.
+fn ink_bounds(coverage: &RgbaImage) -> Option<(u32, u32, u32, u32)> {
+    let mut ink_pixels = 0;
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (u32::MAX, u32::MAX, 0, 0);
+    for (x, y, pixel) in coverage.enumerate_pixels() {
+        if pixel[3] >= INK_THRESHOLD {
+            ink_pixels += 1;
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+        }
+    }
+    if ink_pixels < MIN_INK_PIXELS {
+        return None;
+    }
+    let (width, height) = (max_x - min_x + 1, max_y - min_y + 1);
+    if width.max(height) < MIN_INK_EXTENT {
+        return None;
+    }
+    Some((min_x, min_y, width, height))
+}
+
+/// Met la signature recadrée à l'échelle (sans déformation) et la centre sur le canevas canonique.
+fn fit_on_canvas(cropped: &RgbaImage) -> RgbaImage {
+    let max_width = CANONICAL_WIDTH - 2 * CANONICAL_MARGIN;
+    let max_height = CANONICAL_HEIGHT - 2 * CANONICAL_MARGIN;
+    let scale = (max_width as f32 / cropped.width() as f32)
+        .min(max_height as f32 / cropped.height() as f32)
+        .min(MAX_UPSCALE);
+    let width = ((cropped.width() as f32 * scale).round() as u32).clamp(1, max_width);
+    let height = ((cropped.height() as f32 * scale).round() as u32).clamp(1, max_height);
+    let resized = imageops::resize(cropped, width, height, FilterType::Lanczos3);
+
+    let mut canvas = RgbaImage::new(CANONICAL_WIDTH, CANONICAL_HEIGHT);
+    let x = (CANONICAL_WIDTH - width) / 2;
+    let y = (CANONICAL_HEIGHT - height) / 2;
+    imageops::overlay(&mut canvas, &resized, x as i64, y as i64);
+    canvas
+}
+
+/// Applique à une signature stockée de petites variations aléatoires (rotation, échelle, décalage et ondulation
+/// du tracé) pour que chaque envoi produise un PNG différent. `strength` va de 0 (aucune variation) à 100.
+pub fn vary_signature(data_url: &str, strength: i16, rng: &mut impl Rng) -> Result<String, SignatureImageError> {
+    let image = decode_png(&png_bytes(data_url)?)?;
+
+    let s = strength.clamp(0, MAX_SIGNATURE_VARIATION) as f32 / MAX_SIGNATURE_VARIATION as f32;
+    let mut amount = |max: f32| rng.gen_range(-1.0..=1.0) * max * s;
+    let angle = amount(VARIATION_MAX_ROTATION_DEG).to_radians();
+    let scale = 1.0 + amount(VARIATION_MAX_SCALE);
+    let offset_x = amount(VARIATION_MAX_OFFSET) * image.width() as f32;
+    let offset_y = amount(VARIATION_MAX_OFFSET) * image.height() as f32;
+    let jitter = VARIATION_MAX_JITTER * s;
+    let wave_x = (rng.gen_range(VARIATION_WAVELENGTHS), rng.gen_range(0.0..std::f32::consts::TAU));
+    let wave_y = (rng.gen_range(VARIATION_WAVELENGTHS), rng.gen_range(0.0..std::f32::consts::TAU));
+
+    // Transformation inverse : pour chaque pixel produit, position lue dans la signature d'origine
+    let (center_x, center_y) = (image.width() as f32 / 2.0, image.height() as f32 / 2.0);
+    let (sin, cos) = angle.sin_cos();
+    let varied = RgbaImage::from_fn(image.width(), image.height(), |x, y| {
+        let dx = (x as f32 - center_x - offset_x) / scale;
+        let dy = (y as f32 - center_y - offset_y) / scale;
+        let source_x = center_x + dx * cos + dy * sin;
+        let source_y = center_y - dx * sin + dy * cos;
+        let source_x = source_x + jitter * (source_y * std::f32::consts::TAU / wave_x.0 + wave_x.1).sin();
+        let source_y = source_y + jitter * (source_x * std::f32::consts::TAU / wave_y.0 + wave_y.1).sin();
+        sample_bilinear(&image, source_x, source_y)
+    });
+    encode_png(&varied)
+}
+
+/// Pixel interpolé ; transparent en dehors de l'image.
+fn sample_bilinear(image: &RgbaImage, x: f32, y: f32) -> Rgba<u8> {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let pixel = |px: f32, py: f32| -> [f32; 4] {
+        if px < 0.0 || py < 0.0 || px >= image.width() as f32 || py >= image.height() as f32 {
+            return [0.0; 4];
+        }
+        image.get_pixel(px as u32, py as u32).0.map(f32::from)
+    };
+    let (a, b, c, d) = (pixel(x0, y0), pixel(x0 + 1.0, y0), pixel(x0, y0 + 1.0), pixel(x0 + 1.0, y0 + 1.0));
+    Rgba(std::array::from_fn(|i| {
+        let top = a[i] + (b[i] - a[i]) * fx;
+        let bottom = c[i] + (d[i] - c[i]) * fx;
+        (top + (bottom - top) * fy).round().clamp(0.0, 255.0) as u8
+    }))
+}
+
+/// Met une signature stockée en PNG aux dimensions demandées, sans déformation, centrée sur fond transparent.
+pub fn resize_signature(png: &[u8], width: u32, height: u32) -> Result<String, SignatureImageError> {
+    let image = decode_png(png)?;
+    if image.dimensions() == (width, height) {
+        return Ok(png_data_url(png));
+    }
+    let scale = (width as f32 / image.width() as f32).min(height as f32 / image.height() as f32);
+    let resized_width = ((image.width() as f32 * scale).round() as u32).clamp(1, width);
+    let resized_height = ((image.height() as f32 * scale).round() as u32).clamp(1, height);
+    let resized = imageops::resize(&image, resized_width, resized_height, FilterType::Lanczos3);
+
+    let mut canvas = RgbaImage::new(width, height);
+    imageops::overlay(
+        &mut canvas,
+        &resized,
+        ((width - resized_width) / 2) as i64,
+        ((height - resized_height) / 2) as i64,
+    );
+    encode_png(&canvas)
+}
+
+/// Data URL d'un PNG stocké.
+pub fn png_data_url(png: &[u8]) -> String {
+    format!("{}{}", DATA_URL_PREFIX, STANDARD.encode(png))
+}
+
+/// Empreinte SHA-256 (hexadécimal) d'un PNG : détecte les doublons et sert d'ETag.
+pub fn content_hash(png: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(png))
+}
+
+/// Octets PNG d'une data URL.
+pub fn png_bytes(data_url: &str) -> Result<Vec<u8>, SignatureImageError> {
+    let encoded = data_url
+        .strip_prefix(DATA_URL_PREFIX)
+        .ok_or_else(|| SignatureImageError::InvalidFormat("expected PNG base64 data URL".to_string()))?;
+    STANDARD
+        .decode(encoded.trim())
+        .map_err(|e| SignatureImageError::InvalidFormat(format!("invalid base64 ({})", e)))
+}
+
+pub(super) fn encode_png(image: &RgbaImage) -> Result<String, SignatureImageError> {
+    let mut png = Vec::new();
+    PngEncoder::new_with_quality(&mut png, CompressionType::Best, PngFilterType::Adaptive)
+        .write_image(image.as_raw(), image.width(), image.height(), ExtendedColorType::Rgba8)
+        .map_err(|e| SignatureImageError::InvalidFormat(format!("PNG encoding failed ({})", e)))?;
+    Ok(png_data_url(&png))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data_url(image: &RgbaImage) -> String {
+        let mut png = Vec::new();
+        PngEncoder::new(&mut png)
+            .write_image(image.as_raw(), image.width(), image.height(), ExtendedColorType::Rgba8)
+            .unwrap();
+        format!("{}{}", DATA_URL_PREFIX, STANDARD.encode(png))
+    }
+
+    fn decode(data_url: &str) -> RgbaImage {
+        let bytes = STANDARD.decode(data_url.strip_prefix(DATA_URL_PREFIX).unwrap()).unwrap();
+        decode_png(&bytes).unwrap()
+    }
+
+    /// Canevas transparent avec un trait noir horizontal de 3px d'épaisseur.
+    fn stroke(width: u32, height: u32, from: u32, to: u32) -> RgbaImage {
+        let mut image = RgbaImage::new(width, height);
+        for x in from..to {
+            for y in height / 2..height / 2 + 3 {
+                image.put_pixel(x, y, Rgba([0, 0, 0, 255]));
+            }
+        }
+        image
+    }
+
+    #[test]
+    fn crops_and_centers_on_canonical_canvas() {
+        let normalized = normalize_signature(&data_url(&stroke(800, 300, 10, 310))).unwrap();
+        let image = decode(&normalized);
+        assert_eq!(image.dimensions(), (CANONICAL_WIDTH, CANONICAL_HEIGHT));
+
+        let (x, y, width, height) = ink_bounds(&image).unwrap();
+        // Trait de 300px agrandi jusqu'à la largeur utile, centré
+        assert!(width >= CANONICAL_WIDTH - 2 * CANONICAL_MARGIN - 2, "width {}", width);
+        assert!(x.abs_diff(CANONICAL_WIDTH - x - width) <= 2);
+        assert!(y.abs_diff(CANONICAL_HEIGHT - y - height) <= 2);
+    }
+
+    #[test]
+    fn white_background_becomes_transparent() {
+        let mut image = stroke(400, 200, 50, 350);
+        for pixel in image.pixels_mut().filter(|p| p[3] == 0) {
+            *pixel = Rgba([255, 255, 255, 255]);
+        }
+        let normalized = decode(&normalize_signature(&data_url(&image)).unwrap());
+        assert_eq!(normalized.get_pixel(0, 0)[3], 0);
+    }
+
+    #[test]
+    fn rejects_blank_and_invalid_signatures() {
+        assert_eq!(
+            normalize_signature(&data_url(&RgbaImage::new(800, 300))),
+            Err(SignatureImageError::Blank)
+        );
+        // Un simple point
+        assert_eq!(
+            normalize_signature(&data_url(&stroke(800, 300, 100, 105))),
+            Err(SignatureImageError::Blank)
+        );
+        assert!(matches!(
+            normalize_signature("data:image/jpeg;base64,AAAA"),
+            Err(SignatureImageError::InvalidFormat(_))
+        ));
+        assert!(matches!(
+            normalize_signature("data:image/png;base64,bm90IGEgcG5n"),
+            Err(SignatureImageError::InvalidFormat(_))
+        ));
+        assert!(matches!(
+            normalize_signature(&data_url(&RgbaImage::new(MAX_SIGNATURE_DIMENSION + 1, 10))),
+            Err(SignatureImageError::TooLarge(_))
+        ));
+    }
+
+    #[test]
+    fn variations_differ_but_keep_the_signature() {
+        use rand::{SeedableRng, rngs::StdRng};
+
+        let original = normalize_signature(&data_url(&stroke(800, 300, 10, 310))).unwrap();
+        let mut rng = StdRng::seed_from_u64(42);
+        let first = vary_signature(&original, 60, &mut rng).unwrap();
+        let second = vary_signature(&original, 60, &mut rng).unwrap();
+        assert_ne!(first, original);
+        assert_ne!(first, second);
+
+        let ink = |data_url: &str| decode(data_url).pixels().filter(|p| p[3] >= INK_THRESHOLD).count() as f32;
+        let varied = decode(&first);
+        assert_eq!(varied.dimensions(), (CANONICAL_WIDTH, CANONICAL_HEIGHT));
+        let ratio = ink(&first) / ink(&original);
+        assert!((0.7..1.3).contains(&ratio), "ink ratio {}", ratio);
+
+        // Intensité nulle : même tracé, à l'interpolation près
+        let unchanged = decode(&vary_signature(&original, 0, &mut rng).unwrap());
+        assert_eq!(unchanged, decode(&original));
+    }
+
+    #[test]
+    fn stored_png_round_trips_and_hashes_by_content() {
+        let normalized = normalize_signature(&data_url(&stroke(800, 300, 10, 310))).unwrap();
+        let png = png_bytes(&normalized).unwrap();
+        assert_eq!(png_data_url(&png), normalized);
+        // Déjà aux bonnes dimensions : le PNG stocké est renvoyé tel quel
+        assert_eq!(resize_signature(&png, CANONICAL_WIDTH, CANONICAL_HEIGHT).unwrap(), normalized);
+
+        let hash = content_hash(&png);
+        assert_eq!(hash.len(), 64);
+        assert_eq!(hash, content_hash(&png.clone()));
+        let other = png_bytes(&normalize_signature(&data_url(&stroke(800, 300, 10, 200))).unwrap()).unwrap();
+        assert_ne!(content_hash(&other), hash);
+    }
+}
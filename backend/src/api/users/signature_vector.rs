@@ -0,0 +1,276 @@
+use std::fmt::Write;
+
+use image::RgbaImage;
+use svgtypes::{SimplePathSegment, SimplifyingPathParser};
+use tiny_skia::{LineCap, LineJoin, Paint, Path, PathBuilder, PathSegment, Pixmap, Stroke, Transform};
+
+use crate::api::users::models::{SaveSignaturePayload, UserSignature};
+use crate::api::users::signature_image::{
+    CANONICAL_HEIGHT, CANONICAL_MARGIN, CANONICAL_WIDTH, MAX_SIGNATURE_BYTES, MAX_UPSCALE, MIN_INK_EXTENT,
+    SignatureImageError, encode_png, normalize_signature, resize_signature,
+};
+
+/// Nombre maximal de points (ou segments) d'une signature vectorielle.
+const MAX_PATH_POINTS: usize = 20_000;
+/// Épaisseur du trait dans le canevas canonique ; mise à l'échelle avec le reste au rendu.
+const STROKE_WIDTH: f32 = 3.0;
+
+/// Prépare la signature reçue : `(PNG canonique, tracé vectoriel)`. Un tracé (`strokes` ou `svg_path`) est
+/// recadré et centré dans le canevas canonique puis rendu en PNG ; sinon le PNG est normalisé et il n'y a pas de tracé.
+pub fn prepare_signature(payload: &SaveSignaturePayload) -> Result<(String, Option<String>), SignatureImageError> {
+    let path = if let Some(strokes) = &payload.strokes {
+        normalize_strokes(strokes)?
+    } else if let Some(svg_path) = &payload.svg_path {
+        normalize_svg_path(svg_path)?
+    } else if let Some(signature) = &payload.signature {
+        return Ok((normalize_signature(signature)?, None));
+    } else {
+        return Err(SignatureImageError::InvalidFormat(
+            "expected signature, strokes or svg_path".to_string(),
+        ));
+    };
+    let png = render_path(&path, CANONICAL_WIDTH, CANONICAL_HEIGHT)?;
+    Ok((png, Some(path)))
+}
+
+/// PNG de la signature aux dimensions demandées : rendu depuis le tracé s'il existe, sinon PNG stocké (`png`)
+/// redimensionné.
+pub fn render_signature(
+    signature: &UserSignature,
+    png: &[u8],
+    width: u32,
+    height: u32,
+) -> Result<String, SignatureImageError> {
+    match &signature.signature_path {
+        Some(path) => render_path(path, width, height),
+        None => resize_signature(png, width, height),
+    }
+}
+
+/// Traits dessinés (listes de points) → chemin SVG canonique.
+fn normalize_strokes(strokes: &[Vec<[f32; 2]>]) -> Result<String, SignatureImageError> {
+    let points: usize = strokes.iter().map(Vec::len).sum();
+    if points > MAX_PATH_POINTS {
+        return Err(SignatureImageError::TooLarge(format!("max {} points", MAX_PATH_POINTS)));
+    }
+    if strokes.iter().flatten().flatten().any(|c| !c.is_finite()) {
+        return Err(SignatureImageError::InvalidFormat("non-finite stroke coordinate".to_string()));
+    }
+
+    let mut builder = PathBuilder::new();
+    for stroke in strokes {
+        let Some((first, rest)) = stroke.split_first() else {
+            continue;
+        };
+        builder.move_to(first[0], first[1]);
+        // Un simple appui reste visible : segment nul, dessiné en point par l'extrémité arrondie
+        if rest.is_empty() {
+            builder.line_to(first[0], first[1]);
+        }
+        for point in rest {
+            builder.line_to(point[0], point[1]);
+        }
+    }
+    fit_path(builder.finish())
+}
+
+/// Données de chemin SVG (`d`) → chemin SVG canonique.
+fn normalize_svg_path(data: &str) -> Result<String, SignatureImageError> {
+    if data.len() > MAX_SIGNATURE_BYTES {
+        return Err(SignatureImageError::TooLarge(format!("max {} bytes", MAX_SIGNATURE_BYTES)));
+    }
+    let path = parse_path(data)?;
+    if path.as_ref().is_some_and(|p| p.len() > MAX_PATH_POINTS) {
+        return Err(SignatureImageError::TooLarge(format!("max {} points", MAX_PATH_POINTS)));
+    }
+    fit_path(path)
+}
+
+fn parse_path(data: &str) -> Result<Option<Path>, SignatureImageError> {
+    let mut builder = PathBuilder::new();
+    for segment in SimplifyingPathParser::from(data) {
+        match segment.map_err(|e| SignatureImageError::InvalidFormat(format!("invalid SVG path ({})", e)))? {
+            SimplePathSegment::MoveTo { x, y } => builder.move_to(x as f32, y as f32),
+            SimplePathSegment::LineTo { x, y } => builder.line_to(x as f32, y as f32),
+            SimplePathSegment::Quadratic { x1, y1, x, y } => {
+                builder.quad_to(x1 as f32, y1 as f32, x as f32, y as f32)
+            }
+            SimplePathSegment::CurveTo { x1, y1, x2, y2, x, y } => {
+                builder.cubic_to(x1 as f32, y1 as f32, x2 as f32, y2 as f32, x as f32, y as f32)
+            }
+            SimplePathSegment::ClosePath => builder.close(),
+        }
+    }
+    Ok(builder.finish())
+}
+
+/// Met le tracé à l'échelle (sans déformation, comme `normalize_signature`) et le centre sur le canevas canonique.
+fn fit_path(path: Option<Path>) -> Result<String, SignatureImageError> {
+    let path = path.ok_or(SignatureImageError::Blank)?;
+    // Limites du tracé lui-même, sans les points de contrôle des courbes
+    let bounds = path.compute_tight_bounds().ok_or(SignatureImageError::Blank)?;
+    if bounds.width().max(bounds.height()) < MIN_INK_EXTENT as f32 {
+        return Err(SignatureImageError::Blank);
+    }
+
+    let max_width = (CANONICAL_WIDTH - 2 * CANONICAL_MARGIN) as f32 - STROKE_WIDTH;
+    let max_height = (CANONICAL_HEIGHT - 2 * CANONICAL_MARGIN) as f32 - STROKE_WIDTH;
+    let scale = (max_width / bounds.width())
+        .min(max_height / bounds.height())
+        .min(MAX_UPSCALE);
+    let dx = CANONICAL_WIDTH as f32 / 2.0 - (bounds.left() + bounds.width() / 2.0) * scale;
+    let dy = CANONICAL_HEIGHT as f32 / 2.0 - (bounds.top() + bounds.height() / 2.0) * scale;
+    let path = path
+        .transform(Transform::from_row(scale, 0.0, 0.0, scale, dx, dy))
+        .ok_or(SignatureImageError::Blank)?;
+    Ok(path_data(&path))
+}
+
+fn path_data(path: &Path) -> String {
+    let mut data = String::new();
+    for segment in path.segments() {
+        if !data.is_empty() {
+            data.push(' ');
+        }
+        // Écrire dans une String ne peut pas échouer
+        let _ = match segment {
+            PathSegment::MoveTo(p) => write!(data, "M{:.1} {:.1}", p.x, p.y),
+            PathSegment::LineTo(p) => write!(data, "L{:.1} {:.1}", p.x, p.y),
+            PathSegment::QuadTo(p1, p) => write!(data, "Q{:.1} {:.1} {:.1} {:.1}", p1.x, p1.y, p.x, p.y),
+            PathSegment::CubicTo(p1, p2, p) => write!(
+                data,
+                "C{:.1} {:.1} {:.1} {:.1} {:.1} {:.1}",
+                p1.x, p1.y, p2.x, p2.y, p.x, p.y
+            ),
+            PathSegment::Close => write!(data, "Z"),
+        };
+    }
+    data
+}
+
+/// Rend un chemin canonique en PNG `width` x `height` : le canevas canonique est mis à l'échelle et centré,
+/// encre noire sur fond transparent.
+fn render_path(data: &str, width: u32, height: u32) -> Result<String, SignatureImageError> {
+    let path = parse_path(data)?.ok_or(SignatureImageError::Blank)?;
+    let mut pixmap = Pixmap::new(width, height)
+        .ok_or_else(|| SignatureImageError::InvalidFormat(format!("invalid dimensions {}x{}", width, height)))?;
+
+    let scale = (width as f32 / CANONICAL_WIDTH as f32).min(height as f32 / CANONICAL_HEIGHT as f32);
+    let dx = (width as f32 - CANONICAL_WIDTH as f32 * scale) / 2.0;
+    let dy = (height as f32 - CANONICAL_HEIGHT as f32 * scale) / 2.0;
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(0, 0, 0, 255);
+    let stroke = Stroke {
+        width: STROKE_WIDTH,
+        line_cap: LineCap::Round,
+        line_join: LineJoin::Round,
+        ..Stroke::default()
+    };
+    pixmap.stroke_path(&path, &paint, &stroke, Transform::from_row(scale, 0.0, 0.0, scale, dx, dy), None);
+    encode_pixmap(&pixmap)
+}
+
+/// Data URL PNG d'un rendu tiny-skia.
+pub(super) fn encode_pixmap(pixmap: &Pixmap) -> Result<String, SignatureImageError> {
+    let pixels = pixmap
+        .pixels()
+        .iter()
+        .flat_map(|p| {
+            let c = p.demultiply();
+            [c.red(), c.green(), c.blue(), c.alpha()]
+        })
+        .collect();
+    let image = RgbaImage::from_raw(pixmap.width(), pixmap.height(), pixels)
+        .ok_or_else(|| SignatureImageError::InvalidFormat("rendering failed".to_string()))?;
+    encode_png(&image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::users::signature_image::png_bytes;
+
+    fn payload(strokes: Option<Vec<Vec<[f32; 2]>>>, svg_path: Option<&str>) -> SaveSignaturePayload {
+        SaveSignaturePayload {
+            signature: None,
+            strokes,
+            svg_path: svg_path.map(str::to_string),
+        }
+    }
+
+    /// Rectangle (x, y, largeur, hauteur) de l'encre d'un PNG rendu.
+    fn ink_box(data_url: &str) -> (u32, u32, u32, u32, (u32, u32)) {
+        let image = image::load_from_memory(&png_bytes(data_url).unwrap()).unwrap().to_rgba8();
+        let inked: Vec<(u32, u32)> = image
+            .enumerate_pixels()
+            .filter(|(_, _, p)| p[3] > 128)
+            .map(|(x, y, _)| (x, y))
+            .collect();
+        let min_x = inked.iter().map(|p| p.0).min().unwrap();
+        let max_x = inked.iter().map(|p| p.0).max().unwrap();
+        let min_y = inked.iter().map(|p| p.1).min().unwrap();
+        let max_y = inked.iter().map(|p| p.1).max().unwrap();
+        (min_x, min_y, max_x - min_x + 1, max_y - min_y + 1, image.dimensions())
+    }
+
+    #[test]
+    fn strokes_are_centered_on_canonical_canvas() {
+        let strokes = vec![vec![[10.0, 10.0], [110.0, 50.0], [210.0, 10.0]], vec![[60.0, 40.0]]];
+        let (png, path) = prepare_signature(&payload(Some(strokes), None)).unwrap();
+        let path = path.unwrap();
+        assert!(path.starts_with('M'));
+
+        let (x, y, width, height, size) = ink_box(&png);
+        assert_eq!(size, (CANONICAL_WIDTH, CANONICAL_HEIGHT));
+        // 200x40 agrandi 3 fois (agrandissement maximal), centré
+        assert!((600..=606).contains(&width), "width {}", width);
+        assert!((120..=126).contains(&height), "height {}", height);
+        assert!(x.abs_diff(CANONICAL_WIDTH - x - width) <= 2);
+        assert!(y.abs_diff(CANONICAL_HEIGHT - y - height) <= 2);
+    }
+
+    #[test]
+    fn renders_at_requested_resolution() {
+        let (_, path) = prepare_signature(&payload(None, Some("M0 0 C 100 -80, 200 80, 300 0"))).unwrap();
+        let signature = UserSignature {
+            id: "sig".to_string(),
+            user_id: "user".to_string(),
+            created_at: chrono::NaiveDateTime::default(),
+            label: None,
+            enabled: true,
+            use_count: 0,
+            last_used_at: None,
+            signature_path: path,
+            content_hash: String::new(),
+        };
+        let (_, _, small_width, _, size) = ink_box(&render_signature(&signature, &[], 400, 150).unwrap());
+        assert_eq!(size, (400, 150));
+        let (_, _, large_width, _, size) = ink_box(&render_signature(&signature, &[], 1600, 600).unwrap());
+        assert_eq!(size, (1600, 600));
+        assert!(large_width.abs_diff(small_width * 4) <= 8, "{} vs {}", large_width, small_width);
+    }
+
+    #[test]
+    fn rejects_blank_and_invalid_vectors() {
+        assert_eq!(
+            prepare_signature(&payload(Some(vec![vec![[5.0, 5.0]], vec![]]), None)),
+            Err(SignatureImageError::Blank)
+        );
+        assert!(matches!(
+            prepare_signature(&payload(None, Some("M 10 10 L oops"))),
+            Err(SignatureImageError::InvalidFormat(_))
+        ));
+        assert!(matches!(
+            prepare_signature(&payload(Some(vec![vec![[0.0, f32::NAN], [50.0, 50.0]]]), None)),
+            Err(SignatureImageError::InvalidFormat(_))
+        ));
+        assert!(matches!(
+            prepare_signature(&payload(Some(vec![vec![[1.0, 1.0]; MAX_PATH_POINTS + 1]]), None)),
+            Err(SignatureImageError::TooLarge(_))
+        ));
+        assert!(matches!(
+            prepare_signature(&payload(None, None)),
+            Err(SignatureImageError::InvalidFormat(_))
+        ));
+    }
+}
@@ -0,0 +1,373 @@
+use std::collections::HashMap;
+
+use axum::{
+    Form, Json, Router,
+    extract::Query,
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Redirect, Response},
+    routing::{get, post},
+};
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveTime, Weekday};
+use tracing::{error, info, warn};
+
+use crate::api::edsquare::models::EdsquarePlanningEvent;
+
+/// Cookie de session remis par le faux EDSquare (toute valeur commençant par `demo-` est acceptée).
+const DEMO_SESSION_COOKIE: &str = "_edsquare_session";
+const DEMO_SESSION_VALUE: &str = "demo-session-0123456789";
+const DEMO_CSRF: &str = "demo-csrf-token";
+/// Mot de passe refusé par le faux EDSquare (tous les autres sont acceptés).
+pub const DEMO_INVALID_PASSWORD: &str = "invalid";
+
+/// Issue simulée d'une signature intra, d'après le dernier chiffre du token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DemoIntraOutcome {
+    Success,
+    /// Token se terminant par 7
+    AlreadySigned,
+    /// Token se terminant par 8
+    TokenExpired,
+    /// Token se terminant par 9
+    Unavailable,
+}
+
+impl DemoIntraOutcome {
+    pub fn from_token(token: &str) -> Self {
+        match token.trim().chars().last() {
+            Some('7') => DemoIntraOutcome::AlreadySigned,
+            Some('8') => DemoIntraOutcome::TokenExpired,
+            Some('9') => DemoIntraOutcome::Unavailable,
+            _ => DemoIntraOutcome::Success,
+        }
+    }
+
+    /// Statut HTTP que renverrait l'intra (voir l'interprétation dans `sign_user`).
+    fn status(self) -> StatusCode {
+        match self {
+            DemoIntraOutcome::Success => StatusCode::OK,
+            DemoIntraOutcome::AlreadySigned => StatusCode::INTERNAL_SERVER_ERROR,
+            DemoIntraOutcome::TokenExpired => StatusCode::FORBIDDEN,
+            DemoIntraOutcome::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+}
+
+/// Issue simulée d'une validation EDSquare, d'après le dernier chiffre du code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DemoEdsquareOutcome {
+    Success,
+    /// Code se terminant par 7 (« Événement introuvable » : présence déjà enregistrée)
+    AlreadySigned,
+    /// Code se terminant par 8
+    CodeExpired,
+    /// Code se terminant par 9
+    InvalidCode,
+    /// Code se terminant par 0
+    SessionExpired,
+}
+
+impl DemoEdsquareOutcome {
+    pub fn from_code(code: &str) -> Self {
+        match code.trim().chars().last() {
+            Some('7') => DemoEdsquareOutcome::AlreadySigned,
+            Some('8') => DemoEdsquareOutcome::CodeExpired,
+            Some('9') => DemoEdsquareOutcome::InvalidCode,
+            Some('0') => DemoEdsquareOutcome::SessionExpired,
+            _ => DemoEdsquareOutcome::Success,
+        }
+    }
+
+    /// Réponse JavaScript qu'enverrait EDSquare.
+    fn body(self) -> &'static str {
+        match self {
+            DemoEdsquareOutcome::Success => r##"$("#modal").modal("hide"); toastr.success("Votre présence a été enregistrée");"##,
+            DemoEdsquareOutcome::AlreadySigned => r#"toastr.error("Événement introuvable");"#,
+            DemoEdsquareOutcome::CodeExpired => r#"toastr.error("Le code saisi n&#39;est plus valide");"#,
+            DemoEdsquareOutcome::InvalidCode => r#"toastr.error("Le code saisi est invalide");"#,
+            DemoEdsquareOutcome::SessionExpired => r#"window.location.href = "/users/sign_in";"#,
+        }
+    }
+}
+
+/// Cours démo de chaque jour ouvré : (numéro, titre, heure de début, heure de fin).
+const DEMO_SLOTS: [(i64, &str, u32, u32); 2] = [(1, "Algorithmique (démo)", 9, 12), (2, "Anglais (démo)", 14, 17)];
+
+/// Planning fictif : deux cours par jour ouvré (9h-12h et 14h-17h) dans le fuseau de `start`, ids stables par date.
+pub fn demo_planning(start: DateTime<FixedOffset>, end: DateTime<FixedOffset>) -> Vec<EdsquarePlanningEvent> {
+    let offset = *start.offset();
+    let mut events = Vec::new();
+    let mut date = start.date_naive();
+    while date.and_time(NaiveTime::MIN).and_local_timezone(offset).single().is_some_and(|d| d < end) {
+        if !matches!(date.weekday(), Weekday::Sat | Weekday::Sun) {
+            let day_id = date.format("%Y%m%d").to_string().parse::<i64>().unwrap_or_default();
+            for (n, title, from, to) in DEMO_SLOTS {
+                let at = |hour: u32| {
+                    date.and_hms_opt(hour, 0, 0)
+                        .and_then(|local| local.and_local_timezone(offset).single())
+                };
+                let (Some(event_start), Some(event_end)) = (at(from), at(to)) else {
+                    continue;
+                };
+                if event_end <= start || event_start >= end {
+                    continue;
+                }
+                events.push(EdsquarePlanningEvent {
+                    id: day_id * 10 + n,
+                    title: title.to_string(),
+                    target: Some("Salle démo".to_string()),
+                    start: event_start.to_rfc3339(),
+                    end: event_end.to_rfc3339(),
+                    event_type: Some("course".to_string()),
+                    registrable: Some(true),
+                });
+            }
+        }
+        date += Duration::days(1);
+    }
+    events
+}
+
+fn has_demo_session(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::COOKIE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|cookies| {
+            cookies
+                .split("; ")
+                .filter_map(|c| c.split_once('='))
+                .any(|(name, value)| name == DEMO_SESSION_COOKIE && value.starts_with("demo-"))
+        })
+}
+
+fn session_cookie_header() -> (header::HeaderName, String) {
+    (
+        header::SET_COOKIE,
+        format!("{}={}; path=/; HttpOnly", DEMO_SESSION_COOKIE, DEMO_SESSION_VALUE),
+    )
+}
+
+fn html_page(body: &str) -> Response {
+    let html = format!(
+        r#"<html><head><meta name="csrf-token" content="{0}" /></head><body>
+        <input type="hidden" name="authenticity_token" value="{0}" />{1}</body></html>"#,
+        DEMO_CSRF, body
+    );
+    ([(header::CONTENT_TYPE, "text/html; charset=utf-8")], html).into_response()
+}
+
+async fn intra_token(Json(payload): Json<serde_json::Value>) -> StatusCode {
+    let token = payload.get("token").and_then(|t| t.as_str()).unwrap_or_default();
+    let outcome = DemoIntraOutcome::from_token(token);
+    info!("Démo intra: token {} → {:?}", token, outcome);
+    outcome.status()
+}
+
+/// Planning intra fictif (`/planning/load?format=json`) : les cours démo de chaque jour ouvré, tous inscrits.
+async fn intra_planning(Query(query): Query<HashMap<String, String>>) -> Response {
+    let parse = |key: &str| query.get(key).and_then(|v| NaiveDate::parse_from_str(v, "%Y-%m-%d").ok());
+    let (Some(start), Some(end)) = (parse("start"), parse("end")) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    let entries: Vec<serde_json::Value> = start
+        .iter_days()
+        .take_while(|date| *date <= end)
+        .filter(|date| !matches!(date.weekday(), Weekday::Sat | Weekday::Sun))
+        .flat_map(|date| {
+            DEMO_SLOTS.iter().map(move |(n, title, from, to)| {
+                serde_json::json!({
+                    "scolaryear": date.year().to_string(),
+                    "codemodule": "B-DEMO-000",
+                    "codeinstance": "PAR-0-1",
+                    "codeacti": format!("acti-{}", n),
+                    "codeevent": format!("event-{}{}", date.format("%Y%m%d"), n),
+                    "acti_title": title,
+                    "start": format!("{} {:02}:00:00", date, from),
+                    "end": format!("{} {:02}:00:00", date, to),
+                    "event_registered": "registered",
+                })
+            })
+        })
+        .collect();
+    Json(entries).into_response()
+}
+
+async fn edsquare_sign_in_page() -> Response {
+    html_page(r#"<form action="/users/sign_in" method="post"></form>"#)
+}
+
+async fn edsquare_sign_in(Form(form): Form<HashMap<String, String>>) -> Response {
+    if form.get("user[password]").map(String::as_str) == Some(DEMO_INVALID_PASSWORD) {
+        return html_page(r#"<div class="alert">Email ou mot de passe invalide</div><form action="/users/sign_in"></form>"#);
+    }
+    ([session_cookie_header()], Redirect::to("/edsquare/home")).into_response()
+}
+
+async fn edsquare_home(headers: HeaderMap) -> Response {
+    if !has_demo_session(&headers) {
+        return Redirect::to("/edsquare/users/sign_in").into_response();
+    }
+    ([session_cookie_header()], html_page("Accueil (démo)")).into_response()
+}
+
+async fn edsquare_classrooms(headers: HeaderMap) -> Response {
+    if !has_demo_session(&headers) {
+        return Redirect::to("/edsquare/users/sign_in").into_response();
+    }
+    html_page("Aucune signature en attente (démo)")
+}
+
+async fn edsquare_signature(headers: HeaderMap, Form(form): Form<HashMap<String, String>>) -> Response {
+    if !has_demo_session(&headers) {
+        return DemoEdsquareOutcome::SessionExpired.body().into_response();
+    }
+    let code: String = (1..=6)
+        .filter_map(|i| form.get(&format!("secret_code_part_{}", i)).cloned())
+        .collect();
+    let outcome = DemoEdsquareOutcome::from_code(&code);
+    info!("Démo EDSquare: code {} → {:?}", code, outcome);
+    outcome.body().into_response()
+}
+
+async fn edsquare_planning(headers: HeaderMap, Query(query): Query<HashMap<String, String>>) -> Response {
+    if !has_demo_session(&headers) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    let parse = |key: &str| query.get(key).and_then(|v| DateTime::parse_from_rfc3339(v).ok());
+    match (parse("start"), parse("end")) {
+        (Some(start), Some(end)) => Json(demo_planning(start, end)).into_response(),
+        _ => StatusCode::BAD_REQUEST.into_response(),
+    }
+}
+
+fn demo_router() -> Router {
+    let intra = Router::new()
+        .route("/module/{year}/{module}/{instance}/{acti}/{event}/token", post(intra_token))
+        .route("/planning/load", get(intra_planning));
+    let edsquare = Router::new()
+        .route("/users/sign_in", get(edsquare_sign_in_page).post(edsquare_sign_in))
+        .route("/home", get(edsquare_home))
+        .route("/apps/classrooms", get(edsquare_classrooms))
+        .route("/apps/course_user_signatures", post(edsquare_signature))
+        .route("/apps/planning/json_dashboard", get(edsquare_planning));
+    Router::new().nest("/intra", intra).nest("/edsquare", edsquare)
+}
+
+/// Adresses des faux services du mode démo.
+pub struct DemoProviders {
+    pub intra_base_url: String,
+    pub edsquare_base_url: String,
+}
+
+/// Démarre les faux intra et EDSquare sur un port local libre, dans un thread dédié
+/// (l'état global est construit hors de tout runtime tokio).
+pub fn start_demo_providers() -> Result<DemoProviders, String> {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").map_err(|e| format!("bind: {}", e))?;
+    listener.set_nonblocking(true).map_err(|e| format!("set_nonblocking: {}", e))?;
+    let addr = listener.local_addr().map_err(|e| format!("local_addr: {}", e))?;
+
+    std::thread::Builder::new()
+        .name("demo-providers".to_string())
+        .spawn(move || {
+            let runtime = match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+                Ok(runtime) => runtime,
+                Err(e) => {
+                    error!("Mode démo: impossible de créer le runtime: {}", e);
+                    return;
+                }
+            };
+            runtime.block_on(async move {
+                let listener = match tokio::net::TcpListener::from_std(listener) {
+                    Ok(listener) => listener,
+                    Err(e) => {
+                        error!("Mode démo: écoute impossible: {}", e);
+                        return;
+                    }
+                };
+                if let Err(e) = axum::serve(listener, demo_router()).await {
+                    error!("Mode démo: arrêt des faux services: {}", e);
+                }
+            });
+        })
+        .map_err(|e| format!("thread: {}", e))?;
+
+    warn!("Mode démo actif : intra et EDSquare simulés sur http://{}", addr);
+    Ok(DemoProviders {
+        intra_base_url: format!("http://{}/intra", addr),
+        edsquare_base_url: format!("http://{}/edsquare", addr),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outcomes_follow_last_digit() {
+        assert_eq!(DemoIntraOutcome::from_token("12345671"), DemoIntraOutcome::Success);
+        assert_eq!(DemoIntraOutcome::from_token("12345677"), DemoIntraOutcome::AlreadySigned);
+        assert_eq!(DemoIntraOutcome::from_token("12345678"), DemoIntraOutcome::TokenExpired);
+        assert_eq!(DemoEdsquareOutcome::from_code("123456"), DemoEdsquareOutcome::Success);
+        assert_eq!(DemoEdsquareOutcome::from_code("123457"), DemoEdsquareOutcome::AlreadySigned);
+        assert_eq!(DemoEdsquareOutcome::from_code("123458"), DemoEdsquareOutcome::CodeExpired);
+        assert_eq!(DemoEdsquareOutcome::from_code("123459"), DemoEdsquareOutcome::InvalidCode);
+        assert_eq!(DemoEdsquareOutcome::from_code("123450"), DemoEdsquareOutcome::SessionExpired);
+    }
+
+    #[test]
+    fn planning_has_two_courses_per_weekday() {
+        let start = DateTime::parse_from_rfc3339("2026-10-16T00:00:00+02:00").unwrap(); // vendredi
+        let end = start + Duration::days(4);
+        let events = demo_planning(start, end);
+        // vendredi et lundi, pas le week-end
+        assert_eq!(
+            events.iter().map(|e| e.id).collect::<Vec<_>>(),
+            vec![202610161, 202610162, 202610191, 202610192]
+        );
+        assert_eq!(events[0].start, "2026-10-16T09:00:00+02:00");
+        // Même période, mêmes cours
+        assert_eq!(demo_planning(start, end).len(), events.len());
+    }
+
+    #[tokio::test]
+    async fn demo_providers_answer_like_the_real_services() {
+        use crate::api::edsquare::{EdsquareClient, services::check_edsquare_session};
+        use crate::api::sign::services::{fetch_intra_events, sign_user};
+        use crate::api::sign::{IntraClient, SignResponse, url_parser::parse_sign_url};
+        use crate::api::users::User;
+
+        let demo = start_demo_providers().unwrap();
+
+        let intra = IntraClient::new(&demo.intra_base_url).unwrap();
+        let event = "https://intra.epitech.eu/module/2026/B-DEV-500/PAR-5-1/acti-1/event-2/registered?token=";
+        let mut user = User::new("demo".to_string(), "demo".to_string());
+        user.jwt_intra_epitech = Some("demo-jwt".to_string());
+        let mut responses = Vec::new();
+        for token in ["12345671", "12345677", "12345678"] {
+            let sign_url = parse_sign_url(&format!("{}{}", event, token)).unwrap();
+            responses.push(sign_user(&intra, &[], &user, &sign_url).await);
+        }
+        assert_eq!(
+            responses,
+            vec![SignResponse::Success, SignResponse::AlreadySigned, SignResponse::TokenExpired]
+        );
+        let monday = chrono::NaiveDate::from_ymd_opt(2026, 10, 19).unwrap();
+        let events = fetch_intra_events(&intra, &user, monday, chrono_tz::Europe::Paris).await.unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].start, "2026-10-19T09:00:00+02:00");
+
+        let edsquare = EdsquareClient::new(&demo.edsquare_base_url).unwrap();
+        let mut cookie = crate::api::sign::CookieItem {
+            name: DEMO_SESSION_COOKIE.to_string(),
+            value: DEMO_SESSION_VALUE.to_string(),
+            domain: "127.0.0.1".to_string(),
+            path: "/".to_string(),
+            expires: None,
+            http_only: true,
+            secure: false,
+            same_site: None,
+        };
+        assert!(check_edsquare_session(&edsquare, std::slice::from_ref(&cookie)).await.is_ok());
+        cookie.value = "expired".to_string();
+        assert!(check_edsquare_session(&edsquare, &[cookie]).await.is_err());
+    }
+}
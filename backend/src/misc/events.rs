@@ -0,0 +1,129 @@
+use serde::Serialize;
+use tokio::sync::broadcast;
+use utoipa::ToSchema;
+
+/// Nombre d'événements gardés en mémoire pour les abonnés lents avant qu'ils en perdent.
+const EVENT_BUS_CAPACITY: usize = 256;
+
+/// Événement temps réel diffusé aux clients abonnés à `/api/events/stream`.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum ServerEvent {
+    /// Résultats d'une signature intra (immédiate ou programmée)
+    SignResults {
+        initiated_by: String,
+        event: String,
+        results: Vec<UserResultEvent>,
+    },
+    /// Résultats d'une validation EDSquare multi-utilisateurs
+    EdsquareValidation {
+        initiated_by: String,
+        global_success: bool,
+        results: Vec<UserResultEvent>,
+    },
+    /// Réservation créée, annulée ou honorée
+    ReservationChanged {
+        id: String,
+        user_id: String,
+        date: String,
+        event: Option<String>,
+        status: String,
+    },
+    /// Membres d'une salle de signature modifiés (création, arrivée, départ, consentement)
+    RoomUpdated {
+        room_id: String,
+        member_count: usize,
+        consenting_count: usize,
+    },
+    /// Résultats par membre après le scan du QR code dans une salle
+    RoomSignResults {
+        room_id: String,
+        event: String,
+        scanned_by: String,
+        results: Vec<UserResultEvent>,
+    },
+    /// Salle fermée (fin de session ou fermeture par son créateur)
+    RoomClosed { room_id: String },
+    /// Disponibilité des cookies intra (globaux) ou EDSquare (par utilisateur)
+    CookieStatusChanged {
+        /// `intra` ou `edsquare`
+        provider: String,
+        user_id: Option<String>,
+        available: bool,
+    },
+    /// Reconnexion EDSquare automatique suspendue : l'utilisateur doit ressaisir son mot de passe
+    EdsquareCredentialsSuspended {
+        user_id: String,
+        failed_login_count: i32,
+    },
+    /// La session EDSquare d'un utilisateur n'a pas pu être rafraîchie avant le cours
+    EdsquareSessionAlert {
+        user_id: String,
+        username: String,
+        message: String,
+    },
+}
+
+impl ServerEvent {
+    /// Nom de l'événement SSE (champ `event:`), pour `addEventListener` côté client.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ServerEvent::SignResults { .. } => "sign_results",
+            ServerEvent::EdsquareValidation { .. } => "edsquare_validation",
+            ServerEvent::ReservationChanged { .. } => "reservation_changed",
+            ServerEvent::RoomUpdated { .. } => "room_updated",
+            ServerEvent::RoomSignResults { .. } => "room_sign_results",
+            ServerEvent::RoomClosed { .. } => "room_closed",
+            ServerEvent::CookieStatusChanged { .. } => "cookie_status_changed",
+            ServerEvent::EdsquareSessionAlert { .. } => "edsquare_session_alert",
+            ServerEvent::EdsquareCredentialsSuspended { .. } => "edsquare_credentials_suspended",
+        }
+    }
+
+    /// Utilisateur seul destinataire d'un événement personnel (cookies EDSquare, session, identifiants) ;
+    /// `None` pour les événements diffusés à tous.
+    pub fn recipient(&self) -> Option<&str> {
+        match self {
+            ServerEvent::CookieStatusChanged { user_id, .. } => user_id.as_deref(),
+            ServerEvent::EdsquareCredentialsSuspended { user_id, .. }
+            | ServerEvent::EdsquareSessionAlert { user_id, .. } => Some(user_id),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct UserResultEvent {
+    pub user_id: String,
+    pub username: String,
+    pub success: bool,
+    pub message: String,
+}
+
+/// Bus d'événements en mémoire (broadcast) partagé via `GlobalState`.
+#[derive(Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<ServerEvent>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_BUS_CAPACITY);
+        Self { sender }
+    }
+
+    /// Diffuse un événement ; sans abonné, l'événement est simplement ignoré.
+    pub fn publish(&self, event: ServerEvent) {
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<ServerEvent> {
+        self.sender.subscribe()
+    }
+}
@@ -0,0 +1,122 @@
+use std::{future::Future, time::Duration};
+
+use futures_util::{StreamExt, stream};
+
+/// Exécute `task` pour chaque élément avec au plus `limit` tâches simultanées.
+/// Chaque tâche est bornée par `timeout` (remplacée par `on_timeout` si elle dépasse).
+/// Les résultats sont retournés dans l'ordre des éléments, quel que soit l'ordre de fin.
+pub async fn run_bounded<I, T, F, Fut, G>(
+    items: Vec<I>,
+    limit: usize,
+    timeout: Duration,
+    task: F,
+    on_timeout: G,
+) -> Vec<T>
+where
+    I: Clone,
+    F: Fn(I) -> Fut,
+    Fut: Future<Output = T>,
+    G: Fn(I) -> T,
+{
+    // `buffer_unordered` libère la place d'une tâche dès qu'elle finit (`buffered` attendrait la plus ancienne) ;
+    // l'ordre est rétabli ensuite grâce à l'index.
+    let mut results: Vec<(usize, T)> = stream::iter(items.into_iter().enumerate())
+        .map(|(index, item)| {
+            let fut = tokio::time::timeout(timeout, task(item.clone()));
+            let on_timeout = &on_timeout;
+            async move { (index, fut.await.unwrap_or_else(|_| on_timeout(item))) }
+        })
+        .buffer_unordered(limit.max(1))
+        .collect()
+        .await;
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn keeps_request_order() {
+        // Les premiers éléments finissent en dernier
+        let results = run_bounded(
+            vec![30u64, 20, 10, 0],
+            4,
+            Duration::from_secs(1),
+            |ms| async move {
+                tokio::time::sleep(Duration::from_millis(ms)).await;
+                ms
+            },
+            |_| u64::MAX,
+        )
+        .await;
+        assert_eq!(results, vec![30, 20, 10, 0]);
+    }
+
+    #[tokio::test]
+    async fn replaces_slow_tasks_with_timeout_result() {
+        let results = run_bounded(
+            vec![0u64, 500, 0],
+            2,
+            Duration::from_millis(50),
+            |ms| async move {
+                tokio::time::sleep(Duration::from_millis(ms)).await;
+                format!("ok {}", ms)
+            },
+            |ms| format!("timeout {}", ms),
+        )
+        .await;
+        assert_eq!(results, vec!["ok 0", "timeout 500", "ok 0"]);
+    }
+
+    #[tokio::test]
+    async fn a_slow_task_does_not_hold_back_the_other_slots() {
+        // Les 12 tâches courtes tournent sur les 3 autres places pendant la longue : ~600 ms au total
+        // (avec `buffered`, elles attendraient la longue : ~900 ms)
+        let start = tokio::time::Instant::now();
+        let items: Vec<u64> = std::iter::once(600).chain(std::iter::repeat_n(100, 12)).collect();
+        let results = run_bounded(
+            items.clone(),
+            4,
+            Duration::from_secs(5),
+            |ms| async move {
+                tokio::time::sleep(Duration::from_millis(ms)).await;
+                ms
+            },
+            |_| u64::MAX,
+        )
+        .await;
+        assert_eq!(results, items);
+        assert!(start.elapsed() < Duration::from_millis(750), "took {:?}", start.elapsed());
+    }
+
+    #[tokio::test]
+    async fn never_exceeds_limit() {
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        run_bounded(
+            (0..12).collect::<Vec<_>>(),
+            3,
+            Duration::from_secs(1),
+            |_| {
+                let running = running.clone();
+                let peak = peak.clone();
+                async move {
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                }
+            },
+            |_| (),
+        )
+        .await;
+        assert_eq!(peak.load(Ordering::SeqCst), 3);
+    }
+}
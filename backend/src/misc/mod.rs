@@ -1,7 +1,17 @@
+mod demo;
+mod events;
+mod executor;
 mod logger;
 mod router;
 mod state;
+mod time;
 
+pub use events::{EventBus, ServerEvent, UserResultEvent};
+pub use executor::run_bounded;
 pub use logger::start_logger;
 pub use router::{get_router, start_server};
 pub use state::{GlobalState, PlanningEventsCacheEntry};
+#[cfg(test)]
+pub use demo::start_demo_providers;
+#[cfg(test)]
+pub use state::test_database_url;
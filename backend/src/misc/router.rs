@@ -15,19 +15,33 @@ use crate::{
 
 pub fn get_router() -> Router {
     let state = GlobalState::new();
+    api::sign::spawn_sign_scheduler(state.clone());
+    api::events::spawn_cookie_status_watcher(state.clone());
+    api::rooms::spawn_room_sweeper(state.clone());
+    api::edsquare::spawn_edsquare_cookie_gc(state.clone());
+    api::edsquare::spawn_edsquare_keepalive(state.clone());
 
     // Routes admin (clé X-Admin-Key, pas de JWT) — à merger avant les routes protégées
     let admin_routes = Router::new()
         .nest("/api/admin", crate::api::admin::get_routes(state.clone()));
 
+    // Flux iCalendar (jeton dans l'URL, pas de JWT : les agendas ne savent pas s'authentifier)
+    let calendar_routes = Router::new()
+        .nest("/api/calendar", crate::api::edsquare::get_calendar_routes(state.clone()));
+
     let api_routes = Router::new()
         .nest("/api/sign", crate::api::sign::get_routes(state.clone()))
         .nest("/api/users", crate::api::users::get_routes(state.clone()))
         .nest("/api/edsquare", crate::api::edsquare::get_routes(state.clone()))
+        .nest("/api/reservations", crate::api::reservations::get_routes(state.clone()))
+        .nest("/api/rooms", crate::api::rooms::get_routes(state.clone()))
+        .nest("/api/events", crate::api::events::get_routes(state.clone()))
+        .nest("/api/attendance", crate::api::attendance::get_routes(state.clone()))
         .layer(from_fn(api::auth::auth_middleware));
 
     Router::new()
         .merge(admin_routes)
+        .merge(calendar_routes)
         .merge(api_routes)
         .nest(
             "/api/auth",
@@ -1,15 +1,27 @@
 use std::sync::Arc;
 use std::collections::HashMap;
-use std::time::{SystemTime, Duration};
+use std::time::{Duration, SystemTime};
 
 use diesel::{
     PgConnection,
     r2d2::{ConnectionManager, Pool, PoolError, PooledConnection},
 };
-use tokio::sync::RwLock;
-use chrono::NaiveDate;
+use tokio::sync::{Mutex, RwLock};
+use chrono::{DateTime, NaiveDate};
+use chrono_tz::Tz;
+use ab_glyph::FontArc;
+use tracing::warn;
 
-use crate::api::edsquare::models::EdsquarePlanningEvent;
+use crate::api::edsquare::EdsquareClient;
+use crate::api::edsquare::client::DEFAULT_EDSQUARE_BASE_URL;
+use crate::api::edsquare::models::{EdsquarePlanningEvent, EdsquareSessionHealth};
+use crate::api::sign::IntraClient;
+use crate::api::sign::client::DEFAULT_INTRA_BASE_URL;
+use crate::api::rooms::models::SignRoom;
+use crate::api::users::load_signature_font;
+use crate::misc::EventBus;
+use crate::misc::demo::start_demo_providers;
+use crate::misc::time::{day_bounds, today_in};
 
 /// Cache pour les cours EDSquare par (user_id, date) avec expiration
 #[derive(Clone)]
@@ -21,6 +33,26 @@ pub struct PlanningEventsCacheEntry {
 /// Cache en mémoire pour les cours EDSquare (durée de vie: 5 minutes)
 type PlanningEventsCache = Arc<RwLock<HashMap<(String, NaiveDate), PlanningEventsCacheEntry>>>;
 
+/// Verrou par utilisateur (ex: les appels concurrents partagent une seule reconnexion EDSquare)
+type UserLocks = Arc<std::sync::Mutex<HashMap<String, Arc<Mutex<()>>>>>;
+
+/// Dernier contrôle de session EDSquare par utilisateur (tâche de maintien de session)
+type SessionHealthMap = Arc<std::sync::Mutex<HashMap<String, EdsquareSessionHealth>>>;
+
+/// Salles de signature actives, par id (en mémoire : elles ne vivent que le temps d'une session)
+type SignRooms = Arc<RwLock<HashMap<String, SignRoom>>>;
+
+/// Base Postgres migrée pour les tests qui touchent au stockage (`TEST_DATABASE_URL`) ; ces tests
+/// sont ignorés sans elle.
+#[cfg(test)]
+pub fn test_database_url() -> Option<String> {
+    let url = std::env::var("TEST_DATABASE_URL").ok().filter(|s| !s.trim().is_empty());
+    if url.is_none() {
+        eprintln!("TEST_DATABASE_URL absent : test ignoré");
+    }
+    url
+}
+
 #[derive(Clone)]
 pub struct GlobalState {
     pub db_pool: Arc<Pool<ConnectionManager<PgConnection>>>,
@@ -33,6 +65,44 @@ pub struct GlobalState {
     pub sign_webhook_url: Option<String>,
     /// Cache pour les cours EDSquare (évite de spammer l'API)
     pub edsquare_planning_cache: PlanningEventsCache,
+    /// Bus des événements temps réel (SSE)
+    pub events: EventBus,
+    /// Salles de signature (un scan signe tous les membres consentants)
+    pub sign_rooms: SignRooms,
+    /// Verrous de (re)connexion EDSquare par utilisateur
+    pub edsquare_login_locks: UserLocks,
+    /// Verrous d'envoi de signature par utilisateur : du choix de la signature au comptage de son utilisation
+    pub signature_locks: UserLocks,
+    /// Nombre d'utilisateurs traités en parallèle pour les opérations EDSquare multi-utilisateurs
+    pub edsquare_concurrency: usize,
+    /// Temps maximal accordé à chaque utilisateur (connexion comprise) dans ces opérations
+    pub edsquare_user_timeout: Duration,
+    /// Fuseau horaire de l'école : définit « aujourd'hui » et les bornes des journées de planning
+    pub school_timezone: Tz,
+    /// Marge avant le début d'un cours EDSquare pendant laquelle il est retenu par défaut pour la validation
+    pub edsquare_event_grace: chrono::Duration,
+    /// Échecs consécutifs (identifiants refusés) avant suspension de la reconnexion automatique EDSquare
+    pub edsquare_max_login_failures: i32,
+    /// Fréquence du contrôle (et du rafraîchissement) des sessions EDSquare
+    pub edsquare_keepalive_interval: Duration,
+    /// Fichier des jours de session (une date jj/mm/aa par ligne) ; sans fichier, tous les jours sont contrôlés
+    pub session_dates_file: Option<String>,
+    /// Résultat du dernier contrôle de session EDSquare par utilisateur
+    pub edsquare_session_health: SessionHealthMap,
+    /// Mode démo : intra et EDSquare simulés en local, aucun compte de l'école nécessaire
+    pub demo_mode: bool,
+    /// Client HTTP EDSquare (adresse de base `EDSQUARE_BASE_URL`)
+    pub edsquare: EdsquareClient,
+    /// Client HTTP de l'intra Epitech (adresse de base `INTRA_BASE_URL`)
+    pub intra: IntraClient,
+    /// Police des signatures générées à partir d'un nom (`SIGNATURE_FONT_PATH`, sinon police embarquée de secours)
+    pub signature_font: FontArc,
+}
+
+impl Default for GlobalState {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl GlobalState {
@@ -43,6 +113,68 @@ impl GlobalState {
         let admin_key = std::env::var("ADMIN_KEY").ok().filter(|s| !s.trim().is_empty());
         let edsquare_webhook_url = std::env::var("EDSQUARE_WEBHOOK_URL").ok().filter(|s| !s.trim().is_empty());
         let sign_webhook_url = std::env::var("SIGN_WEBHOOK_URL").ok().filter(|s| !s.trim().is_empty());
+        let edsquare_concurrency = std::env::var("EDSQUARE_CONCURRENCY")
+            .ok()
+            .and_then(|s| s.trim().parse::<usize>().ok())
+            .filter(|n| *n > 0)
+            .unwrap_or(5);
+        let edsquare_user_timeout = std::env::var("EDSQUARE_USER_TIMEOUT_SECS")
+            .ok()
+            .and_then(|s| s.trim().parse::<u64>().ok())
+            .filter(|n| *n > 0)
+            .map(Duration::from_secs)
+            .unwrap_or(Duration::from_secs(45));
+        let school_timezone = match std::env::var("SCHOOL_TIMEZONE").ok().filter(|s| !s.trim().is_empty()) {
+            Some(name) => name
+                .trim()
+                .parse::<Tz>()
+                .unwrap_or_else(|_| panic!("SCHOOL_TIMEZONE invalide: {}", name)),
+            None => chrono_tz::Europe::Paris,
+        };
+        let edsquare_event_grace = std::env::var("EDSQUARE_EVENT_GRACE_MINUTES")
+            .ok()
+            .and_then(|s| s.trim().parse::<i64>().ok())
+            .filter(|n| *n >= 0)
+            .map(chrono::Duration::minutes)
+            .unwrap_or(chrono::Duration::minutes(15));
+        let edsquare_keepalive_interval = std::env::var("EDSQUARE_KEEPALIVE_MINUTES")
+            .ok()
+            .and_then(|s| s.trim().parse::<u64>().ok())
+            .filter(|n| *n > 0)
+            .map(|n| Duration::from_secs(n * 60))
+            .unwrap_or(Duration::from_secs(30 * 60));
+        let edsquare_max_login_failures = std::env::var("EDSQUARE_MAX_LOGIN_FAILURES")
+            .ok()
+            .and_then(|s| s.trim().parse::<i32>().ok())
+            .filter(|n| *n > 0)
+            .unwrap_or(3);
+        let session_dates_file = std::env::var("SESSION_DATES_FILE").ok().filter(|s| !s.trim().is_empty());
+        let mut edsquare_base_url = std::env::var("EDSQUARE_BASE_URL")
+            .ok()
+            .filter(|s| !s.trim().is_empty())
+            .unwrap_or_else(|| DEFAULT_EDSQUARE_BASE_URL.to_string());
+        let mut intra_base_url = std::env::var("INTRA_BASE_URL")
+            .ok()
+            .filter(|s| !s.trim().is_empty())
+            .unwrap_or_else(|| DEFAULT_INTRA_BASE_URL.to_string());
+        let signature_font_path = std::env::var("SIGNATURE_FONT_PATH").ok().filter(|s| !s.trim().is_empty());
+        let signature_font = load_signature_font(signature_font_path.as_deref().map(str::trim))
+            .unwrap_or_else(|e| panic!("SIGNATURE_FONT_PATH invalide: {}", e));
+        if signature_font_path.is_none() {
+            warn!(
+                "SIGNATURE_FONT_PATH absent : les signatures générées utilisent la police embarquée (serif italique, pas une écriture manuscrite)"
+            );
+        }
+        let demo_mode = std::env::var("DEMO_MODE")
+            .ok()
+            .is_some_and(|s| matches!(s.trim().to_lowercase().as_str(), "1" | "true" | "yes"));
+        if demo_mode {
+            let demo = start_demo_providers().expect("Failed to start demo providers");
+            edsquare_base_url = demo.edsquare_base_url;
+            intra_base_url = demo.intra_base_url;
+        }
+        let edsquare = EdsquareClient::new(&edsquare_base_url).expect("Failed to create EDSquare HTTP client");
+        let intra = IntraClient::new(&intra_base_url).expect("Failed to create intra HTTP client");
         let manager = ConnectionManager::<PgConnection>::new(db_url);
         let db_pool = Pool::builder()
             .max_size(10)
@@ -56,9 +188,86 @@ impl GlobalState {
             edsquare_webhook_url,
             sign_webhook_url,
             edsquare_planning_cache: Arc::new(RwLock::new(HashMap::new())),
+            events: EventBus::new(),
+            sign_rooms: Arc::new(RwLock::new(HashMap::new())),
+            edsquare_login_locks: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            signature_locks: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            edsquare_concurrency,
+            edsquare_user_timeout,
+            school_timezone,
+            edsquare_event_grace,
+            edsquare_max_login_failures,
+            edsquare_keepalive_interval,
+            session_dates_file,
+            edsquare_session_health: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            demo_mode,
+            edsquare,
+            intra,
+            signature_font,
+        }
+    }
+
+    /// État de test : base `db_url` (connexions ouvertes à la demande), EDSquare simulé par `edsquare`,
+    /// réglages par défaut pour le reste.
+    #[cfg(test)]
+    pub fn for_tests(db_url: &str, edsquare: EdsquareClient) -> Self {
+        let db_pool = Pool::builder()
+            .max_size(2)
+            .build_unchecked(ConnectionManager::<PgConnection>::new(db_url));
+        GlobalState {
+            db_pool: Arc::new(db_pool),
+            register_key: "test".to_string(),
+            admin_key: None,
+            edsquare_webhook_url: None,
+            sign_webhook_url: None,
+            edsquare_planning_cache: Arc::new(RwLock::new(HashMap::new())),
+            events: EventBus::new(),
+            sign_rooms: Arc::new(RwLock::new(HashMap::new())),
+            edsquare_login_locks: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            signature_locks: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            edsquare_concurrency: 5,
+            edsquare_user_timeout: Duration::from_secs(45),
+            school_timezone: chrono_tz::Europe::Paris,
+            edsquare_event_grace: chrono::Duration::minutes(15),
+            edsquare_max_login_failures: 3,
+            edsquare_keepalive_interval: Duration::from_secs(30 * 60),
+            session_dates_file: None,
+            edsquare_session_health: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            demo_mode: false,
+            edsquare,
+            intra: IntraClient::new(DEFAULT_INTRA_BASE_URL).expect("Failed to create intra HTTP client"),
+            signature_font: load_signature_font(None).expect("Failed to load the bundled signature font"),
         }
     }
 
+    /// Verrou de connexion EDSquare de l'utilisateur (créé au premier appel).
+    pub fn edsquare_login_lock(&self, user_id: &str) -> Arc<Mutex<()>> {
+        let mut locks = self
+            .edsquare_login_locks
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        locks.entry(user_id.to_string()).or_default().clone()
+    }
+
+    /// Verrou d'envoi de signature de l'utilisateur (créé au premier appel).
+    pub fn signature_lock(&self, user_id: &str) -> Arc<Mutex<()>> {
+        let mut locks = self
+            .signature_locks
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        locks.entry(user_id.to_string()).or_default().clone()
+    }
+
+    /// Date du jour dans le fuseau de l'école.
+    pub fn today(&self) -> NaiveDate {
+        today_in(self.school_timezone)
+    }
+
+    /// Début et fin (exclue) d'une journée dans le fuseau de l'école.
+    pub fn day_bounds(&self, date: NaiveDate) -> (DateTime<Tz>, DateTime<Tz>) {
+        day_bounds(self.school_timezone, date)
+    }
+
     pub fn get_db_conn(
         &self,
     ) -> Result<PooledConnection<ConnectionManager<PgConnection>>, PoolError> {
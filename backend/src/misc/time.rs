@@ -0,0 +1,61 @@
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+
+/// Date du jour dans le fuseau donné.
+pub fn today_in(tz: Tz) -> NaiveDate {
+    Utc::now().with_timezone(&tz).date_naive()
+}
+
+/// Début et fin (exclue) d'une journée locale, en tenant compte des changements d'heure.
+pub fn day_bounds(tz: Tz, date: NaiveDate) -> (DateTime<Tz>, DateTime<Tz>) {
+    let next = date.succ_opt().unwrap_or(date);
+    (start_of_day(tz, date), start_of_day(tz, next))
+}
+
+/// Premier instant de la journée : minuit, ou la première heure qui existe si minuit tombe dans un saut d'heure.
+fn start_of_day(tz: Tz, date: NaiveDate) -> DateTime<Tz> {
+    (0..=23)
+        .filter_map(|hour| date.and_hms_opt(hour, 0, 0))
+        .find_map(|local| tz.from_local_datetime(&local).earliest())
+        .unwrap_or_else(|| tz.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap_or_default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn winter_and_summer_offsets() {
+        let (start, end) = day_bounds(chrono_tz::Europe::Paris, date(2026, 1, 15));
+        assert_eq!(start.to_rfc3339(), "2026-01-15T00:00:00+01:00");
+        assert_eq!(end.to_rfc3339(), "2026-01-16T00:00:00+01:00");
+
+        let (start, end) = day_bounds(chrono_tz::Europe::Paris, date(2026, 6, 15));
+        assert_eq!(start.to_rfc3339(), "2026-06-15T00:00:00+02:00");
+        assert_eq!(end.to_rfc3339(), "2026-06-16T00:00:00+02:00");
+    }
+
+    #[test]
+    fn dst_transition_days() {
+        // Passage à l'heure d'été : journée de 23h
+        let (start, end) = day_bounds(chrono_tz::Europe::Paris, date(2026, 3, 29));
+        assert_eq!(start.to_rfc3339(), "2026-03-29T00:00:00+01:00");
+        assert_eq!(end.to_rfc3339(), "2026-03-30T00:00:00+02:00");
+        assert_eq!((end - start).num_hours(), 23);
+
+        // Retour à l'heure d'hiver : journée de 25h
+        let (start, end) = day_bounds(chrono_tz::Europe::Paris, date(2026, 10, 25));
+        assert_eq!((end - start).num_hours(), 25);
+    }
+
+    #[test]
+    fn missing_midnight_starts_at_first_valid_hour() {
+        // São Paulo passait à l'heure d'été à minuit (2018-11-04 00:00 n'existe pas)
+        let (start, _) = day_bounds(chrono_tz::America::Sao_Paulo, date(2018, 11, 4));
+        assert_eq!(start.to_rfc3339(), "2018-11-04T01:00:00-02:00");
+    }
+}
@@ -16,6 +16,9 @@ diesel::table! {
         jwt_intra_epitech -> Nullable<Text>,
         jwt_expires_at -> Nullable<Timestamp>,
         signature_manuscrite -> Nullable<Text>,
+        signature_variation -> Int2,
+        signature_rotation -> Text,
+        pinned_signature_id -> Nullable<Text>,
     }
 }
 
@@ -23,8 +26,10 @@ diesel::table! {
     edsquare_cookies (id) {
         id -> Text,
         user_id -> Text,
-        date -> Date,
         cookie_data -> Jsonb,
+        expires_at -> Nullable<Timestamp>,
+        last_verified_at -> Timestamp,
+        updated_at -> Timestamp,
     }
 }
 
@@ -34,6 +39,9 @@ diesel::table! {
         user_id -> Text,
         email -> Text,
         password -> Text,
+        failed_login_count -> Int4,
+        last_failed_login_at -> Nullable<Timestamp>,
+        suspended_at -> Nullable<Timestamp>,
     }
 }
 
@@ -41,7 +49,53 @@ diesel::table! {
     user_signatures (id) {
         id -> Text,
         user_id -> Text,
-        signature_data -> Text,
+        created_at -> Timestamp,
+        label -> Nullable<Text>,
+        enabled -> Bool,
+        use_count -> Int4,
+        last_used_at -> Nullable<Timestamp>,
+        signature_path -> Nullable<Text>,
+        image -> Bytea,
+        content_hash -> Text,
+    }
+}
+
+diesel::table! {
+    sign_jobs (id) {
+        id -> Text,
+        created_by -> Text,
+        user_ids -> Jsonb,
+        url -> Nullable<Text>,
+        event_url -> Nullable<Text>,
+        not_before -> Timestamp,
+        deadline -> Nullable<Timestamp>,
+        status -> Text,
+        attempts -> Int4,
+        results -> Nullable<Jsonb>,
+        error -> Nullable<Text>,
+        created_at -> Timestamp,
+        executed_at -> Nullable<Timestamp>,
+        token -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    sign_reservations (id) {
+        id -> Text,
+        user_id -> Text,
+        date -> Date,
+        event -> Nullable<Text>,
+        status -> Text,
+        created_at -> Timestamp,
+        fulfilled_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    calendar_tokens (id) {
+        id -> Text,
+        user_id -> Text,
+        token -> Text,
         created_at -> Timestamp,
     }
 }
@@ -52,4 +106,7 @@ diesel::allow_tables_to_appear_in_same_query!(
     edsquare_cookies,
     edsquare_credentials,
     user_signatures,
+    sign_jobs,
+    sign_reservations,
+    calendar_tokens,
 );
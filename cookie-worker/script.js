@@ -35,7 +35,9 @@ async function get_cookies () {
 };
 
 async function save_cookies(cookies) {
-    const formattedDate = new Date().toISOString().split('T')[0];
+    // Date du jour dans le fuseau de l'école (même "aujourd'hui" que le backend)
+    const timeZone = process.env.SCHOOL_TIMEZONE || 'Europe/Paris';
+    const formattedDate = new Date().toLocaleDateString('sv-SE', { timeZone });
     const id = ulid();
 
     // Replace with your actual Postgres URL
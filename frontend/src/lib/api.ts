@@ -11,7 +11,11 @@ import type {
     UserSignResponse,
     UpdateUserPayload,
     SaveSignaturePayload,
+    SignatureDrawing,
+    GenerateSignaturesPayload,
+    GeneratedSignaturesResponse,
     UserSignature,
+    UpdateSignaturePayload,
     ValidateEdsquarePayload,
     ValidateEdsquareResponse,
     ValidateEdsquareMultiResponse,
@@ -189,8 +193,8 @@ export async function updateUserJWT(jwt: string, customFetch?: typeof fetch): Pr
     }, customFetch);
 }
 
-export async function saveSignature(signature: string, customFetch?: typeof fetch): Promise<UserSignature> {
-    const payload: SaveSignaturePayload = { signature };
+export async function saveSignature(drawing: SignatureDrawing, customFetch?: typeof fetch): Promise<UserSignature> {
+    const payload: SaveSignaturePayload = { signature: drawing.signature, strokes: drawing.strokes };
     return await apiCall<UserSignature>('/users/me/signature', {
         method: 'POST',
         body: JSON.stringify(payload)
@@ -205,6 +209,22 @@ export async function deleteSignature(signatureId: string, customFetch?: typeof
     await apiCall<void>(`/users/me/signatures/${signatureId}`, { method: 'DELETE' }, customFetch);
 }
 
+/** Propositions de signature écrivant le nom avec la police des signatures ; rien n'est enregistré. */
+export async function generateSignatures(payload: GenerateSignaturesPayload, customFetch?: typeof fetch): Promise<string[]> {
+    const response = await apiCall<GeneratedSignaturesResponse>('/users/me/signatures/generate', {
+        method: 'POST',
+        body: JSON.stringify(payload)
+    }, customFetch);
+    return response.candidates;
+}
+
+export async function updateSignature(signatureId: string, payload: UpdateSignaturePayload, customFetch?: typeof fetch): Promise<UserSignature> {
+    return await apiCall<UserSignature>(`/users/me/signatures/${signatureId}`, {
+        method: 'PATCH',
+        body: JSON.stringify(payload)
+    }, customFetch);
+}
+
 /** Supprime le compte de l'utilisateur connecté et toutes les données associées. Déconnecte ensuite côté client. */
 export async function deleteAccount(customFetch?: typeof fetch): Promise<void> {
     await apiCall<void>('/users/me', { method: 'DELETE' }, customFetch);
@@ -3,6 +3,7 @@ export interface PublicUserResponse {
     username: string;
     jwtExpiresAt?: string | null;
     jwtIsExpired?: boolean;
+    reserved?: boolean;
 }
 
 export interface LoginPayload {
@@ -18,7 +19,10 @@ export interface RegisterPayload {
 
 export interface SignPayload {
     ulids: string[];
-    url: string;
+    /** Lien scanné ; sinon `event_url` et `token` */
+    url?: string;
+    event_url?: string;
+    token?: string;
 }
 
 export type SignResponse = 
@@ -47,25 +51,72 @@ export interface UpdateUserPayload {
     username?: string | null;
     old_password?: string | null;
     new_password?: string | null;
+    signature_variation?: number | null;
+    signature_rotation?: SignatureRotation | null;
+    /** Chaîne vide pour ne plus épingler de signature */
+    pinned_signature_id?: string | null;
 }
 
+export type SignatureRotation = 'round_robin' | 'least_recently_used' | 'random' | 'pinned';
+
 export interface User {
     id: string;
     username: string;
     jwtExpiresAt?: string | null;
     jwtIntraEpitech?: string | null;
+    /** URL de l'image de la signature la plus récente */
     signatureManuscrite?: string | null;
+    signatureVariation?: number;
+    signatureRotation?: SignatureRotation;
+    pinnedSignatureId?: string | null;
 }
 
 export interface SaveSignaturePayload {
+    signature?: string | null;
+    /** Traits dessinés, chacun une liste de points [x, y] du canevas : le backend en garde le tracé vectoriel */
+    strokes?: SignatureStrokes | null;
+}
+
+export type SignatureStrokes = [number, number][][];
+
+export interface SignatureDrawing {
     signature: string;
+    strokes?: SignatureStrokes;
 }
 
 export interface UserSignature {
     id: string;
     userId: string;
-    signatureData: string;
     createdAt: string;
+    label?: string | null;
+    enabled: boolean;
+    useCount: number;
+    lastUsedAt?: string | null;
+    /** SHA-256 du PNG stocké (ETag de l'image) */
+    contentHash: string;
+    /** Enregistrée comme tracé vectoriel */
+    vector: boolean;
+    imageUrl: string;
+    thumbnailUrl: string;
+}
+
+export interface GenerateSignaturesPayload {
+    /** Nom à écrire (par défaut le nom d'utilisateur) */
+    name?: string | null;
+    /** Inclinaison en degrés (-30 à 30) */
+    slant?: number | null;
+    /** Hauteur du texte en pixels (40 à 220) */
+    size?: number | null;
+    count?: number | null;
+}
+
+export interface GeneratedSignaturesResponse {
+    candidates: string[];
+}
+
+export interface UpdateSignaturePayload {
+    label?: string | null;
+    enabled?: boolean | null;
 }
 
 export interface ValidateEdsquarePayload {
@@ -107,7 +158,25 @@ export interface EdsquareStatusResponse {
     has_signature: boolean;
     has_cookies: boolean;
     has_saved_credentials: boolean;
+    /** Reconnexion automatique suspendue après trop de refus : ressaisir le mot de passe */
+    credentials_suspended: boolean;
+    failed_login_count: number;
+    /** Dernier refus des identifiants (RFC 3339), null depuis la dernière connexion réussie */
+    last_failed_login_at: string | null;
     is_ready: boolean;
+    /** Dernier contrôle de session par la tâche de maintien (null si jamais contrôlé) */
+    session_health: EdsquareSessionHealth | null;
+}
+
+export interface EdsquareSessionHealth {
+    user_id: string;
+    username: string;
+    /** false : session expirée (ou sur le point de l'être) et non rafraîchie */
+    healthy: boolean;
+    /** true si une reconnexion avec les identifiants sauvegardés a été faite lors du contrôle */
+    refreshed: boolean;
+    message: string;
+    checked_at: string;
 }
 
 export interface EdsquareEligibleUser {